@@ -0,0 +1,202 @@
+//! `dimland alert`, pulsing the overlay in a color as a visual bell.
+//!
+//! An alert is drawn over the overlay instead of changing it, so the alpha, tint and
+//! everything else are left as they are, including changes made while it pulses.
+//! Alerts arriving while one pulses wait their turn, one in the same color as the last
+//! one waiting adds its pulses to it instead. Each pulse swells and fades along `--easing`.
+
+use std::{
+  collections::VecDeque,
+  time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  RegistrationToken,
+};
+
+use crate::{animation::Easing, render::Color, DimlandData, DimlandSettings};
+
+/// How long a pulse shows, and how long the overlay shows between two of them.
+const PULSE: Duration = Duration::from_millis(250);
+/// How often a pulse is drawn again while it swells and fades.
+const PULSE_STEP: Duration = Duration::from_millis(16);
+/// The least alpha an alert is drawn with, strong enough to notice out of the corner of an eye.
+pub const ALPHA: f32 = 0.8;
+pub const DEFAULT_COLOR: Color = Color([0xFF, 0x00, 0x00]);
+pub const DEFAULT_COUNT: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alert {
+  pub color: Color,
+  pub count: u32,
+}
+
+/// The alerts still to pulse, the first one pulsing right now.
+#[derive(Debug, Clone, Default)]
+pub struct Alerts {
+  queue: VecDeque<Alert>,
+  /// Whether a pulse shows right now.
+  on: bool,
+  /// When the pulse showing right now started.
+  started: Option<Instant>,
+  timer: Option<RegistrationToken>,
+}
+
+/// How strongly a pulse shows `elapsed` into it, swelling up to 1 halfway and fading back to 0.
+fn strength(elapsed: Duration, easing: Easing) -> f32 {
+  let t = elapsed.as_secs_f32() / PULSE.as_secs_f32();
+  easing.apply(1.0 - (2.0 * t - 1.0).abs())
+}
+
+impl Alerts {
+  /// Queues an alert, or adds its pulses to the last one waiting in the same color.
+  fn push(&mut self, alert: Alert) {
+    match self.queue.back_mut() {
+      Some(last) if last.color == alert.color => last.count += alert.count,
+      _ => self.queue.push_back(alert),
+    }
+  }
+
+  /// Moves on by half a pulse, false once all alerts are done.
+  fn step(&mut self) -> bool {
+    if !self.on {
+      self.on = !self.queue.is_empty();
+      return self.on;
+    }
+    self.on = false;
+    if let Some(first) = self.queue.front_mut() {
+      first.count -= 1;
+      if first.count == 0 {
+        self.queue.pop_front();
+      }
+    }
+    // the pause after the last pulse still shows the overlay before it is done
+    true
+  }
+
+  pub fn pulses_left(&self) -> u32 {
+    self.queue.iter().map(|alert| alert.count).sum()
+  }
+}
+
+impl DimlandSettings {
+  /// The color of the pulse showing right now with how strongly it shows, if any.
+  ///
+  /// A blackout is black, alerts keep counting down below it.
+  pub fn alert_color(&self) -> Option<([u8; 3], f32)> {
+    if !self.alerts.on || self.blacked_out.is_some() {
+      return None;
+    }
+    let strength = self
+      .alerts
+      .started
+      .map_or(1.0, |started| strength(started.elapsed(), self.easing));
+    self
+      .alerts
+      .queue
+      .front()
+      .map(|alert| (alert.color.0, strength))
+  }
+}
+
+impl DimlandData {
+  pub fn alert(&mut self, alert: Alert) {
+    if alert.count == 0 {
+      return;
+    }
+    self.settings.alerts.push(alert);
+    self
+      .settings
+      .watchers
+      .emit(&format!("alert {} {}", alert.color, alert.count));
+    if self.settings.alerts.timer.is_some() {
+      return;
+    }
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::immediate(), |_, _, data| data.step_alert())
+      .expect("can't start the alert timer");
+    self.settings.alerts.timer = Some(timer);
+  }
+
+  fn step_alert(&mut self) -> TimeoutAction {
+    let alerts = &mut self.settings.alerts;
+    let swelling = alerts.on
+      && alerts
+        .started
+        .is_some_and(|started| started.elapsed() < PULSE);
+    let going = swelling || alerts.step();
+    if alerts.on && !swelling {
+      alerts.started = Some(Instant::now());
+    }
+    let on = alerts.on;
+    // alerts aren't part of what views compare to know whether they are up to date
+    self.repaint_all();
+    if going {
+      return TimeoutAction::ToDuration(if on { PULSE_STEP } else { PULSE });
+    }
+    self.settings.alerts.timer = None;
+    self.settings.watchers.emit("alert end");
+    TimeoutAction::Drop
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BLUE: Color = Color([0x00, 0x00, 0xFF]);
+
+  fn pulses(alerts: &mut Alerts) -> Vec<Option<Color>> {
+    let mut shown = Vec::new();
+    while alerts.step() {
+      shown.push(alerts.on.then(|| alerts.queue.front().unwrap().color));
+    }
+    shown
+  }
+
+  #[test]
+  fn pulses_in_turn_and_ends_on_the_overlay() {
+    let mut alerts = Alerts::default();
+    alerts.push(Alert {
+      color: DEFAULT_COLOR,
+      count: 2,
+    });
+    alerts.push(Alert {
+      color: BLUE,
+      count: 1,
+    });
+    assert_eq!(alerts.pulses_left(), 3);
+    let red = Some(DEFAULT_COLOR);
+    assert_eq!(
+      pulses(&mut alerts),
+      [red, None, red, None, Some(BLUE), None]
+    );
+    assert!(!alerts.on);
+  }
+
+  #[test]
+  fn swells_and_fades() {
+    let at = |millis| strength(Duration::from_millis(millis), Easing::Linear);
+    assert_eq!(at(0), 0.0);
+    assert_eq!(at(125), 1.0);
+    assert_eq!(at(250), 0.0);
+    assert!(at(60) < at(100));
+    assert!(strength(Duration::from_millis(60), Easing::EaseIn) < at(60));
+  }
+
+  #[test]
+  fn the_same_color_adds_up() {
+    let mut alerts = Alerts::default();
+    for _ in 0..2 {
+      alerts.push(Alert {
+        color: BLUE,
+        count: 2,
+      });
+    }
+    assert_eq!(alerts.queue.len(), 1);
+    assert_eq!(pulses(&mut alerts).len(), 8);
+  }
+}
@@ -0,0 +1,116 @@
+//! Following the room brightness with `--ambient`.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  LoopHandle,
+};
+
+use crate::DimlandData;
+
+pub const DEFAULT_INTERVAL: u64 = 5;
+pub const DEFAULT_BRIGHT_LUX: f32 = 1000.0;
+pub const DEFAULT_CURVE: f32 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct AmbientSettings {
+  /// The alpha in a pitch black room.
+  pub darkest_alpha: f32,
+  /// The illuminance at which there is no dimming at all.
+  pub bright_lux: f32,
+  /// Exponent applied to the darkness before scaling the alpha with it.
+  pub curve: f32,
+  pub interval: Duration,
+}
+
+impl AmbientSettings {
+  /// Maps a brightness level between 0 and 1 to an alpha.
+  pub fn alpha(&self, level: f32) -> f32 {
+    let darkness = 1.0 - level.clamp(0.0, 1.0);
+    (self.darkest_alpha * darkness.powf(self.curve)).clamp(0.0, 1.0)
+  }
+}
+
+/// Where the brightness level comes from.
+enum Sensor {
+  /// An iio ambient light sensor, read in lux.
+  Light(PathBuf),
+  /// A backlight, whose level is a fallback guess at how bright the room is.
+  Backlight(PathBuf),
+}
+
+impl Sensor {
+  fn detect() -> Option<Self> {
+    let light = devices("/sys/bus/iio/devices").find(|device| {
+      device.join("in_illuminance_input").exists() || device.join("in_illuminance_raw").exists()
+    });
+    if let Some(device) = light {
+      return Some(Sensor::Light(device));
+    }
+
+    devices("/sys/class/backlight")
+      .find(|device| device.join("max_brightness").exists())
+      .map(Sensor::Backlight)
+  }
+
+  /// The current brightness between 0 and 1.
+  fn level(&self, bright_lux: f32) -> Option<f32> {
+    match self {
+      Sensor::Light(device) => {
+        let lux = read(&device.join("in_illuminance_input")).or_else(|| {
+          let raw = read(&device.join("in_illuminance_raw"))?;
+          let offset = read(&device.join("in_illuminance_offset")).unwrap_or(0.0);
+          let scale = read(&device.join("in_illuminance_scale")).unwrap_or(1.0);
+          Some((raw + offset) * scale)
+        })?;
+        // eyes are roughly logarithmic, so is this
+        Some((1.0 + lux.max(0.0)).ln() / (1.0 + bright_lux).ln())
+      }
+      Sensor::Backlight(device) => {
+        let brightness = read(&device.join("brightness"))?;
+        let max = read(&device.join("max_brightness"))?;
+        (max > 0.0).then(|| brightness / max)
+      }
+    }
+  }
+
+  fn path(&self) -> &Path {
+    match self {
+      Sensor::Light(device) | Sensor::Backlight(device) => device,
+    }
+  }
+}
+
+fn devices(dir: &str) -> impl Iterator<Item = PathBuf> {
+  fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| Some(entry.ok()?.path()))
+}
+
+fn read(path: &Path) -> Option<f32> {
+  fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Polls the sensor on a timer and feeds the result to [`DimlandData::set_alpha`].
+pub fn watch(handle: &LoopHandle<DimlandData>, settings: AmbientSettings) {
+  let Some(sensor) = Sensor::detect() else {
+    eprintln!("no ambient light sensor or backlight found, ignoring --ambient");
+    return;
+  };
+  eprintln!("following {}", sensor.path().display());
+
+  handle
+    .insert_source(Timer::immediate(), move |_, _, data| {
+      if let Some(level) = sensor.level(settings.bright_lux) {
+        data.set_alpha(settings.alpha(level));
+      }
+      TimeoutAction::ToDuration(settings.interval)
+    })
+    .expect("can't start the ambient timer");
+}
@@ -0,0 +1,263 @@
+//! Interpolating values over time, driven by frame callbacks.
+
+use std::{
+  str::FromStr,
+  time::{Duration, Instant},
+};
+
+use crate::render::mix;
+
+/// Iterations when solving a cubic bezier for its parameter, plenty for f32.
+const BEZIER_ITERATIONS: usize = 32;
+
+/// The curve progress follows over the duration of an animation, named like in CSS.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Easing {
+  #[default]
+  Linear,
+  /// `t²`, starting slow.
+  EaseIn,
+  /// `1 - (1 - t)²`, ending slow.
+  EaseOut,
+  /// `cubic-bezier(0.42,0,0.58,1)` like in CSS.
+  EaseInOut,
+  /// `cubic-bezier(x1,y1,x2,y2)`, the x values have to be between 0 and 1.
+  CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+  /// Maps linear progress to eased progress, 0 and 1 stay exactly where they are.
+  pub fn apply(self, t: f32) -> f32 {
+    if t <= 0.0 {
+      return 0.0;
+    }
+    if t >= 1.0 {
+      return 1.0;
+    }
+
+    match self {
+      Easing::Linear => t,
+      Easing::EaseIn => t * t,
+      Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+      Easing::EaseInOut => cubic_bezier(0.42, 0.0, 0.58, 1.0, t),
+      Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, t),
+    }
+  }
+}
+
+impl FromStr for Easing {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "linear" => return Ok(Easing::Linear),
+      "ease-in" => return Ok(Easing::EaseIn),
+      "ease-out" => return Ok(Easing::EaseOut),
+      "ease-in-out" => return Ok(Easing::EaseInOut),
+      _ => {}
+    }
+
+    let invalid = || {
+      format!("{s} is not an easing, use linear, ease-in, ease-out, ease-in-out or cubic-bezier(x1,y1,x2,y2)")
+    };
+    let points: Vec<f32> = s
+      .strip_prefix("cubic-bezier(")
+      .and_then(|s| s.strip_suffix(')'))
+      .ok_or_else(invalid)?
+      .split(',')
+      .map(|point| point.trim().parse().map_err(|_| invalid()))
+      .collect::<Result<_, _>>()?;
+    match points[..] {
+      [x1, y1, x2, y2] if (0.0..=1.0).contains(&x1) && (0.0..=1.0).contains(&x2) => {
+        Ok(Easing::CubicBezier(x1, y1, x2, y2))
+      }
+      [_, _, _, _] => Err(format!("the x values of {s} have to be between 0 and 1")),
+      _ => Err(invalid()),
+    }
+  }
+}
+
+/// The y of a bezier from 0,0 to 1,1 with the given control points where it passes x.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+  let bezier = |a: f32, b: f32, s: f32| {
+    let inverse = 1.0 - s;
+    3.0 * inverse * inverse * s * a + 3.0 * inverse * s * s * b + s * s * s
+  };
+
+  // x grows with the parameter as long as x1 and x2 are within 0 and 1, so bisect
+  let (mut low, mut high) = (0.0, 1.0);
+  for _ in 0..BEZIER_ITERATIONS {
+    let middle = (low + high) / 2.0;
+    if bezier(x1, x2, middle) < x {
+      low = middle;
+    } else {
+      high = middle;
+    }
+  }
+  bezier(y1, y2, (low + high) / 2.0)
+}
+
+/// Moves through colors over a period, blending each into the next and the last back into the first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorCycle {
+  pub colors: Vec<[u8; 3]>,
+  pub period: Duration,
+  pub start: Instant,
+}
+
+impl ColorCycle {
+  pub fn color(&self, now: Instant) -> [u8; 3] {
+    let cycles =
+      now.saturating_duration_since(self.start).as_secs_f32() / self.period.as_secs_f32();
+    let position = cycles.fract() * self.colors.len() as f32;
+    let index = position as usize % self.colors.len();
+    let next = (index + 1) % self.colors.len();
+    mix(self.colors[index], self.colors[next], position.fract())
+  }
+}
+
+/// How an animation runs, given as `DURATION[:EASING]` like `250ms:ease-out`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Transition {
+  /// Left out to use the default duration.
+  pub duration: Option<Duration>,
+  /// Left out to use the default easing.
+  pub easing: Option<Easing>,
+}
+
+impl FromStr for Transition {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (duration, easing) = s.split_once(':').unwrap_or((s, ""));
+    Ok(Self {
+      duration: match duration {
+        "" => None,
+        duration => Some(crate::control::parse_duration(duration)?),
+      },
+      easing: match easing {
+        "" => None,
+        easing => Some(easing.parse()?),
+      },
+    })
+  }
+}
+
+/// A value moving from one number to another along an easing curve.
+#[derive(Debug, Clone, Copy)]
+pub struct Tween {
+  from: f32,
+  to: f32,
+  start: Instant,
+  duration: Duration,
+  easing: Easing,
+}
+
+impl Tween {
+  pub fn new(from: f32, to: f32, duration: Duration, easing: Easing) -> Self {
+    Self {
+      from,
+      to,
+      start: Instant::now(),
+      duration,
+      easing,
+    }
+  }
+
+  /// How far along the tween is at `now`, between 0 and 1.
+  pub fn progress(&self, now: Instant) -> f32 {
+    if self.duration.is_zero() {
+      return 1.0;
+    }
+    (now.saturating_duration_since(self.start).as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+  }
+
+  pub fn value(&self, now: Instant) -> f32 {
+    self.from + (self.to - self.from) * self.easing.apply(self.progress(now))
+  }
+
+  pub fn finished(&self, now: Instant) -> bool {
+    self.progress(now) >= 1.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const EASINGS: [Easing; 6] = [
+    Easing::Linear,
+    Easing::EaseIn,
+    Easing::EaseOut,
+    Easing::EaseInOut,
+    Easing::CubicBezier(0.25, 0.1, 0.25, 1.0),
+    // overshooting y values still have to start and end in place
+    Easing::CubicBezier(0.68, -0.55, 0.27, 1.55),
+  ];
+
+  #[test]
+  fn endpoints_are_exact() {
+    for easing in EASINGS {
+      assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+      assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+      assert_eq!(easing.apply(-0.5), 0.0, "{easing:?}");
+      assert_eq!(easing.apply(1.5), 1.0, "{easing:?}");
+    }
+  }
+
+  #[test]
+  fn never_go_back() {
+    for easing in &EASINGS[..5] {
+      let mut last = 0.0;
+      for step in 0..=100 {
+        let eased = easing.apply(step as f32 / 100.0);
+        assert!(eased >= last, "{easing:?} goes back at {step}%");
+        last = eased;
+      }
+    }
+  }
+
+  #[test]
+  fn quadratic_in_and_out() {
+    assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+    assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+    assert_eq!(Easing::EaseIn.apply(0.1), 0.1 * 0.1);
+    // symmetric, so it passes the middle in the middle
+    assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-4);
+  }
+
+  #[test]
+  fn parse_easings() {
+    assert_eq!("ease-out".parse(), Ok(Easing::EaseOut));
+    assert_eq!(
+      "cubic-bezier(0.1, 0.7, 1, 0.1)".parse(),
+      Ok(Easing::CubicBezier(0.1, 0.7, 1.0, 0.1))
+    );
+    assert!("cubic-bezier(1.5,0,0,1)".parse::<Easing>().is_err());
+    assert!("cubic-bezier(0,0,1)".parse::<Easing>().is_err());
+    assert!("bouncy".parse::<Easing>().is_err());
+  }
+
+  #[test]
+  fn parse_transitions() {
+    assert_eq!(
+      "400ms:ease-in".parse(),
+      Ok(Transition {
+        duration: Some(Duration::from_millis(400)),
+        easing: Some(Easing::EaseIn),
+      })
+    );
+    assert_eq!(":linear".parse::<Transition>().unwrap().duration, None);
+    assert!("400ms:bouncy".parse::<Transition>().is_err());
+  }
+
+  #[test]
+  fn tweens_end_where_they_should() {
+    let tween = Tween::new(24.0, 0.0, Duration::from_millis(250), Easing::EaseOut);
+    assert_eq!(tween.value(tween.start), 24.0);
+    assert_eq!(tween.value(tween.start + Duration::from_millis(250)), 0.0);
+    assert!(tween.finished(tween.start + Duration::from_secs(1)));
+    let instant = Tween::new(0.0, 8.0, Duration::ZERO, Easing::Linear);
+    assert_eq!(instant.value(instant.start), 8.0);
+  }
+}
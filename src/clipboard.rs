@@ -0,0 +1,298 @@
+//! `--clipboard-indicator`, a small badge on the first output whenever something is copied.
+//!
+//! Regular clients only hear about the clipboard while they have keyboard focus, which the overlay
+//! never has, so this listens with zwlr_data_control_manager_v1 like `wl-paste --watch` does.
+//! The badge shows for a few seconds and then fades out, repainting only the pixels it covers.
+
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::{
+  calloop::{
+    timer::{TimeoutAction, Timer},
+    RegistrationToken,
+  },
+  client::{
+    event_created_child,
+    globals::GlobalList,
+    protocol::wl_seat::{self, WlSeat},
+    Connection, Dispatch, QueueHandle,
+  },
+  protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::{self, ZwlrDataControlManagerV1},
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+  },
+};
+
+use crate::{events, DimlandData};
+
+/// How long the badge shows before it starts fading.
+const SHOW: Duration = Duration::from_secs(3);
+const FADE: Duration = Duration::from_millis(500);
+const FADE_STEP: Duration = Duration::from_millis(33);
+/// Distance of the badge from the top left corner, the top right one is taken by `--fps-display`.
+const MARGIN: u32 = 16;
+/// Pixels of the badge around the icon.
+const PADDING: u32 = 6;
+/// Pixels of the badge per pixel of the icon.
+const SCALE: u32 = 2;
+const BACKGROUND: [u8; 3] = [0x4C, 0x7B, 0xD9];
+const FOREGROUND: [u8; 3] = [0xFF, 0xFF, 0xFF];
+/// A clipboard with lines of text on it, `#` is drawn in the foreground.
+const ICON: [&[u8; 10]; 12] = [
+  b"...####...",
+  b"##.#..#.##",
+  b"#..####..#",
+  b"#........#",
+  b"#.######.#",
+  b"#........#",
+  b"#.######.#",
+  b"#........#",
+  b"#.####...#",
+  b"#........#",
+  b"#........#",
+  b"##########",
+];
+
+pub struct Clipboard {
+  seat: WlSeat,
+  manager: ZwlrDataControlManagerV1,
+  device: ZwlrDataControlDeviceV1,
+  /// The compositor tells the current selection right away, which isn't a change yet.
+  seen_first: bool,
+  timer: Option<RegistrationToken>,
+}
+
+impl Clipboard {
+  pub fn bind(globals: &GlobalList, qh: &QueueHandle<DimlandData>) -> Option<Self> {
+    let manager: ZwlrDataControlManagerV1 = globals
+      .bind(qh, 1..=2, ())
+      .inspect_err(|_| {
+        eprintln!("zwlr_data_control_manager_v1 is not available, the clipboard isn't indicated")
+      })
+      .ok()?;
+    let seat: WlSeat = globals
+      .bind(qh, 1..=1, ())
+      .inspect_err(|_| eprintln!("there is no seat, the clipboard isn't indicated"))
+      .ok()?;
+    let device = manager.get_data_device(&seat, qh, ());
+    Some(Self {
+      seat,
+      manager,
+      device,
+      seen_first: false,
+      timer: None,
+    })
+  }
+}
+
+impl Drop for Clipboard {
+  fn drop(&mut self) {
+    self.device.destroy();
+    self.manager.destroy();
+    self.seat.release();
+  }
+}
+
+/// How opaque a badge shown at `start` is by now, none once it faded out.
+pub fn opacity(start: Instant, now: Instant) -> Option<f32> {
+  let fading = now.saturating_duration_since(start).checked_sub(SHOW);
+  match fading {
+    None => Some(1.0),
+    Some(fading) if fading < FADE => Some(1.0 - fading.as_secs_f32() / FADE.as_secs_f32()),
+    Some(_) => None,
+  }
+}
+
+/// The color of the badge at a pixel, none where it doesn't cover it.
+pub fn badge(x: u32, y: u32) -> Option<[u8; 3]> {
+  let (x, y) = (x.checked_sub(MARGIN)?, y.checked_sub(MARGIN)?);
+  let badge_width = ICON[0].len() as u32 * SCALE + 2 * PADDING;
+  let badge_height = ICON.len() as u32 * SCALE + 2 * PADDING;
+  if x >= badge_width || y >= badge_height {
+    return None;
+  }
+  let icon = |x: u32, y: u32| {
+    let (x, y) = (
+      x.checked_sub(PADDING)? / SCALE,
+      y.checked_sub(PADDING)? / SCALE,
+    );
+    ICON.get(y as usize)?.get(x as usize).copied()
+  };
+  Some(match icon(x, y) {
+    Some(b'#') => FOREGROUND,
+    _ => BACKGROUND,
+  })
+}
+
+/// Draws the badge over the dim with `opacity`.
+pub fn over(color: [u8; 3], alpha: f32, badge: [u8; 3], opacity: f32) -> ([u8; 3], f32) {
+  let combined = opacity + alpha * (1.0 - opacity);
+  if combined <= 0.0 {
+    return (color, 0.0);
+  }
+  let rgb = std::array::from_fn(|i| {
+    ((badge[i] as f32 * opacity + color[i] as f32 * alpha * (1.0 - opacity)) / combined).round()
+      as u8
+  });
+  (rgb, combined)
+}
+
+impl DimlandData {
+  /// Shows the badge on the first view, starting over if it already shows.
+  fn indicate_clipboard(&mut self) {
+    self.settings.watchers.emit("clipboard");
+    let Some(view) = self.views.first_mut() else {
+      return;
+    };
+    view.badge = Some(Instant::now());
+    // the badge isn't part of what views compare to know whether they are up to date
+    view.painted = None;
+    self.schedule_repaint();
+
+    let Some(clipboard) = &mut self.clipboard else {
+      return;
+    };
+    if let Some(timer) = clipboard.timer.take() {
+      self.loop_handle.remove(timer);
+    }
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::from_duration(SHOW), |_, _, data| {
+        data.step_clipboard_badge()
+      })
+      .expect("can't start the clipboard badge timer");
+    clipboard.timer = Some(timer);
+  }
+
+  fn step_clipboard_badge(&mut self) -> TimeoutAction {
+    let now = Instant::now();
+    let mut showing = false;
+    if let Some(view) = self.views.first_mut() {
+      view.badge = view.badge.filter(|&start| opacity(start, now).is_some());
+      view.painted = None;
+      showing = view.badge.is_some();
+      self.schedule_repaint();
+    }
+    if showing {
+      return TimeoutAction::ToDuration(FADE_STEP);
+    }
+    if let Some(clipboard) = &mut self.clipboard {
+      clipboard.timer = None;
+    }
+    TimeoutAction::Drop
+  }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for DimlandData {
+  fn event(
+    data: &mut Self,
+    device: &ZwlrDataControlDeviceV1,
+    event: zwlr_data_control_device_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(device, &event);
+    match event {
+      zwlr_data_control_device_v1::Event::Selection { id } => {
+        // nothing is read from the clipboard, only that it changed
+        if let Some(offer) = id {
+          offer.destroy();
+        }
+        let Some(clipboard) = &mut data.clipboard else {
+          return;
+        };
+        if std::mem::replace(&mut clipboard.seen_first, true) {
+          data.indicate_clipboard();
+        }
+      }
+      zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+      zwlr_data_control_device_v1::Event::Finished => {
+        eprintln!("the compositor stopped telling about the clipboard");
+        data.clipboard = None;
+      }
+      _ => {}
+    }
+  }
+
+  event_created_child!(DimlandData, ZwlrDataControlDeviceV1, [
+    zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+  ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    offer: &ZwlrDataControlOfferV1,
+    event: zwlr_data_control_offer_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(offer, &event);
+  }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    manager: &ZwlrDataControlManagerV1,
+    event: zwlr_data_control_manager_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(manager, &event);
+  }
+}
+
+impl Dispatch<WlSeat, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    seat: &WlSeat,
+    event: wl_seat::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(seat, &event);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fades_out_after_showing() {
+    let start = Instant::now();
+    assert_eq!(opacity(start, start), Some(1.0));
+    assert_eq!(opacity(start, start + SHOW), Some(1.0));
+    let half = opacity(start, start + SHOW + FADE / 2).unwrap();
+    assert!((half - 0.5).abs() < 0.01, "{half}");
+    assert_eq!(opacity(start, start + SHOW + FADE), None);
+  }
+
+  #[test]
+  fn sits_in_the_top_left_corner() {
+    // 32 wide and 36 high with the icon scaled up twice
+    assert_eq!(badge(MARGIN, MARGIN), Some(BACKGROUND));
+    assert_eq!(badge(MARGIN + 31, MARGIN + 35), Some(BACKGROUND));
+    assert_eq!(badge(MARGIN - 1, MARGIN), None);
+    assert_eq!(badge(MARGIN + 32, MARGIN), None);
+    assert_eq!(badge(MARGIN, MARGIN + 36), None);
+    // the top of the clip, 3 icon pixels in
+    assert_eq!(
+      badge(MARGIN + PADDING + 6, MARGIN + PADDING),
+      Some(FOREGROUND)
+    );
+  }
+
+  #[test]
+  fn fades_into_the_dim() {
+    let dim = [0x10, 0x20, 0x30];
+    assert_eq!(over(dim, 0.5, BACKGROUND, 1.0), (BACKGROUND, 1.0));
+    assert_eq!(over(dim, 0.5, BACKGROUND, 0.0), (dim, 0.5));
+  }
+}
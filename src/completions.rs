@@ -0,0 +1,130 @@
+//! Shell completions, generated from the clap definitions.
+//!
+//! clap only knows static values, so for arguments taking an output name the
+//! generated scripts get a small addition that asks `dimland list-outputs` for
+//! the currently connected outputs and suggests nothing if that fails.
+
+use std::io::Write;
+
+use clap::{Command, CommandFactory};
+use clap_complete::Shell;
+
+use crate::DimlandArgs;
+
+/// The value name marking arguments that get dynamic output name completion.
+pub const OUTPUT_VALUE_NAME: &str = "OUTPUT";
+
+pub fn print_completions(shell: Shell) {
+  let mut command = DimlandArgs::command();
+  let name = command.get_name().to_string();
+
+  let mut script = Vec::new();
+  clap_complete::generate(shell, &mut command, &name, &mut script);
+  let mut script = String::from_utf8(script).expect("clap generated garbage");
+
+  let flags = output_flags(&command);
+  if !flags.is_empty() {
+    match shell {
+      Shell::Bash => script.push_str(&bash_outputs(&name, &flags)),
+      Shell::Zsh => script = zsh_outputs(&name, script),
+      Shell::Fish => script.push_str(&fish_outputs(&name, &flags)),
+      _ => {}
+    }
+  }
+
+  std::io::stdout().write_all(script.as_bytes()).unwrap();
+}
+
+/// All spellings of the flags that take an output name, on the command and its subcommands.
+fn output_flags(command: &Command) -> Vec<String> {
+  let mut flags: Vec<String> = command
+    .get_arguments()
+    .filter(|arg| {
+      arg
+        .get_value_names()
+        .is_some_and(|names| names.iter().any(|name| name == OUTPUT_VALUE_NAME))
+    })
+    .flat_map(|arg| {
+      let long = arg.get_long().map(|long| format!("--{long}"));
+      let short = arg.get_short().map(|short| format!("-{short}"));
+      long.into_iter().chain(short)
+    })
+    .chain(command.get_subcommands().flat_map(output_flags))
+    .collect();
+  // the scripts complete by the flag alone, whichever subcommand it belongs to
+  flags.sort();
+  flags.dedup();
+  flags
+}
+
+fn bash_outputs(name: &str, flags: &[String]) -> String {
+  let function = name.replace('-', "__");
+  let flags = flags.join("|");
+  format!(
+    r#"
+_{function}_dynamic() {{
+    case "${{COMP_WORDS[COMP_CWORD-1]}}" in
+        {flags})
+            COMPREPLY=($(compgen -W "$({name} list-outputs 2>/dev/null)" -- "${{COMP_WORDS[COMP_CWORD]}}"))
+            ;;
+        *)
+            _{function} "$@"
+            ;;
+    esac
+}}
+complete -F _{function}_dynamic -o nosort -o bashdefault -o default {name}
+"#
+  )
+}
+
+/// zsh completes values through an action in the argument spec, so the
+/// generated default action for output values is swapped for our own, which
+/// has to be defined before the script runs `_dimland` at the very end.
+fn zsh_outputs(name: &str, script: String) -> String {
+  let function = format!("_{}_outputs", name.replace('-', "_"));
+  let script = script.replace(
+    &format!(":{OUTPUT_VALUE_NAME}:_default"),
+    &format!(":{OUTPUT_VALUE_NAME}:{function}"),
+  );
+  let definition = format!(
+    r#"(( $+functions[{function}] )) ||
+{function}() {{
+    local -a outputs
+    outputs=(${{(f)"$({name} list-outputs 2>/dev/null)"}})
+    _describe 'output' outputs
+}}
+
+"#
+  );
+
+  match script.rfind("if [ \"$funcstack[1]\"") {
+    Some(index) => format!("{}{definition}{}", &script[..index], &script[index..]),
+    None => script + &definition,
+  }
+}
+
+fn fish_outputs(name: &str, flags: &[String]) -> String {
+  flags
+    .iter()
+    .map(|flag| {
+      let option = match flag.strip_prefix("--") {
+        Some(long) => format!("-l {long}"),
+        None => format!("-s {}", &flag[1..]),
+      };
+      format!("complete -c {name} {option} -f -a \"({name} list-outputs 2>/dev/null)\"\n")
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn output_flags_of_subcommands() {
+    let flags = output_flags(&DimlandArgs::command());
+    assert!(flags.contains(&"--only".to_string()));
+    assert!(flags.contains(&"--output".to_string()));
+    assert_eq!(flags.iter().filter(|flag| *flag == "--output").count(), 1);
+  }
+}
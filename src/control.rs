@@ -0,0 +1,1392 @@
+//! The control socket that lets `dimland set` and friends talk to a running dimland.
+//!
+//! Every connection carries a single line with a text command, which is answered
+//! with the result before the connection is closed. The exception is `watch`,
+//! which keeps the connection open and streams an event per line to it.
+//! Lines starting with `{` are requests of the JSON protocol in [`dimland::rpc`] instead.
+
+use std::{
+  cell::RefCell,
+  fmt::Write as _,
+  io::{ErrorKind, Read, Write},
+  os::unix::{
+    io::FromRawFd,
+    net::{UnixListener, UnixStream},
+  },
+  path::{Path, PathBuf},
+  rc::Rc,
+  str::FromStr,
+  time::{Duration, Instant, SystemTime},
+};
+
+use clap::ValueEnum;
+use dimland::rpc;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use smithay_client_toolkit::reexports::calloop::{
+  generic::Generic,
+  timer::{TimeoutAction, Timer},
+  Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+};
+
+use crate::{
+  alert::{self, Alert},
+  image::Image,
+  progress,
+  ramp::RampEnd,
+  render::{Color, Curtain, Radius},
+  DimlandData, DimlandSettings, Extent,
+};
+
+pub const DEFAULT_STEP: f32 = 0.1;
+
+/// Longest command line accepted before the client is cut off.
+pub const MAX_COMMAND_LENGTH: usize = 4096;
+
+/// Events a watcher may fall behind by before new ones are dropped for it.
+const MAX_WATCHER_BACKLOG: usize = 4096;
+
+/// A command as sent over the socket, one per line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+  /// `set [alpha <value>|+<step>|-<step>] [radius <value>] [curtain <change>] [image <path>|none] [force] [for <duration>]`,
+  /// can also start with `alpha` or `radius` directly, or `set color <color>|none output <name>`,
+  /// or `set region <name> [alpha ...] [radius ...] [color ...]` for a named region
+  Set(SetCommand),
+  /// `toggle [for <duration>]`
+  Toggle { duration: Option<Duration> },
+  /// `increase [step]`
+  Increase(f32),
+  /// `decrease [step]`
+  Decrease(f32),
+  /// `cancel-override`
+  CancelOverride,
+  /// `status`
+  Status,
+  /// `watch`, keeps the connection open for events
+  Watch,
+  /// `metrics`
+  Metrics,
+  /// `stats`
+  Stats,
+  /// `profile <name>`
+  Profile(String),
+  /// `blackout [for <duration>]` or `blackout off`
+  Blackout {
+    off: bool,
+    duration: Option<Duration>,
+  },
+  /// `ramp to <alpha> over <duration> [then flash|hold|revert]` or `ramp pause|resume|cancel`
+  Ramp(RampCommand),
+  /// `alert [color <color>] [count <n>]`
+  Alert(Alert),
+  /// `progress <value> [edge <edge>] [color <color>] [for <duration>]`
+  Progress {
+    bar: progress::Bar,
+    duration: Option<Duration>,
+  },
+  /// `strip [y <pixels>] [height <pixels>]` or `strip off`
+  Strip {
+    off: bool,
+    y: Option<u32>,
+    height: Option<u32>,
+  },
+}
+
+impl ControlCommand {
+  /// Whether the command only reads the state, which doesn't end `--start-idle`.
+  pub fn is_query(&self) -> bool {
+    matches!(
+      self,
+      ControlCommand::Status
+        | ControlCommand::Watch
+        | ControlCommand::Metrics
+        | ControlCommand::Stats
+    )
+  }
+
+  /// Whether the command hides, shows or dims the overlay, which `--blackout` can't.
+  pub fn changes_alpha(&self) -> bool {
+    match self {
+      ControlCommand::Set(set) => set.alpha.is_some() && set.region.is_none(),
+      ControlCommand::Toggle { .. }
+      | ControlCommand::Increase(_)
+      | ControlCommand::Decrease(_)
+      | ControlCommand::Ramp(RampCommand::Start { .. }) => true,
+      _ => false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RampCommand {
+  Start {
+    to: f32,
+    over: Duration,
+    end: RampEnd,
+  },
+  Pause,
+  Resume,
+  Cancel,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetCommand {
+  pub alpha: Option<AlphaChange>,
+  pub radius: Option<Radius>,
+  pub curtain: Option<CurtainChange>,
+  /// A png to crossfade to, or none to take the image down.
+  pub image: Option<Option<PathBuf>>,
+  /// A color for `output` alone, or none to go back to the usual one.
+  pub color: Option<Option<Color>>,
+  pub output: Option<String>,
+  /// A named region to change instead of the whole overlay.
+  pub region: Option<String>,
+  /// Ignore `--min-alpha` and `--max-alpha`.
+  pub force: bool,
+  /// Revert to the current state after this long.
+  pub duration: Option<Duration>,
+}
+
+impl FromStr for ControlCommand {
+  type Err = String;
+
+  fn from_str(line: &str) -> Result<Self, Self::Err> {
+    let mut words = line.split_whitespace().peekable();
+    let command = *words.peek().ok_or("empty command")?;
+    if command != "alpha" && command != "radius" {
+      words.next();
+    }
+
+    let parsed = match command {
+      "set" | "alpha" | "radius" => {
+        let mut set = SetCommand::default();
+        while let Some(word) = words.next() {
+          match word {
+            "alpha" => set.alpha = Some(parse_value(word, words.next())?),
+            "radius" => set.radius = Some(parse_value(word, words.next())?),
+            "curtain" => set.curtain = Some(parse_value(word, words.next())?),
+            "image" => set.image = Some(parse_image(words.next())?),
+            "color" => set.color = Some(parse_color(words.next())?),
+            "output" => set.output = Some(words.next().ok_or("output needs a name")?.to_string()),
+            "region" => set.region = Some(words.next().ok_or("region needs a name")?.to_string()),
+            "force" => set.force = true,
+            "for" => {
+              set.duration = Some(parse_duration(words.next().ok_or("for needs a duration")?)?)
+            }
+            _ => return Err(format!("unexpected {word}")),
+          }
+        }
+        set.check()?;
+        ControlCommand::Set(set)
+      }
+      "toggle" => ControlCommand::Toggle {
+        duration: match words.next() {
+          Some("for") => Some(parse_duration(words.next().ok_or("for needs a duration")?)?),
+          Some(word) => return Err(format!("unexpected {word}")),
+          None => None,
+        },
+      },
+      "increase" => ControlCommand::Increase(parse_step(words.next())?),
+      "decrease" => ControlCommand::Decrease(parse_step(words.next())?),
+      "cancel-override" => ControlCommand::CancelOverride,
+      "status" => ControlCommand::Status,
+      "watch" => ControlCommand::Watch,
+      "metrics" => ControlCommand::Metrics,
+      "stats" => ControlCommand::Stats,
+      "profile" => ControlCommand::Profile(words.next().ok_or("profile needs a name")?.to_string()),
+      "ramp" => ControlCommand::Ramp(match words.next() {
+        Some("pause") => RampCommand::Pause,
+        Some("resume") => RampCommand::Resume,
+        Some("cancel") => RampCommand::Cancel,
+        Some("to") => {
+          let to = parse_value("to", words.next())?;
+          if words.next() != Some("over") {
+            return Err("ramp needs over and a duration".to_string());
+          }
+          let over = parse_duration(words.next().ok_or("over needs a duration")?)?;
+          let end = match words.next() {
+            Some("then") => parse_ramp_end(words.next().ok_or("then needs an action")?)?,
+            Some(word) => return Err(format!("unexpected {word}")),
+            None => RampEnd::default(),
+          };
+          RampCommand::Start { to, over, end }
+        }
+        Some(word) => return Err(format!("unexpected {word}")),
+        None => return Err("ramp needs to, pause, resume or cancel".to_string()),
+      }),
+      "progress" => {
+        let mut bar = progress::Bar {
+          value: check_progress(parse_value("progress", words.next())?)?,
+          edge: progress::DEFAULT_EDGE,
+          color: progress::DEFAULT_COLOR,
+        };
+        let mut duration = None;
+        while let Some(word) = words.next() {
+          match word {
+            "edge" => bar.edge = parse_value(word, words.next())?,
+            "color" => bar.color = parse_value(word, words.next())?,
+            "for" => duration = Some(parse_duration(words.next().ok_or("for needs a duration")?)?),
+            _ => return Err(format!("unexpected {word}")),
+          }
+        }
+        ControlCommand::Progress { bar, duration }
+      }
+      "strip" => {
+        let (mut off, mut y, mut height) = (false, None, None);
+        while let Some(word) = words.next() {
+          match word {
+            "off" => off = true,
+            "y" => y = Some(parse_value(word, words.next())?),
+            "height" => height = Some(check_strip_height(parse_value(word, words.next())?)?),
+            _ => return Err(format!("unexpected {word}")),
+          }
+        }
+        if off && (y.is_some() || height.is_some()) {
+          return Err("off takes no y or height".to_string());
+        }
+        ControlCommand::Strip { off, y, height }
+      }
+      "alert" => {
+        let mut alert = Alert {
+          color: alert::DEFAULT_COLOR,
+          count: alert::DEFAULT_COUNT,
+        };
+        while let Some(word) = words.next() {
+          match word {
+            "color" => alert.color = parse_value(word, words.next())?,
+            "count" => alert.count = parse_value(word, words.next())?,
+            _ => return Err(format!("unexpected {word}")),
+          }
+        }
+        if alert.count == 0 {
+          return Err("count has to be at least 1".to_string());
+        }
+        ControlCommand::Alert(alert)
+      }
+      "blackout" => match words.next() {
+        Some("off") => ControlCommand::Blackout {
+          off: true,
+          duration: None,
+        },
+        Some("for") => ControlCommand::Blackout {
+          off: false,
+          duration: Some(parse_duration(words.next().ok_or("for needs a duration")?)?),
+        },
+        Some(word) => return Err(format!("unexpected {word}")),
+        None => ControlCommand::Blackout {
+          off: false,
+          duration: None,
+        },
+      },
+      _ => return Err(format!("unknown command {command}")),
+    };
+
+    match words.next() {
+      Some(word) => Err(format!("unexpected {word}")),
+      None => Ok(parsed),
+    }
+  }
+}
+
+impl SetCommand {
+  /// Whether it changes anything, and nothing but the color of a single output
+  /// or what a named region can have of its own.
+  fn check(&self) -> Result<(), String> {
+    if self.region.is_some() {
+      if self.curtain.is_some() || self.image.is_some() || self.output.is_some() {
+        return Err("only the alpha, radius and color can be set for a region".to_string());
+      }
+      if self.duration.is_some() {
+        return Err("a region takes no for".to_string());
+      }
+      if self.alpha.is_none() && self.radius.is_none() && self.color.is_none() {
+        return Err("region needs alpha, radius or color".to_string());
+      }
+      return Ok(());
+    }
+    let global = self.alpha.is_some()
+      || self.radius.is_some()
+      || self.curtain.is_some()
+      || self.image.is_some();
+    // --for only goes back to the alpha, radius and whether it's hidden
+    if self.curtain.is_some() && self.duration.is_some() {
+      return Err("a curtain takes no for".to_string());
+    }
+    if self.image.is_some() && self.duration.is_some() {
+      return Err("an image takes no for".to_string());
+    }
+    match (&self.color, &self.output) {
+      (None, None) if !global => Err("set needs alpha, radius, curtain or image".to_string()),
+      (None, None) => Ok(()),
+      (Some(_), None) => Err("color needs an output or a region".to_string()),
+      (None, Some(_)) => Err("output needs a color".to_string()),
+      (Some(_), Some(_)) if global => Err("only the color can be set for one output".to_string()),
+      // there is nothing to go back to for a color of its own
+      (Some(_), Some(_)) if self.duration.is_some() => {
+        Err("a color for one output takes no for".to_string())
+      }
+      (Some(_), Some(_)) => Ok(()),
+    }
+  }
+}
+
+fn parse_ramp_end(word: &str) -> Result<RampEnd, String> {
+  RampEnd::from_str(word, false).map_err(|_| format!("{word} is not flash, hold or revert"))
+}
+
+fn parse_image(word: Option<&str>) -> Result<Option<PathBuf>, String> {
+  match word.ok_or("image needs a path or none")? {
+    "none" => Ok(None),
+    path => Ok(Some(PathBuf::from(path))),
+  }
+}
+
+fn check_progress(value: f32) -> Result<f32, String> {
+  match (0.0..=1.0).contains(&value) {
+    true => Ok(value),
+    false => Err(format!("{value} is not a progress from 0 to 1")),
+  }
+}
+
+fn check_strip_height(height: u32) -> Result<u32, String> {
+  match height {
+    0 => Err("height has to be at least 1".to_string()),
+    height => Ok(height),
+  }
+}
+
+fn parse_color(word: Option<&str>) -> Result<Option<Color>, String> {
+  match word.ok_or("color needs a color or none")? {
+    "none" => Ok(None),
+    color => color.parse().map(Some),
+  }
+}
+
+fn parse_value<T: FromStr>(key: &str, word: Option<&str>) -> Result<T, String> {
+  let word = word.ok_or_else(|| format!("{key} needs a value"))?;
+  word
+    .parse()
+    .map_err(|_| format!("{word} is not a valid {key}"))
+}
+
+fn parse_step(word: Option<&str>) -> Result<f32, String> {
+  word.map_or(Ok(DEFAULT_STEP), |word| parse_value("step", Some(word)))
+}
+
+/// Parses durations like `90`, `250ms`, `30s`, `10m` or `1.5h`, plain numbers are seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+  let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+    Some(index) => s.split_at(index),
+    None => (s, "s"),
+  };
+  let seconds = match unit {
+    "ms" => 0.001,
+    "s" => 1.0,
+    "m" => 60.0,
+    "h" => 3600.0,
+    _ => return Err(format!("unknown unit {unit}, use ms, s, m or h")),
+  };
+  match number.parse::<f64>() {
+    Ok(number) if number >= 0.0 => Ok(Duration::from_secs_f64(number * seconds)),
+    _ => Err(format!("{s} is not a duration")),
+  }
+}
+
+/// A runtime change to the alpha, so scripts can step it without asking for it first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaChange {
+  /// `0.6`.
+  Set(f32),
+  /// `+0.1` or `-0.1`, clamped like any other change.
+  Step(f32),
+}
+
+impl AlphaChange {
+  pub fn apply(self, alpha: f32) -> f32 {
+    match self {
+      AlphaChange::Set(alpha) => alpha,
+      AlphaChange::Step(step) => alpha + step,
+    }
+  }
+}
+
+impl FromStr for AlphaChange {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let change = match s.starts_with(['+', '-']) {
+      true => AlphaChange::Step,
+      false => AlphaChange::Set,
+    };
+    s.parse()
+      .map(change)
+      .map_err(|_| format!("{s} is neither an alpha like 0.6 nor a step like +0.1"))
+  }
+}
+
+impl std::fmt::Display for AlphaChange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AlphaChange::Set(alpha) => write!(f, "{alpha}"),
+      AlphaChange::Step(step) => write!(f, "{step:+}"),
+    }
+  }
+}
+
+/// A runtime change to the curtain, so a key can drag it step by step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurtainChange {
+  /// `bottom:40%`, or `none` to take it down.
+  Set(Option<Curtain>),
+  /// `+5%` or `-20`, moving the inner edge in the unit the curtain has.
+  Move(f32, bool),
+}
+
+impl CurtainChange {
+  pub fn apply(self, curtain: Option<Curtain>) -> Result<Option<Curtain>, String> {
+    let (amount, percent) = match self {
+      CurtainChange::Set(curtain) => return Ok(curtain),
+      CurtainChange::Move(amount, percent) => (amount, percent),
+    };
+    let mut curtain = curtain.ok_or("there is no curtain to move")?;
+    curtain.extent = match (curtain.extent, percent) {
+      (Extent::Percent(current), true) => Extent::Percent((current + amount).clamp(0.0, 100.0)),
+      (Extent::Pixels(current), false) => Extent::Pixels((current as f32 + amount).max(0.0) as u32),
+      (Extent::Percent(_), false) => return Err("the curtain is in percent".to_string()),
+      (Extent::Pixels(_), true) => return Err("the curtain is in pixels".to_string()),
+    };
+    Ok(Some(curtain))
+  }
+}
+
+impl FromStr for CurtainChange {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s == "none" {
+      return Ok(CurtainChange::Set(None));
+    }
+    if !s.starts_with(['+', '-']) {
+      return s.parse().map(|curtain| CurtainChange::Set(Some(curtain)));
+    }
+
+    let (amount, percent) = match s.strip_suffix('%') {
+      Some(amount) => (amount, true),
+      None => (s, false),
+    };
+    amount
+      .parse()
+      .map(|amount| CurtainChange::Move(amount, percent))
+      .map_err(|_| format!("{s} is neither a curtain like bottom:40% nor a step like +5%"))
+  }
+}
+
+impl std::fmt::Display for CurtainChange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CurtainChange::Set(Some(curtain)) => write!(f, "{curtain}"),
+      CurtainChange::Set(None) => write!(f, "none"),
+      CurtainChange::Move(amount, percent) => {
+        write!(f, "{amount:+}{}", if *percent { "%" } else { "" })
+      }
+    }
+  }
+}
+
+/// What a temporary override goes back to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuntimeState {
+  pub alpha: f32,
+  pub radius: Radius,
+  pub hidden: bool,
+}
+
+impl RuntimeState {
+  pub fn of(settings: &DimlandSettings) -> Self {
+    Self {
+      alpha: settings.alpha,
+      radius: settings.radius,
+      hidden: settings.hidden,
+    }
+  }
+
+  /// The state once a blackout or pending override went back, the current one without them.
+  pub fn lasting(settings: &DimlandSettings) -> Self {
+    let blackout = settings
+      .blacked_out
+      .as_ref()
+      .map(|blackout| blackout.previous);
+    let pending = settings
+      .pending_override
+      .as_ref()
+      .map(|pending| pending.previous);
+    blackout.or(pending).unwrap_or_else(|| Self::of(settings))
+  }
+}
+
+/// A runtime blackout, lifted by `blackout off` or when its timer fires.
+#[derive(Debug, Clone)]
+pub struct Blackout {
+  previous: RuntimeState,
+  timer: RegistrationToken,
+  until: Instant,
+}
+
+/// An override that reverts itself when its timer fires.
+#[derive(Debug, Clone)]
+pub struct PendingOverride {
+  previous: RuntimeState,
+  timer: RegistrationToken,
+  until: Instant,
+}
+
+impl DimlandData {
+  /// Runs a command and returns what to answer with.
+  pub fn handle_command(&mut self, command: ControlCommand) -> Result<String, String> {
+    // the buffer has no alpha channel and the compositor skips what is below
+    if self.settings.blackout && command.changes_alpha() {
+      return Err(
+        "the outputs are blacked out with --blackout, restart dimland without it to dim"
+          .to_string(),
+      );
+    }
+    if !command.is_query() {
+      self.wake();
+    }
+    match command {
+      ControlCommand::Set(set) => {
+        if let Some(region) = &set.region {
+          self.change_region(region, set.alpha, set.force, set.radius, set.color)?;
+          return Ok(String::new());
+        }
+        // everything that can fail comes first, so a rejected command changes nothing
+        let curtain = set
+          .curtain
+          .map(|change| change.apply(self.settings.curtain))
+          .transpose()?;
+        let image = match &set.image {
+          Some(path) => Some((
+            path.as_deref(),
+            path.as_deref().map(Image::load).transpose()?,
+          )),
+          None => None,
+        };
+
+        if let Some(duration) = set.duration {
+          self.start_override(duration);
+        }
+        match set.alpha.map(|change| change.apply(self.settings.alpha)) {
+          Some(alpha) if set.force => self.force_alpha(alpha),
+          Some(alpha) => self.set_alpha(alpha),
+          None => {}
+        }
+        if let Some(radius) = set.radius {
+          self.set_radius(radius);
+        }
+        if let Some(curtain) = curtain {
+          self.set_curtain(curtain);
+        }
+        if let Some((path, image)) = image {
+          self.set_image(path, image);
+        }
+        if let (Some(color), Some(output)) = (set.color, &set.output) {
+          self.set_output_color(output, color)?;
+        }
+      }
+      ControlCommand::Toggle { duration } => {
+        if let Some(duration) = duration {
+          self.start_override(duration);
+        }
+        self.set_hidden(!self.settings.hidden);
+      }
+      ControlCommand::Increase(step) => self.set_alpha(self.settings.alpha + step),
+      ControlCommand::Decrease(step) => self.set_alpha(self.settings.alpha - step),
+      ControlCommand::CancelOverride => {
+        let pending = self
+          .settings
+          .pending_override
+          .take()
+          .ok_or("no override to cancel")?;
+        self.loop_handle.remove(pending.timer);
+        self.restore(pending.previous);
+      }
+      ControlCommand::Status => return Ok(self.status()),
+      ControlCommand::Metrics => return Ok(self.metrics()),
+      ControlCommand::Stats => return Ok(self.settings.stats.get().to_string()),
+      ControlCommand::Profile(name) => self.set_profile(&name)?,
+      ControlCommand::Blackout { off: true, .. } => {
+        let blackout = self
+          .settings
+          .blacked_out
+          .take()
+          .ok_or("no blackout to lift")?;
+        self.loop_handle.remove(blackout.timer);
+        self.lift_blackout(blackout);
+      }
+      ControlCommand::Blackout {
+        off: false,
+        duration,
+      } => self.start_blackout(duration.unwrap_or(self.settings.blackout_timeout)),
+      ControlCommand::Ramp(RampCommand::Start { to, over, end }) => self.start_ramp(to, over, end),
+      ControlCommand::Ramp(RampCommand::Pause) => self.pause_ramp()?,
+      ControlCommand::Ramp(RampCommand::Resume) => self.resume_ramp()?,
+      ControlCommand::Ramp(RampCommand::Cancel) => self.cancel_ramp()?,
+      ControlCommand::Alert(alert) => self.alert(alert),
+      ControlCommand::Progress { bar, duration } => {
+        self.show_progress(bar, duration.unwrap_or(progress::DEFAULT_TIMEOUT))
+      }
+      ControlCommand::Strip { off: true, .. } => self.remove_strip()?,
+      ControlCommand::Strip { y, height, .. } => self.move_strip(y, height),
+      ControlCommand::Watch => return Err("watch needs its own connection".to_string()),
+    }
+    Ok(String::new())
+  }
+
+  /// Remembers the current state and arms a timer to go back to it.
+  ///
+  /// A new override replaces a pending one, but still goes back to the state
+  /// from before the first.
+  fn start_override(&mut self, duration: Duration) {
+    let previous = match self.settings.pending_override.take() {
+      Some(pending) => {
+        self.loop_handle.remove(pending.timer);
+        pending.previous
+      }
+      None => RuntimeState::of(&self.settings),
+    };
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::from_duration(duration), |_, _, data| {
+        if let Some(pending) = data.settings.pending_override.take() {
+          data.restore(pending.previous);
+        }
+        TimeoutAction::Drop
+      })
+      .expect("can't start the override timer");
+
+    self.settings.pending_override = Some(PendingOverride {
+      previous,
+      timer,
+      until: Instant::now() + duration,
+    });
+  }
+
+  /// Covers every output in opaque black and takes all input, until `duration` is up at the latest.
+  ///
+  /// A pending override is folded in, so lifting the blackout goes back to the state from before it.
+  fn start_blackout(&mut self, duration: Duration) {
+    let previous = match self.settings.blacked_out.take() {
+      Some(blackout) => {
+        self.loop_handle.remove(blackout.timer);
+        blackout.previous
+      }
+      None => match self.settings.pending_override.take() {
+        Some(pending) => {
+          self.loop_handle.remove(pending.timer);
+          pending.previous
+        }
+        None => RuntimeState::of(&self.settings),
+      },
+    };
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::from_duration(duration), |_, _, data| {
+        if let Some(blackout) = data.settings.blacked_out.take() {
+          eprintln!("the blackout timed out, lifting it");
+          data.lift_blackout(blackout);
+        }
+        TimeoutAction::Drop
+      })
+      .expect("can't start the blackout timer");
+
+    let started = self.settings.blacked_out.is_none();
+    self.settings.blacked_out = Some(Blackout {
+      previous,
+      timer,
+      until: Instant::now() + duration,
+    });
+    if !started {
+      return;
+    }
+
+    self.force_alpha(1.0);
+    self.set_radius(Radius::default());
+    self.set_hidden(false);
+    for view in &self.views {
+      self.set_grab(view, true);
+    }
+    self.refresh_alpha_factors();
+    self.settings.watchers.emit("blackout on");
+    self.schedule_repaint();
+  }
+
+  fn lift_blackout(&mut self, blackout: Blackout) {
+    for view in &self.views {
+      self.set_grab(view, false);
+    }
+    self.restore(blackout.previous);
+    self.refresh_alpha_factors();
+    self.settings.watchers.emit("blackout off");
+    self.schedule_repaint();
+  }
+
+  fn restore(&mut self, state: RuntimeState) {
+    self.force_alpha(state.alpha);
+    self.set_radius(state.radius);
+    self.set_hidden(state.hidden);
+  }
+
+  /// What `status` answers in the JSON protocol, the text one tells a bit more.
+  fn status_report(&self) -> rpc::Status {
+    rpc::Status {
+      alpha: self.settings.alpha,
+      radius: self.settings.radius.to_string(),
+      curtain: self.settings.curtain.map(|curtain| curtain.to_string()),
+      image: self
+        .settings
+        .image
+        .as_ref()
+        .map(|image| image.path.display().to_string()),
+      min_alpha: self.settings.min_alpha,
+      max_alpha: self.settings.max_alpha,
+      hidden: self.settings.hidden,
+      profile: self.settings.profile.clone(),
+      override_left: self.settings.pending_override.as_ref().map(|pending| {
+        pending
+          .until
+          .saturating_duration_since(Instant::now())
+          .as_secs()
+      }),
+      ramp: self.settings.ramp.as_ref().map(|ramp| rpc::RampStatus {
+        from: ramp.tween.from,
+        to: ramp.tween.to,
+        progress: ramp.tween.progress(SystemTime::now()),
+        paused: ramp.tween.paused(),
+      }),
+      blackout_left: self.settings.blacked_out.as_ref().map(|blackout| {
+        blackout
+          .until
+          .saturating_duration_since(Instant::now())
+          .as_secs()
+      }),
+      outputs: self
+        .views
+        .iter()
+        .filter_map(|view| self.output_state.info(&view.output)?.name)
+        .collect(),
+      alert_pulses: self.settings.alerts.pulses_left(),
+      strip: self.settings.strip.map(|strip| rpc::StripStatus {
+        y: strip.y,
+        height: strip.height,
+      }),
+      colors: self
+        .views
+        .iter()
+        .filter_map(|view| {
+          let name = self.output_state.info(&view.output)?.name?;
+          let color = match view.color {
+            Some(color) if self.settings.blacked_out.is_none() => color,
+            _ => self.settings.overlay_color(),
+          };
+          Some((name, Color(color).to_string()))
+        })
+        .collect(),
+      color_sources: self
+        .views
+        .iter()
+        .filter_map(|view| {
+          let info = self.output_state.info(&view.output)?;
+          let source = self.settings.color_source(&info)?;
+          Some((info.name?, source.to_string()))
+        })
+        .collect(),
+      regions: self
+        .settings
+        .named_regions
+        .iter()
+        .map(|(name, region)| {
+          let status = rpc::RegionStatus {
+            alpha: self.settings.region_alpha(Some(name)),
+            radius: self.settings.region_radius(Some(name)).to_string(),
+            color: region.color.map(|color| color.to_string()),
+          };
+          (name.clone(), status)
+        })
+        .collect(),
+    }
+  }
+
+  pub fn status(&self) -> String {
+    let report = self.status_report();
+    let mut status = String::new();
+    writeln!(status, "alpha {:.2}", report.alpha).unwrap();
+    writeln!(status, "radius {}", report.radius).unwrap();
+    if let Some(curtain) = &report.curtain {
+      writeln!(status, "curtain {curtain}").unwrap();
+    }
+    if let Some(image) = &report.image {
+      writeln!(status, "image {image}").unwrap();
+    }
+    writeln!(status, "min-alpha {:.2}", report.min_alpha).unwrap();
+    writeln!(status, "max-alpha {:.2}", report.max_alpha).unwrap();
+    writeln!(status, "hidden {}", report.hidden).unwrap();
+    if let Some(profile) = &report.profile {
+      writeln!(status, "profile {profile}").unwrap();
+    }
+    if let Some(left) = report.override_left {
+      writeln!(status, "override {left}s left").unwrap();
+    }
+    if let Some(ramp) = &report.ramp {
+      writeln!(
+        status,
+        "ramp {:.2}→{:.2} {:.0}%{}",
+        ramp.from,
+        ramp.to,
+        ramp.progress * 100.0,
+        if ramp.paused { " paused" } else { "" }
+      )
+      .unwrap();
+    }
+    if report.alert_pulses > 0 {
+      writeln!(status, "alert {} pulses left", report.alert_pulses).unwrap();
+    }
+    if let Some(left) = report.blackout_left {
+      writeln!(status, "blackout {left}s left").unwrap();
+    }
+    if let Some(strip) = self.settings.strip {
+      writeln!(status, "strip {strip}").unwrap();
+    }
+    writeln!(status, "outputs {}", report.outputs.join(" ")).unwrap();
+    for view in &self.views {
+      let (Some(name), Some(radius)) = (
+        self
+          .output_state
+          .info(&view.output)
+          .and_then(|info| info.name),
+        view.effective_radius(view.current_radius),
+      ) else {
+        continue;
+      };
+      writeln!(status, "effective-radius {name} {radius}").unwrap();
+    }
+    for (name, color) in &report.colors {
+      match report.color_sources.get(name) {
+        Some(source) => writeln!(status, "color {name} {color} {source}").unwrap(),
+        None => writeln!(status, "color {name} {color}").unwrap(),
+      }
+    }
+    for (name, region) in &report.regions {
+      write!(
+        status,
+        "region {name} alpha {:.2} radius {}",
+        region.alpha, region.radius
+      )
+      .unwrap();
+      if let Some(color) = &region.color {
+        write!(status, " color {color}").unwrap();
+      }
+      writeln!(status).unwrap();
+    }
+    if let Some(workspaces) = &self.settings.workspaces {
+      for (output, workspace) in &workspaces.visible {
+        writeln!(status, "workspace {output} {workspace}").unwrap();
+      }
+    }
+    status
+  }
+}
+
+impl DimlandData {
+  /// Answers a line of the JSON protocol.
+  pub fn handle_rpc(&mut self, line: &str) -> rpc::Response {
+    let request = serde_json::from_str::<Value>(line)
+      .map_err(|err| rpc::Error::new(rpc::PARSE_ERROR, err.to_string()))
+      .and_then(|value| {
+        serde_json::from_value::<rpc::Request>(value)
+          .map_err(|err| rpc::Error::new(rpc::INVALID_REQUEST, err.to_string()))
+      });
+    let request = match request {
+      Ok(request) => request,
+      Err(error) => return rpc::Response::error(None, error),
+    };
+    let id = request.id.clone();
+    match self.call(request) {
+      Ok(result) => rpc::Response::result(id, result),
+      Err(error) => rpc::Response::error(id, error),
+    }
+  }
+
+  /// Turns a request into the command the text protocol would have sent and runs it.
+  fn call(&mut self, request: rpc::Request) -> Result<Value, rpc::Error> {
+    let command = match request.method.as_str() {
+      "hello" => {
+        let hello: rpc::HelloParams = params(request.params)?;
+        if hello.version != rpc::VERSION {
+          return Err(rpc::Error::new(
+            rpc::UNSUPPORTED_VERSION,
+            format!("dimland speaks version {} of the protocol", rpc::VERSION),
+          ));
+        }
+        return Ok(
+          serde_json::to_value(rpc::HelloResult {
+            version: rpc::VERSION,
+          })
+          .unwrap(),
+        );
+      }
+      "set" => {
+        let set: rpc::SetParams = params(request.params)?;
+        let set = SetCommand {
+          alpha: set.alpha.map(AlphaChange::Set),
+          radius: set
+            .radius
+            .map(|radius| radius.parse())
+            .transpose()
+            .map_err(invalid_params)?,
+          curtain: set
+            .curtain
+            .map(|curtain| curtain.parse())
+            .transpose()
+            .map_err(invalid_params)?,
+          image: set
+            .image
+            .map(|image| parse_image(Some(&image)))
+            .transpose()
+            .map_err(invalid_params)?,
+          color: set
+            .color
+            .map(|color| parse_color(Some(&color)))
+            .transpose()
+            .map_err(invalid_params)?,
+          output: set.output,
+          region: set.region,
+          force: set.force,
+          duration: parse_for(set.duration)?,
+        };
+        set.check().map_err(invalid_params)?;
+        ControlCommand::Set(set)
+      }
+      "toggle" => {
+        let toggle: rpc::ToggleParams = params(request.params)?;
+        ControlCommand::Toggle {
+          duration: parse_for(toggle.duration)?,
+        }
+      }
+      "increase" => {
+        let step: rpc::StepParams = params(request.params)?;
+        ControlCommand::Increase(step.step.unwrap_or(DEFAULT_STEP))
+      }
+      "decrease" => {
+        let step: rpc::StepParams = params(request.params)?;
+        ControlCommand::Decrease(step.step.unwrap_or(DEFAULT_STEP))
+      }
+      "cancel-override" => ControlCommand::CancelOverride,
+      "status" => return Ok(serde_json::to_value(self.status_report()).unwrap()),
+      "metrics" => ControlCommand::Metrics,
+      "stats" => ControlCommand::Stats,
+      "profile" => {
+        let profile: rpc::ProfileParams = params(request.params)?;
+        ControlCommand::Profile(profile.name)
+      }
+      "ramp" => {
+        let ramp: rpc::RampParams = params(request.params)?;
+        ControlCommand::Ramp(RampCommand::Start {
+          to: ramp.to,
+          over: parse_duration(&ramp.over).map_err(invalid_params)?,
+          end: ramp
+            .then
+            .as_deref()
+            .map(parse_ramp_end)
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or_default(),
+        })
+      }
+      "ramp-pause" => ControlCommand::Ramp(RampCommand::Pause),
+      "ramp-resume" => ControlCommand::Ramp(RampCommand::Resume),
+      "ramp-cancel" => ControlCommand::Ramp(RampCommand::Cancel),
+      "blackout" => {
+        let blackout: rpc::BlackoutParams = params(request.params)?;
+        if blackout.off && blackout.duration.is_some() {
+          return Err(invalid_params("off takes no duration"));
+        }
+        ControlCommand::Blackout {
+          off: blackout.off,
+          duration: parse_for(blackout.duration)?,
+        }
+      }
+      "progress" => {
+        let bar: rpc::ProgressParams = params(request.params)?;
+        ControlCommand::Progress {
+          bar: progress::Bar {
+            value: check_progress(bar.value).map_err(invalid_params)?,
+            edge: bar
+              .edge
+              .map(|edge| edge.parse())
+              .transpose()
+              .map_err(invalid_params)?
+              .unwrap_or(progress::DEFAULT_EDGE),
+            color: bar
+              .color
+              .map(|color| color.parse())
+              .transpose()
+              .map_err(invalid_params)?
+              .unwrap_or(progress::DEFAULT_COLOR),
+          },
+          duration: parse_for(bar.duration)?,
+        }
+      }
+      "strip" => {
+        let strip: rpc::StripParams = params(request.params)?;
+        if strip.off && (strip.y.is_some() || strip.height.is_some()) {
+          return Err(invalid_params("off takes no y or height"));
+        }
+        ControlCommand::Strip {
+          off: strip.off,
+          y: strip.y,
+          height: strip
+            .height
+            .map(check_strip_height)
+            .transpose()
+            .map_err(invalid_params)?,
+        }
+      }
+      "alert" => {
+        let alert: rpc::AlertParams = params(request.params)?;
+        let alert = Alert {
+          color: alert
+            .color
+            .map(|color| color.parse())
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or(alert::DEFAULT_COLOR),
+          count: alert.count.unwrap_or(alert::DEFAULT_COUNT),
+        };
+        if alert.count == 0 {
+          return Err(invalid_params("count has to be at least 1"));
+        }
+        ControlCommand::Alert(alert)
+      }
+      "watch" => {
+        return Err(rpc::Error::new(
+          rpc::UNSUPPORTED,
+          "watch is only available as a text command",
+        ))
+      }
+      method => {
+        return Err(rpc::Error::new(
+          rpc::METHOD_NOT_FOUND,
+          format!("no method {method}"),
+        ))
+      }
+    };
+
+    match self.handle_command(command) {
+      Ok(answer) if answer.is_empty() => Ok(Value::Null),
+      Ok(answer) => Ok(Value::String(answer)),
+      Err(err) => Err(rpc::Error::new(rpc::FAILED, err)),
+    }
+  }
+}
+
+/// Reads the params of a method, where left out params count as an empty object.
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, rpc::Error> {
+  let params = match params {
+    Value::Null => Value::Object(Default::default()),
+    params => params,
+  };
+  serde_json::from_value(params).map_err(|err| invalid_params(err.to_string()))
+}
+
+fn invalid_params(message: impl Into<String>) -> rpc::Error {
+  rpc::Error::new(rpc::INVALID_PARAMS, message)
+}
+
+fn parse_for(duration: Option<String>) -> Result<Option<Duration>, rpc::Error> {
+  duration
+    .map(|duration| parse_duration(&duration))
+    .transpose()
+    .map_err(invalid_params)
+}
+
+/// Where the socket lives, inside the runtime dir of the user.
+pub fn socket_path() -> PathBuf {
+  let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_else(|| "/tmp".into());
+  PathBuf::from(runtime_dir).join("dimland.sock")
+}
+
+/// Sends a command to the running dimland and returns its answer.
+pub fn send(command: &str) -> Result<String, String> {
+  let path = socket_path();
+  let mut stream = UnixStream::connect(&path).map_err(|err| {
+    format!(
+      "can't reach dimland at {} ({err}), is it running?",
+      path.display()
+    )
+  })?;
+  writeln!(stream, "{command}").map_err(|err| err.to_string())?;
+  stream
+    .shutdown(std::net::Shutdown::Write)
+    .map_err(|err| err.to_string())?;
+
+  let mut answer = String::new();
+  stream
+    .read_to_string(&mut answer)
+    .map_err(|err| err.to_string())?;
+  match answer.strip_prefix("error: ") {
+    Some(err) => Err(err.trim_end().to_string()),
+    None => Ok(answer),
+  }
+}
+
+/// Prints the events of the running dimland as they happen, until it exits.
+pub fn watch() -> Result<(), String> {
+  let path = socket_path();
+  let mut stream = UnixStream::connect(&path).map_err(|err| {
+    format!(
+      "can't reach dimland at {} ({err}), is it running?",
+      path.display()
+    )
+  })?;
+  writeln!(stream, "watch").map_err(|err| err.to_string())?;
+
+  let mut stdout = std::io::stdout();
+  std::io::copy(&mut stream, &mut stdout).map_err(|err| err.to_string())?;
+  Ok(())
+}
+
+/// The connections of `watch` clients, kept across reconnects.
+#[derive(Debug, Clone, Default)]
+pub struct Watchers(Rc<RefCell<Vec<Watcher>>>);
+
+#[derive(Debug)]
+struct Watcher {
+  stream: UnixStream,
+  /// Whole lines the client hasn't read yet.
+  backlog: Vec<u8>,
+}
+
+impl Watchers {
+  fn add(&self, stream: UnixStream) {
+    self.0.borrow_mut().push(Watcher {
+      stream,
+      backlog: Vec::new(),
+    });
+  }
+
+  /// Sends an event line to every watcher without blocking.
+  ///
+  /// Watchers that don't keep up miss events instead of piling them up here,
+  /// watchers that hung up are forgotten.
+  pub fn emit(&self, event: &str) {
+    self.0.borrow_mut().retain_mut(|watcher| {
+      if watcher.backlog.len() + event.len() < MAX_WATCHER_BACKLOG {
+        watcher.backlog.extend_from_slice(event.as_bytes());
+        watcher.backlog.push(b'\n');
+      }
+
+      while !watcher.backlog.is_empty() {
+        match watcher.stream.write(&watcher.backlog) {
+          Ok(0) => return false,
+          Ok(written) => {
+            watcher.backlog.drain(..written);
+          }
+          Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+          Err(_) => return false,
+        }
+      }
+      true
+    });
+  }
+}
+
+/// Removes the socket when dimland exits, unless systemd handed it over.
+pub struct ControlSocket {
+  path: Option<PathBuf>,
+}
+
+impl Drop for ControlSocket {
+  fn drop(&mut self) {
+    if let Some(path) = &self.path {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+/// The first file descriptor systemd passes, see sd_listen_fds(3).
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Takes over the socket systemd bound for us with socket activation, if it did.
+///
+/// Picks the one named `control` in `LISTEN_FDNAMES`, or the first one.
+/// The variables are removed so processes spawned later don't take it for theirs.
+fn activated_listener() -> Option<UnixListener> {
+  let pid = std::env::var("LISTEN_PID").ok()?;
+  let fds = std::env::var("LISTEN_FDS").ok()?;
+  let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+  for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+    std::env::remove_var(var);
+  }
+  if pid.parse() != Ok(std::process::id()) {
+    return None;
+  }
+  let fds: i32 = fds.parse().ok().filter(|&fds| fds > 0)?;
+
+  let index = names
+    .split(':')
+    .position(|name| name == "control")
+    .map_or(0, |index| index as i32);
+  if index >= fds {
+    return None;
+  }
+  let fd = SD_LISTEN_FDS_START + index;
+  // SAFETY: systemd passes us the fds from SD_LISTEN_FDS_START on and nothing else owns them
+  unsafe {
+    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+    Some(UnixListener::from_raw_fd(fd))
+  }
+}
+
+/// Starts answering commands on the socket, the one from systemd if it was socket activated.
+pub fn listen(handle: &LoopHandle<'static, DimlandData>) -> Option<ControlSocket> {
+  let (listener, path) = match activated_listener() {
+    // systemd keeps the socket around to start us again, so it isn't ours to remove
+    Some(listener) => (listener, None),
+    None => {
+      let path = socket_path();
+      (bind(&path)?, Some(path))
+    }
+  };
+  listener.set_nonblocking(true).unwrap();
+
+  let clients = handle.clone();
+  handle
+    .insert_source(
+      Generic::new(listener, Interest::READ, Mode::Level),
+      move |_, listener, _| {
+        while let Ok((stream, _)) = listener.accept() {
+          accept(&clients, stream);
+        }
+        Ok(PostAction::Continue)
+      },
+    )
+    .expect("can't listen on the control socket");
+
+  Some(ControlSocket { path })
+}
+
+/// Binds the socket, unless another dimland is already listening on it.
+fn bind(path: &Path) -> Option<UnixListener> {
+  let listener = match UnixListener::bind(path) {
+    Ok(listener) => listener,
+    Err(err) if err.kind() == ErrorKind::AddrInUse => {
+      if UnixStream::connect(path).is_ok() {
+        eprintln!(
+          "another dimland is listening on {}, running without control socket",
+          path.display()
+        );
+        return None;
+      }
+      // left behind by a dimland that didn't exit cleanly
+      std::fs::remove_file(path).ok()?;
+      UnixListener::bind(path).ok()?
+    }
+    Err(err) => {
+      eprintln!("can't listen on {} ({err})", path.display());
+      return None;
+    }
+  };
+  Some(listener)
+}
+
+/// Collects a command line from the client without blocking the event loop.
+fn accept(handle: &LoopHandle<'static, DimlandData>, stream: UnixStream) {
+  if stream.set_nonblocking(true).is_err() {
+    return;
+  }
+
+  let mut line = Vec::new();
+  let replies = handle.clone();
+  let _ = handle.insert_source(
+    Generic::new(stream, Interest::READ, Mode::Level),
+    move |_, stream, data| {
+      let mut chunk = [0; 256];
+      let finished = loop {
+        match (&**stream).read(&mut chunk) {
+          Ok(0) => break true,
+          Ok(read) => {
+            line.extend_from_slice(&chunk[..read]);
+            if line.contains(&b'\n') || line.len() > MAX_COMMAND_LENGTH {
+              break true;
+            }
+          }
+          Err(err) if err.kind() == ErrorKind::WouldBlock => break false,
+          Err(_) => return Ok(PostAction::Remove),
+        }
+      };
+      if !finished {
+        return Ok(PostAction::Continue);
+      }
+
+      let line = String::from_utf8_lossy(&line);
+      let line = line.lines().next().unwrap_or_default();
+      if line.trim_start().starts_with('{') {
+        let response = data.handle_rpc(line);
+        let mut answer = serde_json::to_string(&response).unwrap();
+        answer.push('\n');
+        reply(&replies, stream, answer);
+        return Ok(PostAction::Remove);
+      }
+      let command = line.parse();
+      if command == Ok(ControlCommand::Watch) {
+        if let Ok(stream) = stream.try_clone() {
+          data.settings.watchers.add(stream);
+        }
+        return Ok(PostAction::Remove);
+      }
+      let answer = command
+        .and_then(|command| data.handle_command(command))
+        .unwrap_or_else(|err| format!("error: {err}\n"));
+      reply(&replies, stream, answer);
+      Ok(PostAction::Remove)
+    },
+  );
+}
+
+/// Sends an answer without blocking the event loop, the rest of it
+/// follows whenever a slow client reads.
+fn reply(handle: &LoopHandle<'static, DimlandData>, stream: &UnixStream, answer: String) {
+  let mut pending = answer.into_bytes();
+  if flush(stream, &mut pending) {
+    return;
+  }
+  let Ok(stream) = stream.try_clone() else {
+    return;
+  };
+  let _ = handle.insert_source(
+    Generic::new(stream, Interest::WRITE, Mode::Level),
+    move |_, stream, _| match flush(stream, &mut pending) {
+      true => Ok(PostAction::Remove),
+      false => Ok(PostAction::Continue),
+    },
+  );
+}
+
+/// Writes as much of `pending` as the client takes right now,
+/// true once all of it is out or the client hung up.
+fn flush(mut stream: &UnixStream, pending: &mut Vec<u8>) -> bool {
+  while !pending.is_empty() {
+    match stream.write(pending) {
+      Ok(0) => return true,
+      Ok(written) => {
+        pending.drain(..written);
+      }
+      Err(err) if err.kind() == ErrorKind::WouldBlock => return false,
+      Err(_) => return true,
+    }
+  }
+  true
+}
@@ -0,0 +1,157 @@
+//! Writing rendered buffers to disk for bug reports and previews.
+
+use std::{
+  fs::File,
+  io::BufWriter,
+  path::{Path, PathBuf},
+};
+
+use smithay_client_toolkit::{output::OutputInfo, reexports::client::Connection};
+
+use crate::{
+  outputs::{self, Rect},
+  render::{self, Frame, PixelFormat},
+  DimlandSettings,
+};
+
+/// Renders the buffer every output would get and writes it next to `path`,
+/// with the output name added to the file name, and the number of the rectangle with `--geometry`
+/// or named regions.
+pub fn render_debug(conn: &Connection, settings: &DimlandSettings, path: &Path) {
+  let infos: Vec<_> = outputs::query_outputs(conn)
+    .into_iter()
+    .map(|info| settings.transformed(info))
+    .collect();
+  for info in &infos {
+    if !settings.wants_output(Some(info)) {
+      continue;
+    }
+    for (index, (rect, region)) in settings.view_rects(Some(info)).into_iter().enumerate() {
+      let path = output_path(path, info, rect.map(|_| index));
+      render_view(settings, info, &infos, rect, region.as_deref(), &path);
+    }
+  }
+}
+
+fn render_view(
+  settings: &DimlandSettings,
+  info: &OutputInfo,
+  infos: &[OutputInfo],
+  rect: Option<Rect>,
+  region: Option<&str>,
+  path: &Path,
+) {
+  let (width, height) = match rect {
+    Some(rect) => (rect.width, rect.height),
+    None => settings.overlay_size(info.logical_size),
+  };
+
+  let mut canvas = vec![0; width as usize * height as usize * 4];
+  let alpha = match settings.mirror_alpha {
+    true => settings.region_alpha(region) * outputs::mirror_alpha_factor(info, infos),
+    false => settings.region_alpha(region),
+  };
+  let frame = Frame {
+    width,
+    height,
+    format: PixelFormat::Argb8888,
+    alpha,
+    radius: settings.region_radius(region),
+    underlay: None,
+    span: settings
+      .span
+      .then(|| outputs::span(info, infos))
+      .flatten()
+      .map(|span| rect.map_or(span, |rect| rect.within(span))),
+    color: settings.region_color(region, Some(info)),
+    badge: None,
+  };
+  render::render(&mut canvas, frame, settings);
+
+  if let Err(err) = write_png(path, &canvas, width, height) {
+    eprintln!("{err}");
+    std::process::exit(1);
+  }
+  eprintln!("wrote {width}x{height} buffer to {}", path.display());
+}
+
+/// Renders the buffer an output of `size` would get and writes it to `path`,
+/// without touching Wayland, so looks can be tried out without dimming the screen.
+pub fn preview(settings: &DimlandSettings, path: &Path, size: (u32, u32)) {
+  let (width, height) = settings.overlay_size(Some((size.0 as i32, size.1 as i32)));
+  if let Err(err) = settings.check_buffer(width, height) {
+    eprintln!("{err}");
+    std::process::exit(1);
+  }
+  let mut canvas = vec![0; width as usize * height as usize * 4];
+  let frame = Frame {
+    width,
+    height,
+    format: PixelFormat::Argb8888,
+    alpha: settings.alpha,
+    radius: settings.radius,
+    underlay: None,
+    span: None,
+    color: None,
+    badge: None,
+  };
+  render::render(&mut canvas, frame, settings);
+  if let Err(err) = write_png(path, &canvas, width, height) {
+    eprintln!("{err}");
+    std::process::exit(1);
+  }
+  eprintln!("wrote {width}x{height} buffer to {}", path.display());
+}
+
+/// Parses a size like `1920x1080`.
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+  let invalid = || format!("{s} is not a size like 1920x1080");
+  let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+  let width: u32 = width.parse().map_err(|_| invalid())?;
+  let height: u32 = height.parse().map_err(|_| invalid())?;
+  if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+    return Err(invalid());
+  }
+  Ok((width, height))
+}
+
+fn output_path(path: &Path, info: &OutputInfo, rect: Option<usize>) -> PathBuf {
+  let mut name = info
+    .name
+    .clone()
+    .unwrap_or_else(|| format!("output{}", info.id));
+  if let Some(rect) = rect {
+    name += &format!("-{rect}");
+  }
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let extension = path.extension().unwrap_or("png".as_ref()).to_string_lossy();
+  path.with_file_name(format!("{stem}-{name}.{extension}"))
+}
+
+/// Writes a premultiplied Argb8888 canvas as a straight-alpha RGBA png.
+pub fn write_png(path: &Path, canvas: &[u8], width: u32, height: u32) -> Result<(), String> {
+  let pixels: Vec<u8> = canvas
+    .chunks_exact(4)
+    .flat_map(|chunk| {
+      let [b, g, r, a] = [chunk[0], chunk[1], chunk[2], chunk[3]];
+      let unpremultiply = |c: u8| {
+        if a == 0 {
+          0
+        } else {
+          (c as u32 * 255 / a as u32) as u8
+        }
+      };
+      [unpremultiply(r), unpremultiply(g), unpremultiply(b), a]
+    })
+    .collect();
+
+  let file =
+    File::create(path).map_err(|err| format!("can't create {} ({err})", path.display()))?;
+  let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+  encoder.set_color(png::ColorType::Rgba);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(&pixels))
+    .map_err(|err| format!("can't write {} ({err})", path.display()))
+}
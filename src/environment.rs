@@ -0,0 +1,89 @@
+//! `--watch-env`, picking up `DIMLAND_ALPHA` from the environment again on SIGUSR2.
+
+use std::{
+  fs::{self, File},
+  io::Read,
+  os::fd::{FromRawFd, OwnedFd},
+};
+
+use smithay_client_toolkit::reexports::calloop::{
+  generic::Generic, Interest, LoopHandle, Mode, PostAction,
+};
+
+use crate::DimlandData;
+
+/// Where the environment of the process can be read back from, null separated.
+const ENVIRON: &str = "/proc/self/environ";
+
+/// Listens for SIGUSR2 and applies the variables it finds in the environment then.
+///
+/// Has to be called before any threads are spawned, since it blocks the signal
+/// to receive it through a signalfd instead.
+pub fn watch(handle: &LoopHandle<DimlandData>) {
+  let Some(signals) = signal_fd(&[libc::SIGUSR2]) else {
+    eprintln!(
+      "can't watch for SIGUSR2 ({}), ignoring --watch-env",
+      std::io::Error::last_os_error()
+    );
+    return;
+  };
+
+  handle
+    .insert_source(
+      Generic::new(signals, Interest::READ, Mode::Level),
+      |_, signals, data| {
+        let mut info = [0; std::mem::size_of::<libc::signalfd_siginfo>()];
+        // several signals may have piled up, one reread covers all of them
+        while (&**signals).read(&mut info).is_ok_and(|read| read > 0) {}
+        data.apply_environment();
+        Ok(PostAction::Continue)
+      },
+    )
+    .expect("can't watch for SIGUSR2");
+}
+
+/// Blocks `signals` and returns a nonblocking file they can be read from instead.
+pub fn signal_fd(signals: &[libc::c_int]) -> Option<File> {
+  // SAFETY: the set is initialized by sigemptyset before use and outlives both calls
+  let fd = unsafe {
+    let mut set: libc::sigset_t = std::mem::zeroed();
+    libc::sigemptyset(&mut set);
+    for &signal in signals {
+      libc::sigaddset(&mut set, signal);
+    }
+    if libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) != 0 {
+      return None;
+    }
+    libc::signalfd(-1, &set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC)
+  };
+  // SAFETY: a nonnegative result is a new file descriptor nothing else owns
+  (fd >= 0).then(|| File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+/// Reads a variable from the environment as it is now, rather than at startup.
+fn variable(name: &str) -> Option<String> {
+  let environ = fs::read(ENVIRON).ok()?;
+  let prefix = format!("{name}=");
+  environ
+    .split(|&byte| byte == 0)
+    .find_map(|entry| entry.strip_prefix(prefix.as_bytes()))
+    .map(|value| String::from_utf8_lossy(value).into_owned())
+}
+
+impl DimlandData {
+  /// Applies `DIMLAND_ALPHA` if it is set, within `--min-alpha` and `--max-alpha`.
+  fn apply_environment(&mut self) {
+    if variable("DIMLAND_COLOR").is_some() {
+      eprintln!(
+        "ignoring DIMLAND_COLOR, the tint is picked with --prefer-dark-mode or --prefer-light-mode"
+      );
+    }
+    let Some(alpha) = variable("DIMLAND_ALPHA") else {
+      return;
+    };
+    match alpha.trim().parse() {
+      Ok(alpha) => self.set_alpha(alpha),
+      Err(_) => eprintln!("DIMLAND_ALPHA={alpha} is not a valid alpha"),
+    }
+  }
+}
@@ -0,0 +1,131 @@
+//! What can go wrong while setting dimland up, so `main` can say what to do about it before exiting.
+
+use smithay_client_toolkit::{
+  reexports::{
+    calloop,
+    client::{
+      globals::{BindError, GlobalError},
+      protocol::wl_shm::Format,
+      ConnectError, DispatchError,
+    },
+  },
+  shm::CreatePoolError,
+};
+
+#[derive(Debug)]
+pub enum DimlandError {
+  /// There is no compositor to talk to.
+  Connect(ConnectError),
+  /// The compositor went away before telling which globals it has.
+  Registry(GlobalError),
+  /// The connection broke while waiting for the compositor to answer.
+  Dispatch(DispatchError),
+  /// The event loop couldn't take the connection or stopped running.
+  EventLoop(calloop::Error),
+  /// The compositor lacks a global dimland can't do without.
+  MissingGlobal {
+    interface: &'static str,
+    source: BindError,
+  },
+  /// The shared memory for a buffer couldn't be set up.
+  Shm {
+    width: u32,
+    height: u32,
+    source: CreatePoolError,
+  },
+  /// A buffer would take more than `--max-buffer-bytes`.
+  BufferTooLarge {
+    width: u32,
+    height: u32,
+    max_bytes: u64,
+  },
+  /// A file dimland was pointed at is missing or broken, with what is wrong with it.
+  InvalidConfig(String),
+  /// The compositor offers none of the shm formats dimland can draw with, only these.
+  NoShmFormat { offered: Vec<Format> },
+}
+
+impl std::fmt::Display for DimlandError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DimlandError::Connect(err) => write!(f, "can't connect to the compositor ({err})"),
+      DimlandError::Registry(err) => write!(f, "can't list the globals of the compositor ({err})"),
+      DimlandError::Dispatch(err) => write!(f, "lost the compositor ({err})"),
+      DimlandError::EventLoop(err) => write!(f, "the event loop failed ({err})"),
+      DimlandError::MissingGlobal { interface, source } => {
+        write!(f, "the compositor doesn't support {interface} ({source})")
+      }
+      DimlandError::Shm {
+        width,
+        height,
+        source,
+      } => write!(f, "can't allocate a {width}x{height} buffer ({source})"),
+      DimlandError::BufferTooLarge {
+        width,
+        height,
+        max_bytes,
+      } => write!(
+        f,
+        "a {width}x{height} buffer would take more than --max-buffer-bytes {max_bytes}"
+      ),
+      DimlandError::InvalidConfig(err) => write!(f, "{err}"),
+      DimlandError::NoShmFormat { offered } if offered.is_empty() => {
+        write!(f, "the compositor offers no shm formats at all")
+      }
+      DimlandError::NoShmFormat { offered } => {
+        let offered: Vec<String> = offered.iter().map(|format| format!("{format:?}")).collect();
+        write!(
+          f,
+          "the compositor offers none of the shm formats dimland draws with, only {}",
+          offered.join(", ")
+        )
+      }
+    }
+  }
+}
+
+impl std::error::Error for DimlandError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      DimlandError::Connect(err) => Some(err),
+      DimlandError::Registry(err) => Some(err),
+      DimlandError::Dispatch(err) => Some(err),
+      DimlandError::EventLoop(err) => Some(err),
+      DimlandError::MissingGlobal { source, .. } => Some(source),
+      DimlandError::Shm { source, .. } => Some(source),
+      DimlandError::BufferTooLarge { .. }
+      | DimlandError::InvalidConfig(_)
+      | DimlandError::NoShmFormat { .. } => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn says_what_is_too_large() {
+    let err = DimlandError::BufferTooLarge {
+      width: 30720,
+      height: 17280,
+      max_bytes: 512,
+    };
+    assert_eq!(
+      err.to_string(),
+      "a 30720x17280 buffer would take more than --max-buffer-bytes 512"
+    );
+    assert!(std::error::Error::source(&err).is_none());
+  }
+
+  #[test]
+  fn names_the_offered_formats() {
+    let err = DimlandError::NoShmFormat {
+      offered: vec![Format::Rgb565, Format::Bgr888],
+    };
+    assert_eq!(
+      err.to_string(),
+      "the compositor offers none of the shm formats dimland draws with, only Rgb565, Bgr888"
+    );
+  }
+}
@@ -0,0 +1,35 @@
+//! `--verbose-events`, a line on stderr for every event that reaches one of dimland's own `Dispatch` impls.
+//!
+//! Events handled by smithay-client-toolkit, like configures and output info, don't go
+//! through these, `WAYLAND_DEBUG=1` shows those on the wire.
+
+use std::{
+  fmt::Debug,
+  sync::atomic::{AtomicBool, Ordering},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use smithay_client_toolkit::reexports::client::Proxy;
+
+/// A global since `Dispatch` impls only get the state they dispatch to, which the prober doesn't share.
+static VERBOSE_EVENTS: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+  VERBOSE_EVENTS.store(true, Ordering::Relaxed);
+}
+
+/// Prints the event with the object it is for and when it arrived, if `--verbose-events` is given.
+pub fn log(proxy: &impl Proxy, event: &impl Debug) {
+  if !VERBOSE_EVENTS.load(Ordering::Relaxed) {
+    return;
+  }
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default();
+  eprintln!(
+    "[{}.{:06}] {} {event:?}",
+    now.as_secs(),
+    now.subsec_micros(),
+    proxy.id()
+  );
+}
@@ -0,0 +1,159 @@
+//! `--fifo`, taking the commands of the control socket from a named pipe, one per line.
+
+use std::{
+  ffi::CString,
+  fs::{self, File, OpenOptions},
+  io::{self, ErrorKind, Read},
+  os::unix::{ffi::OsStrExt, fs::FileTypeExt, fs::OpenOptionsExt},
+  path::{Path, PathBuf},
+};
+
+use smithay_client_toolkit::reexports::calloop::{
+  generic::Generic, Interest, LoopHandle, Mode, PostAction,
+};
+
+use crate::{control::MAX_COMMAND_LENGTH, environment, DimlandData};
+
+/// Removes the fifo when dimland exits.
+pub struct ControlFifo {
+  path: PathBuf,
+}
+
+impl Drop for ControlFifo {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+/// Creates the fifo if it isn't there yet and starts running what is written to it.
+pub fn open(handle: &LoopHandle<'static, DimlandData>, path: PathBuf) -> Option<ControlFifo> {
+  if let Err(err) = create(&path).and_then(|()| listen(handle, &path)) {
+    eprintln!("can't read commands from {} ({err})", path.display());
+    return None;
+  }
+  Some(ControlFifo { path })
+}
+
+/// Exits through the event loop on SIGINT and SIGTERM, so the fifo gets removed.
+///
+/// Has to be called before any threads are spawned, like [`environment::watch`].
+pub fn exit_on_signals(handle: &LoopHandle<DimlandData>) {
+  let Some(signals) = environment::signal_fd(&[libc::SIGINT, libc::SIGTERM]) else {
+    eprintln!(
+      "can't watch for SIGINT and SIGTERM ({}), the fifo stays behind when killed",
+      std::io::Error::last_os_error()
+    );
+    return;
+  };
+
+  handle
+    .insert_source(
+      Generic::new(signals, Interest::READ, Mode::Level),
+      |_, _, data| {
+        data.exit = true;
+        Ok(PostAction::Remove)
+      },
+    )
+    .expect("can't watch for SIGINT and SIGTERM");
+}
+
+fn create(path: &Path) -> io::Result<()> {
+  match fs::metadata(path) {
+    Ok(metadata) if metadata.file_type().is_fifo() => return Ok(()),
+    Ok(_) => {
+      return Err(io::Error::new(
+        ErrorKind::AlreadyExists,
+        "something else than a fifo is in the way",
+      ))
+    }
+    Err(err) if err.kind() == ErrorKind::NotFound => {}
+    Err(err) => return Err(err),
+  }
+
+  let path = CString::new(path.as_os_str().as_bytes())?;
+  // SAFETY: the path is nul terminated and outlives the call
+  if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+/// Runs the lines written to the fifo until the last writer closes it, then opens it again.
+fn listen(handle: &LoopHandle<'static, DimlandData>, path: &Path) -> io::Result<()> {
+  // without O_NONBLOCK, opening would wait for the first writer
+  let fifo: File = OpenOptions::new()
+    .read(true)
+    .custom_flags(libc::O_NONBLOCK)
+    .open(path)?;
+
+  let reopen = handle.clone();
+  let path = path.to_owned();
+  let mut line = Vec::new();
+  // set while throwing away the rest of a line that got too long
+  let mut skipping = false;
+  handle
+    .insert_source(
+      Generic::new(fifo, Interest::READ, Mode::Level),
+      move |_, fifo, data| {
+        let mut chunk = [0; 256];
+        loop {
+          let read = match (&**fifo).read(&mut chunk) {
+            Ok(0) => {
+              // whatever the last writer left without a newline is a command too
+              if !skipping {
+                data.run_fifo_line(&line);
+              }
+              if let Err(err) = listen(&reopen, &path) {
+                eprintln!("can't reopen {} ({err})", path.display());
+              }
+              return Ok(PostAction::Remove);
+            }
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+            Err(err) => {
+              eprintln!("can't read from {} ({err})", path.display());
+              return Ok(PostAction::Remove);
+            }
+          };
+
+          for &byte in &chunk[..read] {
+            if byte == b'\n' {
+              if !skipping {
+                data.run_fifo_line(&line);
+              }
+              line.clear();
+              skipping = false;
+            } else if line.len() >= MAX_COMMAND_LENGTH {
+              if !skipping {
+                eprintln!(
+                  "ignoring a command longer than {MAX_COMMAND_LENGTH} bytes from the fifo"
+                );
+              }
+              line.clear();
+              skipping = true;
+            } else if !skipping {
+              line.push(byte);
+            }
+          }
+        }
+      },
+    )
+    .expect("can't listen on the fifo");
+  Ok(())
+}
+
+impl DimlandData {
+  /// Runs a command from the fifo, there is no one to answer so errors go to stderr.
+  fn run_fifo_line(&mut self, line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    if line.trim().is_empty() {
+      return;
+    }
+    if let Err(err) = line
+      .parse()
+      .and_then(|command| self.handle_command(command))
+    {
+      eprintln!("fifo: {err}");
+    }
+  }
+}
@@ -0,0 +1,95 @@
+//! The frame rate counter drawn by `--fps-display`, to see how smooth animations are.
+
+use std::{collections::VecDeque, time::Instant};
+
+use crate::render::{Damage, PixelFormat};
+
+/// Frames the rate is averaged over.
+const WINDOW: usize = 60;
+/// How many buffer pixels a pixel of a digit takes up in each direction.
+const SCALE: u32 = 4;
+/// Distance of the counter from the top and right edge in buffer pixels.
+const MARGIN: u32 = 8;
+
+/// 3x5 pixel digits, a row per entry with the leftmost pixel in the highest of the 3 bits.
+const DIGITS: [[u8; 5]; 10] = [
+  [0b111, 0b101, 0b101, 0b101, 0b111],
+  [0b010, 0b110, 0b010, 0b010, 0b111],
+  [0b111, 0b001, 0b111, 0b100, 0b111],
+  [0b111, 0b001, 0b111, 0b001, 0b111],
+  [0b101, 0b101, 0b111, 0b001, 0b001],
+  [0b111, 0b100, 0b111, 0b001, 0b111],
+  [0b111, 0b100, 0b111, 0b101, 0b111],
+  [0b111, 0b001, 0b010, 0b010, 0b010],
+  [0b111, 0b101, 0b111, 0b101, 0b111],
+  [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// When the last frames arrived.
+#[derive(Debug, Default)]
+pub struct FpsCounter {
+  frames: VecDeque<Instant>,
+}
+
+impl FpsCounter {
+  pub fn tick(&mut self, now: Instant) {
+    // one more timestamp than frames, since the rate comes from the gaps between them
+    if self.frames.len() > WINDOW {
+      self.frames.pop_front();
+    }
+    self.frames.push_back(now);
+  }
+
+  /// The average frame rate over the last frames, 0 until there are at least two.
+  pub fn fps(&self) -> u32 {
+    let (Some(first), Some(last)) = (self.frames.front(), self.frames.back()) else {
+      return 0;
+    };
+    let elapsed = last.duration_since(*first).as_secs_f32();
+    if elapsed <= 0.0 {
+      return 0;
+    }
+    ((self.frames.len() - 1) as f32 / elapsed).round() as u32
+  }
+}
+
+/// Draws `fps` in white into the top right corner and returns where,
+/// or nothing if the canvas is too small to fit it.
+pub fn draw(
+  canvas: &mut [u8],
+  width: u32,
+  height: u32,
+  format: PixelFormat,
+  fps: u32,
+) -> Option<Damage> {
+  let text = fps.to_string();
+  let digit_width = 3 * SCALE;
+  let advance = digit_width + SCALE;
+  let text_width = text.len() as u32 * advance - SCALE;
+  let text_height = 5 * SCALE;
+  if text_width + 2 * MARGIN > width || text_height + 2 * MARGIN > height {
+    return None;
+  }
+
+  let (left, top) = (width - MARGIN - text_width, MARGIN);
+  let white = format.pack([0xFF, 0xFF, 0xFF], 0xFF);
+  for (index, digit) in text.bytes().enumerate() {
+    let glyph = DIGITS[(digit - b'0') as usize];
+    for y in 0..text_height {
+      for x in 0..digit_width {
+        if (glyph[(y / SCALE) as usize] >> (2 - x / SCALE)) & 1 == 0 {
+          continue;
+        }
+        let offset = (((top + y) * width + left + index as u32 * advance + x) * 4) as usize;
+        canvas[offset..offset + 4].copy_from_slice(&white);
+      }
+    }
+  }
+
+  Some(Damage {
+    x: left,
+    y: top,
+    width: text_width,
+    height: text_height,
+  })
+}
@@ -0,0 +1,73 @@
+//! `--grain`, faint noise in the alpha that hides banding at low alphas.
+//!
+//! The noise repeats every [`TILE`] pixels and only depends on the position and a seed,
+//! so the same seed always draws the same grain. `--grain-animate` moves the seed on a few times a second.
+
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  LoopHandle,
+};
+
+use crate::DimlandData;
+
+/// Width and height of the square the noise repeats in.
+pub const TILE: u32 = 256;
+/// How often `--grain-animate` reseeds, slow enough not to repaint every frame.
+const INTERVAL: Duration = Duration::from_millis(125);
+
+/// The grain at a pixel, from -0.5 to 0.5.
+pub fn noise(x: u32, y: u32, seed: u32) -> f32 {
+  // a few rounds of an integer hash over the position in the tile and the seed
+  let mut hash = (y % TILE * TILE + x % TILE) ^ seed.wrapping_mul(0x9E37_79B9);
+  hash ^= hash >> 16;
+  hash = hash.wrapping_mul(0x7FEB_352D);
+  hash ^= hash >> 15;
+  hash = hash.wrapping_mul(0x846C_A68B);
+  hash ^= hash >> 16;
+  (hash >> 8) as f32 / (1 << 24) as f32 - 0.5
+}
+
+/// Varies `alpha` by up to half of `strength` either way, leaving no dim at all alone.
+pub fn apply(alpha: f32, strength: f32, x: u32, y: u32, seed: u32) -> f32 {
+  if strength == 0.0 || alpha == 0.0 {
+    return alpha;
+  }
+  (alpha + noise(x, y, seed) * strength).clamp(0.0, 1.0)
+}
+
+/// Moves the seed on every [`INTERVAL`] and repaints.
+pub fn animate(handle: &LoopHandle<DimlandData>) {
+  handle
+    .insert_source(Timer::immediate(), |_, _, data| {
+      data.settings.grain_seed = data.settings.grain_seed.wrapping_add(1);
+      // the seed isn't part of what views compare to know whether they are up to date
+      data.repaint_all();
+      TimeoutAction::ToDuration(INTERVAL)
+    })
+    .expect("can't start the grain timer");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn the_same_seed_draws_the_same_grain() {
+    assert_eq!(noise(10, 20, 3), noise(10, 20, 3));
+    assert_eq!(noise(10, 20, 3), noise(10 + TILE, 20 + TILE, 3));
+    assert_ne!(noise(10, 20, 3), noise(10, 20, 4));
+    let values: Vec<f32> = (0..TILE).map(|x| noise(x, 0, 0)).collect();
+    assert!(values.iter().all(|value| (-0.5..0.5).contains(value)));
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    assert!(mean.abs() < 0.05, "{mean}");
+  }
+
+  #[test]
+  fn no_dim_stays_without_grain() {
+    assert_eq!(apply(0.0, 0.1, 5, 5, 0), 0.0);
+    assert_eq!(apply(0.4, 0.0, 5, 5, 0), 0.4);
+    assert!((apply(0.4, 0.1, 5, 5, 0) - 0.4).abs() <= 0.05);
+  }
+}
@@ -0,0 +1,325 @@
+//! `--image`, a png drawn over the dim and scaled to every output.
+//!
+//! The png is decoded once, scaled copies are made the first time a size is drawn.
+//! `set image` swaps it at runtime, crossfading from the one before.
+
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fs::File,
+  io::BufReader,
+  path::{Path, PathBuf},
+  rc::Rc,
+  time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  RegistrationToken,
+};
+
+use crate::{animation::Easing, DimlandData, DimlandSettings};
+
+const FADE: Duration = Duration::from_millis(400);
+const FADE_STEP: Duration = Duration::from_millis(16);
+/// Scaled copies kept around, more sizes than this start over.
+const MAX_SCALED: usize = 4;
+
+/// How the image is scaled to the overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImageFit {
+  /// Stretch to the overlay, ignoring the aspect ratio
+  Stretch,
+  /// Cover the overlay, cutting off what sticks out
+  #[default]
+  Fill,
+  /// Fit inside the overlay, leaving the rest to the dim
+  Fit,
+  /// Repeat at its own size from the top left
+  Tile,
+}
+
+/// Straight RGBA pixels, row by row.
+type Pixels = Rc<[[u8; 4]]>;
+
+#[derive(Debug)]
+pub struct Image {
+  pub path: PathBuf,
+  width: u32,
+  height: u32,
+  pixels: Vec<[u8; 4]>,
+  scaled: RefCell<HashMap<(u32, u32), Pixels>>,
+}
+
+impl Image {
+  /// Decodes a png of any color type into straight RGBA.
+  pub fn load(path: &Path) -> Result<Self, String> {
+    let invalid =
+      |err: &dyn std::fmt::Display| format!("{} is not a valid png ({err})", path.display());
+    let file = File::open(path).map_err(|err| format!("can't open {} ({err})", path.display()))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| invalid(&err))?;
+    let mut buffer = vec![
+      0;
+      reader
+        .output_buffer_size()
+        .ok_or_else(|| invalid(&"too large"))?
+    ];
+    let info = reader
+      .next_frame(&mut buffer)
+      .map_err(|err| invalid(&err))?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+      png::ColorType::Rgba => buffer
+        .chunks_exact(4)
+        .map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]])
+        .collect(),
+      png::ColorType::Rgb => buffer
+        .chunks_exact(3)
+        .map(|chunk| [chunk[0], chunk[1], chunk[2], 0xFF])
+        .collect(),
+      png::ColorType::GrayscaleAlpha => buffer
+        .chunks_exact(2)
+        .map(|chunk| [chunk[0], chunk[0], chunk[0], chunk[1]])
+        .collect(),
+      png::ColorType::Grayscale => buffer
+        .iter()
+        .map(|&gray| [gray, gray, gray, 0xFF])
+        .collect(),
+      png::ColorType::Indexed => return Err(invalid(&"palette left unexpanded")),
+    };
+    Ok(Self {
+      path: path.to_path_buf(),
+      width: info.width,
+      height: info.height,
+      pixels,
+      scaled: RefCell::default(),
+    })
+  }
+
+  /// The image scaled to an overlay of this size, from the cache after the first time.
+  pub fn scaled(&self, width: u32, height: u32, fit: ImageFit) -> Pixels {
+    let mut cache = self.scaled.borrow_mut();
+    if let Some(pixels) = cache.get(&(width, height)) {
+      return pixels.clone();
+    }
+    if cache.len() >= MAX_SCALED {
+      cache.clear();
+    }
+    let pixels: Pixels = scale(
+      &self.pixels,
+      (self.width, self.height),
+      (width, height),
+      fit,
+    )
+    .into();
+    cache.insert((width, height), pixels.clone());
+    pixels
+  }
+}
+
+/// Scales straight RGBA pixels by nearest neighbour, leaving what the image doesn't cover transparent.
+fn scale(pixels: &[[u8; 4]], from: (u32, u32), to: (u32, u32), fit: ImageFit) -> Vec<[u8; 4]> {
+  let (from_width, from_height) = (from.0 as f64, from.1 as f64);
+  let (to_width, to_height) = (to.0 as f64, to.1 as f64);
+  let (scale_x, scale_y) = match fit {
+    ImageFit::Stretch => (to_width / from_width, to_height / from_height),
+    ImageFit::Fill => {
+      let scale = (to_width / from_width).max(to_height / from_height);
+      (scale, scale)
+    }
+    ImageFit::Fit => {
+      let scale = (to_width / from_width).min(to_height / from_height);
+      (scale, scale)
+    }
+    ImageFit::Tile => (1.0, 1.0),
+  };
+  // centered, which is no offset at all when stretched
+  let offset_x = (to_width - from_width * scale_x) / 2.0;
+  let offset_y = (to_height - from_height * scale_y) / 2.0;
+
+  let mut scaled = Vec::with_capacity(to.0 as usize * to.1 as usize);
+  for y in 0..to.1 {
+    for x in 0..to.0 {
+      let source = match fit {
+        ImageFit::Tile => Some((x % from.0, y % from.1)),
+        _ => {
+          let source_x = (x as f64 + 0.5 - offset_x) / scale_x;
+          let source_y = (y as f64 + 0.5 - offset_y) / scale_y;
+          (source_x >= 0.0 && source_x < from_width && source_y >= 0.0 && source_y < from_height)
+            .then_some((source_x as u32, source_y as u32))
+        }
+      };
+      scaled.push(source.map_or([0; 4], |(x, y)| {
+        pixels[y as usize * from.0 as usize + x as usize]
+      }));
+    }
+  }
+  scaled
+}
+
+/// The pixel of the images being drawn at `index`, weighted by how far they faded in,
+/// as premultiplied color and coverage from 0 to 1.
+pub fn sample(layers: &[(Pixels, f32)], index: usize) -> Option<([f32; 3], f32)> {
+  if layers.is_empty() {
+    return None;
+  }
+  let mut color = [0.0; 3];
+  let mut coverage = 0.0;
+  for (pixels, weight) in layers {
+    let [r, g, b, a] = pixels[index];
+    let a = a as f32 / 255.0 * weight;
+    for (channel, value) in color.iter_mut().zip([r, g, b]) {
+      *channel += value as f32 * a;
+    }
+    coverage += a;
+  }
+  Some((color, coverage))
+}
+
+/// Draws an image sample over the dim, the image fading with the alpha of the overlay.
+pub fn over(color: [u8; 3], alpha: f32, sample: Option<([f32; 3], f32)>) -> ([u8; 3], f32) {
+  let Some((image, coverage)) = sample else {
+    return (color, alpha);
+  };
+  let opacity = coverage * alpha;
+  let combined = opacity + alpha * (1.0 - opacity);
+  if combined <= 0.0 {
+    return (color, 0.0);
+  }
+  let rgb = std::array::from_fn(|i| {
+    ((image[i] * alpha + color[i] as f32 * alpha * (1.0 - opacity)) / combined).round() as u8
+  });
+  (rgb, combined)
+}
+
+/// The image that was shown before a swap, fading out.
+#[derive(Debug, Clone)]
+pub struct ImageFade {
+  previous: Option<Rc<Image>>,
+  start: Instant,
+  easing: Easing,
+  timer: RegistrationToken,
+}
+
+impl ImageFade {
+  fn progress(&self, now: Instant) -> f32 {
+    let linear = now.saturating_duration_since(self.start).as_secs_f32() / FADE.as_secs_f32();
+    self.easing.apply(linear)
+  }
+}
+
+impl DimlandSettings {
+  /// The images to draw on an overlay of this size with their weights, two while crossfading.
+  pub fn image_layers(&self, width: u32, height: u32) -> Vec<(Pixels, f32)> {
+    // a blackout is black, nothing else
+    if self.blacked_out.is_some() {
+      return Vec::new();
+    }
+    let progress = self
+      .image_fade
+      .as_ref()
+      .map_or(1.0, |fade| fade.progress(Instant::now()));
+    let mut layers = Vec::new();
+    if let Some(image) = &self.image {
+      layers.push((image.scaled(width, height, self.image_fit), progress));
+    }
+    if let Some(previous) = self
+      .image_fade
+      .as_ref()
+      .and_then(|fade| fade.previous.as_ref())
+    {
+      layers.push((
+        previous.scaled(width, height, self.image_fit),
+        1.0 - progress,
+      ));
+    }
+    layers
+  }
+}
+
+impl DimlandData {
+  /// Swaps the image for the one loaded from the png at `path`, or takes it down without one,
+  /// crossfading either way.
+  pub fn set_image(&mut self, path: Option<&Path>, image: Option<Image>) {
+    let image = image.map(Rc::new);
+    let previous = std::mem::replace(&mut self.settings.image, image);
+    if let Some(fade) = self.settings.image_fade.take() {
+      self.loop_handle.remove(fade.timer);
+    }
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::immediate(), |_, _, data| data.step_image_fade())
+      .expect("can't start the image fade timer");
+    self.settings.image_fade = Some(ImageFade {
+      previous,
+      start: Instant::now(),
+      easing: self.settings.easing,
+      timer,
+    });
+    self.settings.watchers.emit(&match path {
+      Some(path) => format!("image {}", path.display()),
+      None => "image none".to_string(),
+    });
+  }
+
+  fn step_image_fade(&mut self) -> TimeoutAction {
+    let Some(fade) = &self.settings.image_fade else {
+      return TimeoutAction::Drop;
+    };
+    let done = fade.progress(Instant::now()) >= 1.0;
+    if done {
+      self.settings.image_fade = None;
+    }
+    // the image isn't part of what views compare to know whether they are up to date
+    self.repaint_all();
+    match done {
+      true => TimeoutAction::Drop,
+      false => TimeoutAction::ToDuration(FADE_STEP),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RED: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+  const BLUE: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+  #[test]
+  fn fit_leaves_bars_and_fill_crops() {
+    // 2x1, red on the left and blue on the right
+    let pixels = [RED, BLUE];
+    let fit = scale(&pixels, (2, 1), (2, 2), ImageFit::Fit);
+    assert_eq!(fit, [RED, BLUE, [0; 4], [0; 4]]);
+    let fill = scale(&pixels, (2, 1), (2, 2), ImageFit::Fill);
+    assert_eq!(fill, [RED, BLUE, RED, BLUE]);
+  }
+
+  #[test]
+  fn stretch_and_tile() {
+    let pixels = [RED, BLUE];
+    assert_eq!(
+      scale(&pixels, (2, 1), (4, 1), ImageFit::Stretch),
+      [RED, RED, BLUE, BLUE]
+    );
+    assert_eq!(
+      scale(&pixels, (2, 1), (3, 1), ImageFit::Tile),
+      [RED, BLUE, RED]
+    );
+  }
+
+  #[test]
+  fn an_opaque_image_replaces_the_dim() {
+    let layers: [(Pixels, f32); 1] = [(Rc::from([RED].as_slice()), 1.0)];
+    let (rgb, alpha) = over([0; 3], 1.0, sample(&layers, 0));
+    assert_eq!((rgb, alpha), ([0xFF, 0x00, 0x00], 1.0));
+    // without an image the dim stays as it is
+    assert_eq!(over([0; 3], 0.5, None), ([0; 3], 0.5));
+  }
+}
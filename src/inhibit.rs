@@ -0,0 +1,69 @@
+//! `--wl-idle-inhibit`, keeping the screensaver and idle locking away while dimland runs.
+//!
+//! Compositors only honor an inhibitor while its surface is visible, so every view gets one
+//! and it is enough for any output to show the overlay. They go away with their views.
+
+use smithay_client_toolkit::reexports::{
+  client::{
+    globals::GlobalList, protocol::wl_surface::WlSurface, Connection, Dispatch, QueueHandle,
+  },
+  protocols::wp::idle_inhibit::zv1::client::{
+    zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+    zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+  },
+};
+
+use crate::{events, DimlandData};
+
+pub struct IdleInhibit {
+  manager: ZwpIdleInhibitManagerV1,
+}
+
+impl IdleInhibit {
+  pub fn bind(globals: &GlobalList, qh: &QueueHandle<DimlandData>) -> Option<Self> {
+    let manager = globals
+      .bind(qh, 1..=1, ())
+      .inspect_err(|_| {
+        eprintln!("zwp_idle_inhibit_manager_v1 is not available, the screensaver can still start")
+      })
+      .ok()?;
+    Some(Self { manager })
+  }
+
+  /// Keeps the compositor from going idle while `surface` is visible.
+  pub fn inhibit(&self, surface: &WlSurface, qh: &QueueHandle<DimlandData>) -> ZwpIdleInhibitorV1 {
+    self.manager.create_inhibitor(surface, qh, ())
+  }
+}
+
+impl Drop for IdleInhibit {
+  fn drop(&mut self) {
+    self.manager.destroy();
+  }
+}
+
+impl Dispatch<ZwpIdleInhibitManagerV1, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    manager: &ZwpIdleInhibitManagerV1,
+    event: zwp_idle_inhibit_manager_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(manager, &event);
+  }
+}
+
+impl Dispatch<ZwpIdleInhibitorV1, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    inhibitor: &ZwpIdleInhibitorV1,
+    event: zwp_idle_inhibitor_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(inhibitor, &event);
+  }
+}
@@ -0,0 +1,171 @@
+//! The quit key of `--keyboard-shortcut-quit`, for when the overlay takes keyboard focus.
+//!
+//! Keyboards are only asked for with `--keyboard on-demand` or `exclusive`,
+//! without focus the overlay would never hear a key anyway.
+
+use smithay_client_toolkit::{
+  reexports::client::{
+    protocol::{wl_keyboard::WlKeyboard, wl_seat::WlSeat, wl_surface::WlSurface},
+    Connection, QueueHandle,
+  },
+  seat::{
+    keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+    Capability, SeatHandler, SeatState,
+  },
+  shell::wlr_layer::KeyboardInteractivity,
+};
+use xkbcommon::xkb;
+
+use crate::DimlandData;
+
+/// Parses a keysym name like `Escape`, also with the `XKB_KEY_` prefix of the C headers.
+pub fn parse_keysym(s: &str) -> Result<Keysym, String> {
+  let name = s.strip_prefix("XKB_KEY_").unwrap_or(s);
+  match xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS) {
+    keysym if keysym.raw() == 0 => Err(format!("{s} is not a keysym name like Escape or q")),
+    keysym => Ok(keysym),
+  }
+}
+
+/// The keyboards dimland listens to, one per seat.
+pub struct Seats {
+  state: SeatState,
+  keyboards: Vec<(WlSeat, WlKeyboard)>,
+}
+
+impl Seats {
+  pub fn new(state: SeatState) -> Self {
+    Self {
+      state,
+      keyboards: Vec::new(),
+    }
+  }
+}
+
+impl Drop for Seats {
+  fn drop(&mut self) {
+    for (_, keyboard) in &self.keyboards {
+      keyboard.release();
+    }
+  }
+}
+
+impl SeatHandler for DimlandData {
+  fn seat_state(&mut self) -> &mut SeatState {
+    &mut self.seats.state
+  }
+
+  fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+
+  fn new_capability(
+    &mut self,
+    _: &Connection,
+    qh: &QueueHandle<Self>,
+    seat: WlSeat,
+    capability: Capability,
+  ) {
+    if capability != Capability::Keyboard || self.settings.keyboard == KeyboardInteractivity::None {
+      return;
+    }
+    match self.seats.state.get_keyboard(qh, &seat, None) {
+      Ok(keyboard) => self.seats.keyboards.push((seat, keyboard)),
+      Err(err) => eprintln!("can't listen to the keyboard, the quit key won't work ({err})"),
+    }
+  }
+
+  fn remove_capability(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    seat: WlSeat,
+    capability: Capability,
+  ) {
+    if capability != Capability::Keyboard {
+      return;
+    }
+    for (_, keyboard) in self
+      .seats
+      .keyboards
+      .iter()
+      .filter(|(other, _)| other == &seat)
+    {
+      keyboard.release();
+    }
+    self.seats.keyboards.retain(|(other, _)| other != &seat);
+  }
+
+  fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat) {
+    self.seats.keyboards.retain(|(other, _)| other != &seat);
+  }
+}
+
+impl KeyboardHandler for DimlandData {
+  fn enter(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &WlKeyboard,
+    _: &WlSurface,
+    _: u32,
+    _: &[u32],
+    _: &[Keysym],
+  ) {
+  }
+
+  fn leave(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &WlKeyboard,
+    _: &WlSurface,
+    _: u32,
+  ) {
+  }
+
+  fn press_key(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &WlKeyboard,
+    _: u32,
+    event: KeyEvent,
+  ) {
+    if event.keysym == self.settings.quit_key {
+      self.settings.watchers.emit("quit");
+      self.exit = true;
+    }
+  }
+
+  fn release_key(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &WlKeyboard,
+    _: u32,
+    _: KeyEvent,
+  ) {
+  }
+
+  fn update_modifiers(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &WlKeyboard,
+    _: u32,
+    _: Modifiers,
+  ) {
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_keysym_names() {
+    assert_eq!(parse_keysym("Escape"), Ok(Keysym::Escape));
+    assert_eq!(parse_keysym("XKB_KEY_Escape"), Ok(Keysym::Escape));
+    assert_eq!(parse_keysym("q"), Ok(Keysym::q));
+    assert!(parse_keysym("Escapade").is_err());
+  }
+}
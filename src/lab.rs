@@ -0,0 +1,116 @@
+//! `--preserve-wallpaper-colors`, darkening a screenshot in CIELAB instead of blending black over it.
+//!
+//! Black over a color pulls its saturation down along with its lightness,
+//! taking away only L* keeps hue and chroma, so colors stay vivid, just darker.
+
+/// The D65 white point the sRGB primaries are relative to.
+const WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+const EPSILON: f32 = 216.0 / 24389.0;
+const KAPPA: f32 = 24389.0 / 27.0;
+
+fn to_linear(channel: u8) -> f32 {
+  let c = channel as f32 / 255.0;
+  match c <= 0.040_45 {
+    true => c / 12.92,
+    false => ((c + 0.055) / 1.055).powf(2.4),
+  }
+}
+
+fn from_linear(c: f32) -> u8 {
+  let c = match c <= 0.003_130_8 {
+    true => c * 12.92,
+    false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+  };
+  (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn to_lab(rgb: [u8; 3]) -> [f32; 3] {
+  let [r, g, b] = rgb.map(to_linear);
+  let xyz = [
+    0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+    0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b,
+    0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b,
+  ];
+  let [fx, fy, fz] = std::array::from_fn(|i| {
+    let t = xyz[i] / WHITE[i];
+    match t > EPSILON {
+      true => t.cbrt(),
+      false => (KAPPA * t + 16.0) / 116.0,
+    }
+  });
+  [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Linear sRGB, which may lie outside of 0 to 1 for colors sRGB can't show.
+fn from_lab([l, a, b]: [f32; 3]) -> [f32; 3] {
+  let fy = (l + 16.0) / 116.0;
+  let f = [fy + a / 500.0, fy, fy - b / 200.0];
+  let [x, y, z] = std::array::from_fn(|i| {
+    let t = match f[i].powi(3) > EPSILON {
+      true => f[i].powi(3),
+      false => (116.0 * f[i] - 16.0) / KAPPA,
+    };
+    t * WHITE[i]
+  });
+  [
+    3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+    -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+    0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+  ]
+}
+
+fn in_gamut(rgb: [f32; 3]) -> bool {
+  rgb.iter().all(|c| (-0.001..=1.001).contains(c))
+}
+
+/// Takes `alpha` of the lightness away from a color and leaves its hue and chroma,
+/// giving up just as much chroma as sRGB can't show that dark.
+pub fn darken(rgb: [u8; 3], alpha: f32) -> [u8; 3] {
+  if alpha <= 0.0 {
+    return rgb;
+  }
+  // no chroma is left at a lightness of 0
+  if alpha >= 1.0 {
+    return [0, 0, 0];
+  }
+  let [l, a, b] = to_lab(rgb);
+  let l = l * (1.0 - alpha);
+  let scaled = |scale: f32| from_lab([l, a * scale, b * scale]);
+  if in_gamut(scaled(1.0)) {
+    return scaled(1.0).map(from_linear);
+  }
+  // clipping channels one by one would shift the hue, so the chroma shrinks until it fits
+  let (mut fits, mut too_much) = (0.0, 1.0);
+  for _ in 0..12 {
+    let scale = (fits + too_much) / 2.0;
+    match in_gamut(scaled(scale)) {
+      true => fits = scale,
+      false => too_much = scale,
+    }
+  }
+  scaled(fits).map(from_linear)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trips() {
+    for rgb in [[0, 0, 0], [255, 255, 255], [200, 30, 90], [12, 160, 240]] {
+      assert_eq!(from_lab(to_lab(rgb)).map(from_linear), rgb);
+    }
+    let [l, a, b] = to_lab([255, 255, 255]);
+    assert!((l - 100.0).abs() < 0.01 && a.abs() < 0.01 && b.abs() < 0.01);
+  }
+
+  #[test]
+  fn keeps_the_hue() {
+    assert_eq!(darken([200, 30, 90], 0.0), [200, 30, 90]);
+    assert_eq!(darken([200, 30, 90], 1.0), [0, 0, 0]);
+    let [_, a, b] = to_lab([200, 30, 90]);
+    let [_, dark_a, dark_b] = to_lab(darken([200, 30, 90], 0.3));
+    let hue = |a: f32, b: f32| b.atan2(a);
+    assert!((hue(a, b) - hue(dark_a, dark_b)).abs() < 0.05);
+  }
+}
@@ -0,0 +1,3 @@
+//! Types for tools that control a running dimland, see [`rpc`].
+
+pub mod rpc;
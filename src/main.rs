@@ -1,10 +1,84 @@
-use clap::Parser;
+mod alert;
+mod ambient;
+mod animation;
+mod clipboard;
+mod completions;
+mod control;
+mod debug;
+mod environment;
+mod error;
+mod events;
+mod fifo;
+mod fps;
+mod grain;
+mod image;
+mod inhibit;
+mod keyboard;
+mod lab;
+mod man;
+mod metrics;
+mod named_regions;
+mod outputs;
+mod profiles;
+mod progress;
+mod protocols;
+mod ramp;
+mod regions;
+mod render;
+mod scanlines;
+mod schedule;
+mod screencopy;
+mod shell;
+mod state;
+mod stats;
+mod strip;
+mod sway;
+mod sync;
+mod telemetry;
+mod theme;
+
+use std::{
+  collections::{BTreeMap, HashMap},
+  path::PathBuf,
+  rc::Rc,
+  time::{Duration, Instant},
+};
+
+use ambient::AmbientSettings;
+use animation::{ColorCycle, Easing, Transition, Tween};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use clipboard::Clipboard;
+use control::{AlphaChange, CurtainChange};
+use error::DimlandError;
+use fps::FpsCounter;
+use image::{Image, ImageFade, ImageFit};
+use inhibit::IdleInhibit;
+use keyboard::Seats;
+use named_regions::NamedRegion;
+use outputs::{
+  OutputColor, OutputGeometry, OutputIdentity, OutputMatcher, OutputRect, OutputRefreshRate,
+  OutputTransform, Rect, RememberedColor,
+};
+use profiles::Profile;
+use ramp::{Ramp, RampEnd};
+use regions::Region;
+use render::{
+  BrightnessCurve, Color, Curtain, Damage, Edge, Fillets, Frame, PixelFormat, Radius, Span, Tint,
+};
+use scanlines::Scanlines;
+use screencopy::{Capture, Screencopy, Underlay};
+use shell::{DimlandShell, OverlaySurface};
 use smithay_client_toolkit::{
   compositor::{CompositorHandler, CompositorState},
-  delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
-  delegate_simple,
-  output::{OutputHandler, OutputState},
+  delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry,
+  delegate_seat, delegate_shm, delegate_simple,
+  output::{OutputHandler, OutputInfo, OutputState},
   reexports::{
+    calloop::{
+      timer::{TimeoutAction, Timer},
+      EventLoop, LoopHandle,
+    },
+    calloop_wayland_source::WaylandSource,
     client::{
       globals::{registry_queue_init, GlobalList},
       protocol::{
@@ -12,239 +86,3210 @@ use smithay_client_toolkit::{
         wl_output::WlOutput,
         wl_region::WlRegion,
         wl_shm::Format,
+        wl_surface::WlSurface,
       },
       Connection, Dispatch, QueueHandle,
     },
-    protocols::wp::viewporter::client::{
-      wp_viewport::{self, WpViewport},
-      wp_viewporter::{self, WpViewporter},
+    protocols::wp::{
+      idle_inhibit::zv1::client::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+      single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+      viewporter::client::{
+        wp_viewport::{self, WpViewport},
+        wp_viewporter::{self, WpViewporter},
+      },
     },
   },
   registry::{ProvidesRegistryState, RegistryState, SimpleGlobal},
   registry_handlers,
+  seat::{keyboard::Keysym, SeatState},
   shell::{
-    wlr_layer::{KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface},
+    wlr_layer::{
+      Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+    },
+    xdg::XdgShell,
     WaylandSurface,
   },
   shm::{raw::RawPool, Shm, ShmHandler},
 };
+use state::StateFile;
+use sync::SyncGroup;
+use telemetry::{Telemetry, Tracker};
 
 pub const DEFAULT_ALPHA: f32 = 0.5;
-pub const DEFAULT_RADIUS: u32 = 0;
+/// The alpha of a white tint unless `--alpha` is given, which brightens quickly.
+pub const DEFAULT_WHITE_ALPHA: f32 = 0.1;
+pub const DEFAULT_RADIUS: Radius = Radius::circle(0);
+pub const DEFAULT_GAMMA: f32 = 2.2;
+pub const DEFAULT_ANIMATE_DURATION: u64 = 250;
+pub const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(100);
+pub const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(10);
+/// How long `--exit-on-no-outputs` waits for an output to come back, enough to replug a cable.
+pub const NO_OUTPUTS_GRACE: Duration = Duration::from_secs(3);
+/// Enough for a 16K output, more is taken to be a virtual output reporting garbage.
+pub const DEFAULT_MAX_BUFFER_BYTES: u64 = 512 * 1024 * 1024;
+/// How early a frame callback may come for `--output-refresh-rate` and still be drawn,
+/// they jitter around the deadline and a cap at the native rate would drop every other one.
+pub const FRAME_SLACK: Duration = Duration::from_millis(1);
+
+/// The wlr-layer-shell layer the overlay lives on.
+///
+/// There is no way to set a z-offset within a layer, so picking the layer is
+/// the only knob for stacking against other layer surfaces. See the README for
+/// how common compositors order them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DimlandLayer {
+  Background,
+  Bottom,
+  Top,
+  #[default]
+  Overlay,
+}
+
+impl From<DimlandLayer> for Layer {
+  fn from(layer: DimlandLayer) -> Self {
+    match layer {
+      DimlandLayer::Background => Layer::Background,
+      DimlandLayer::Bottom => Layer::Bottom,
+      DimlandLayer::Top => Layer::Top,
+      DimlandLayer::Overlay => Layer::Overlay,
+    }
+  }
+}
+
+/// Whether the overlay takes keyboard focus, see `--keyboard`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DimlandKeyboard {
+  /// Never, keys go to the windows below as if there was no overlay
+  #[default]
+  None,
+  /// When the compositor gives it focus, like after clicking it
+  OnDemand,
+  /// Always, nothing else gets any keys while dimland runs
+  Exclusive,
+}
+
+impl From<DimlandKeyboard> for KeyboardInteractivity {
+  fn from(keyboard: DimlandKeyboard) -> Self {
+    match keyboard {
+      DimlandKeyboard::None => KeyboardInteractivity::None,
+      DimlandKeyboard::OnDemand => KeyboardInteractivity::OnDemand,
+      DimlandKeyboard::Exclusive => KeyboardInteractivity::Exclusive,
+    }
+  }
+}
+
+/// An edge the overlay sticks to, see `--anchor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DimlandAnchor {
+  Top,
+  Bottom,
+  Left,
+  Right,
+}
+
+impl From<DimlandAnchor> for Anchor {
+  fn from(anchor: DimlandAnchor) -> Self {
+    match anchor {
+      DimlandAnchor::Top => Anchor::TOP,
+      DimlandAnchor::Bottom => Anchor::BOTTOM,
+      DimlandAnchor::Left => Anchor::LEFT,
+      DimlandAnchor::Right => Anchor::RIGHT,
+    }
+  }
+}
+
+/// A width or height, either in pixels or relative to the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Extent {
+  Pixels(u32),
+  Percent(f32),
+}
+
+impl Extent {
+  /// Resolves against the full output dimension, never exceeding it.
+  pub fn resolve(self, full: u32) -> u32 {
+    match self {
+      Extent::Pixels(pixels) => pixels.min(full),
+      Extent::Percent(percent) => (full as f32 * percent / 100.0).round() as u32,
+    }
+  }
+}
+
+impl std::fmt::Display for Extent {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Extent::Pixels(pixels) => write!(f, "{pixels}"),
+      Extent::Percent(percent) => write!(f, "{percent}%"),
+    }
+  }
+}
+
+impl std::str::FromStr for Extent {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Some(percent) = s.strip_suffix('%') {
+      match percent.parse::<f32>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(Extent::Percent(percent)),
+        _ => Err(format!("{s} is not a percentage between 0% and 100%")),
+      }
+    } else {
+      s.parse()
+        .map(Extent::Pixels)
+        .map_err(|_| format!("{s} is neither pixels nor a percentage"))
+    }
+  }
+}
+
+/// `--alpha-clamp`, the alpha that is drawn no matter what asks for another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlphaClamp {
+  pub min: f32,
+  pub max: f32,
+}
+
+impl Default for AlphaClamp {
+  fn default() -> Self {
+    Self { min: 0.0, max: 1.0 }
+  }
+}
+
+impl AlphaClamp {
+  pub fn apply(self, alpha: f32) -> f32 {
+    alpha.clamp(self.min, self.max)
+  }
+}
+
+impl std::str::FromStr for AlphaClamp {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("{s} is not a range of alpha like 0.1,0.95");
+    let (min, max) = s.split_once(',').ok_or_else(invalid)?;
+    let (min, max): (f32, f32) = (
+      min.trim().parse().map_err(|_| invalid())?,
+      max.trim().parse().map_err(|_| invalid())?,
+    );
+    if !(0.0 <= min && min <= max && max <= 1.0) {
+      return Err(format!(
+        "{s} has to be two alphas from 0 to 1, the lower one first"
+      ));
+    }
+    Ok(Self { min, max })
+  }
+}
+
+/// An [`Extent`] for `--width` and `--height`, where 0 would leave the compositor no size to use.
+fn parse_size(s: &str) -> Result<Extent, String> {
+  match s.parse()? {
+    Extent::Pixels(0) | Extent::Percent(0.0) => {
+      Err(format!("{s} is too small, leave it out for the full size"))
+    }
+    extent => Ok(extent),
+  }
+}
+
+fn parse_alpha(s: &str) -> Result<f32, String> {
+  match s.parse() {
+    Ok(alpha) if (0.0..=1.0).contains(&alpha) => Ok(alpha),
+    _ => Err(format!("{s} is not an alpha from 0 to 1")),
+  }
+}
 
 #[derive(Debug, Parser)]
 #[command(version)]
 pub struct DimlandArgs {
+  #[command(subcommand)]
+  pub command: Option<DimlandCommand>,
   #[arg(
     short,
     long,
+    value_parser = parse_alpha,
     help = format!("0.0 is transparent, 1.0 is opaque, default is {DEFAULT_ALPHA}")
   )]
   pub alpha: Option<f32>,
   #[arg(
     short,
     long,
-    help = format!("The radius of the opaque screen corners, default is {DEFAULT_RADIUS}")
+    help = format!(
+      "The radius of the opaque screen corners, like 24 or 24x12 for wider than tall, default is {DEFAULT_RADIUS}"
+    )
   )]
-  pub radius: Option<u32>,
-}
+  pub radius: Option<Radius>,
+  #[arg(
+    long,
+    help = "Cut transparent notches into the corners instead of filling them opaque"
+  )]
+  pub invert_corners: bool,
+  #[arg(
+    long,
+    value_name = "EDGE:RADIUS[:INSET]",
+    help = "Round the screen into a panel along an edge, like top:12, or top:12:30 below a 30 pixel bar"
+  )]
+  pub inverse_corners: Option<Fillets>,
+  #[arg(
+    short,
+    long,
+    value_enum,
+    default_value_t,
+    help = "The layer to draw the overlay on, top is hidden behind fullscreen windows in most compositors"
+  )]
+  pub layer: DimlandLayer,
+  #[arg(
+    long,
+    value_enum,
+    default_value_t,
+    help = "Whether the overlay takes keyboard focus, exclusive keeps all keys from other windows"
+  )]
+  pub keyboard: DimlandKeyboard,
+  #[arg(
+    long,
+    value_name = "KEYSYM",
+    default_value = "Escape",
+    value_parser = keyboard::parse_keysym,
+    help = "Exit when this key is pressed while the overlay has keyboard focus with --keyboard, like Escape or XKB_KEY_q"
+  )]
+  pub keyboard_shortcut_quit: Keysym,
+  #[arg(
+    long,
+    help = "Map an extra invisible overlay surface to work around compositors that stack overlapping overlay surfaces unpredictably"
+  )]
+  pub above_other_layers: bool,
+  #[arg(
+    long,
+    conflicts_with = "layer",
+    help = "Dim the desktop below all windows like a wallpaper, same as --layer background"
+  )]
+  pub background_dim: bool,
+  #[arg(
+    long,
+    help = "Reconnect with backoff when the compositor goes away instead of exiting"
+  )]
+  pub reconnect: bool,
+  #[arg(
+    long,
+    value_enum,
+    value_delimiter = ',',
+    help = "The edges to stick the overlay to, like top or bottom,left, default is all of them"
+  )]
+  pub anchor: Vec<DimlandAnchor>,
+  #[arg(
+    long,
+    value_parser = parse_size,
+    help = "The width of the overlay in pixels or percent of the output, default is the full width"
+  )]
+  pub width: Option<Extent>,
+  #[arg(
+    long,
+    value_parser = parse_size,
+    help = "The height of the overlay in pixels or percent of the output, default is the full height"
+  )]
+  pub height: Option<Extent>,
+  #[arg(
+    long,
+    value_name = "OUTPUT:X,Y,WIDTH,HEIGHT",
+    conflicts_with_all = ["anchor", "width", "height", "fallback_window"],
+    help = "Only dim a rectangle of an output in logical pixels, like DP-1:100,100,800,600, can be given multiple times and outputs without one aren't dimmed"
+  )]
+  pub geometry: Vec<OutputRect>,
+  #[arg(long, help = "Print the connected outputs as json and exit")]
+  pub output_json: bool,
+  #[arg(
+    long,
+    help = "Print the shm formats the compositor advertises and which of them dimland draws with, and exit"
+  )]
+  pub list_formats: bool,
+  #[arg(
+    long,
+    value_name = "PATH",
+    help = "Write the buffer of every output to a png named after PATH and the output, then exit"
+  )]
+  pub render_debug: Option<PathBuf>,
+  #[arg(
+    long,
+    num_args = 2,
+    value_names = ["PATH", "WIDTHxHEIGHT"],
+    conflicts_with = "render_debug",
+    help = "Write the buffer an output of this size would get to a png without connecting to Wayland, then exit"
+  )]
+  pub preview: Option<Vec<String>>,
+  #[arg(
+    long,
+    value_name = completions::OUTPUT_VALUE_NAME,
+    help = "Only dim the output with this name, or with model:, make: or description: the one matching that, ignoring case, can be given multiple times"
+  )]
+  pub only: Vec<OutputMatcher>,
+  #[arg(
+    long,
+    value_enum,
+    default_value_t,
+    help = "How alpha is interpreted, srgb and gamma dim perceptually instead of physically"
+  )]
+  pub brightness_curve: BrightnessCurve,
+  #[arg(
+    long,
+    help = format!("The exponent for --brightness-curve gamma, default is {DEFAULT_GAMMA}")
+  )]
+  pub gamma: Option<f32>,
+  #[arg(
+    long,
+    default_value_t = 1.0,
+    help = "Spread alphas away from 0.5 by this factor, or pull them towards it below 1, an alpha of 0 stays 0"
+  )]
+  pub contrast: f32,
+  #[arg(
+    long,
+    value_name = "EDGE:EXTENT",
+    conflicts_with = "blackout",
+    help = "Cover a band along an edge at --curtain-alpha, like bottom:40% or top:200"
+  )]
+  pub curtain: Option<Curtain>,
+  #[arg(
+    long,
+    default_value_t = 1.0,
+    help = "The alpha of the --curtain band, opaque by default"
+  )]
+  pub curtain_alpha: f32,
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = 0,
+    help = "Blend the --curtain into the rest of the overlay across this many pixels"
+  )]
+  pub curtain_feather: u32,
+  #[arg(
+    long,
+    conflicts_with = "blackout",
+    help = "Leave a horizontal strip in the middle undimmed, to read along with, which dimland strip moves"
+  )]
+  pub reading_strip: bool,
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = strip::DEFAULT_HEIGHT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "How tall the reading strip is"
+  )]
+  pub reading_strip_height: u32,
+  #[arg(
+    long,
+    value_name = "PATH",
+    help = "Load rectangles with their own alpha from a JSON file, read again on SIGHUP"
+  )]
+  pub region_file: Option<PathBuf>,
+  #[arg(
+    long,
+    value_name = "PATH",
+    help = "Draw a png over the dim, fading with --alpha and scaled to every output by --image-fit"
+  )]
+  pub image: Option<PathBuf>,
+  #[arg(
+    long,
+    value_enum,
+    default_value_t,
+    help = "How --image is scaled to the overlay"
+  )]
+  pub image_fit: ImageFit,
+  #[arg(
+    long,
+    value_name = "STRENGTH",
+    default_value_t = 0.0,
+    help = "Vary the alpha of every pixel by up to this much, hiding banding at low alphas"
+  )]
+  pub grain: f32,
+  #[arg(long, help = "Move the --grain a few times a second, like film grain")]
+  pub grain_animate: bool,
+  #[arg(
+    long,
+    value_name = "STRENGTH",
+    default_value_t = 0.0,
+    help = "Darken every other row by this much of what the dim lets through, like a CRT"
+  )]
+  pub scanlines: f32,
+  #[arg(
+    long,
+    value_name = "ROWS",
+    default_value_t = scanlines::DEFAULT_SPACING,
+    value_parser = clap::value_parser!(u32).range(2..),
+    help = "Draw a --scanlines row every this many rows instead of every other one"
+  )]
+  pub scanline_spacing: u32,
+  #[arg(
+    long,
+    help = "Follow the ambient light sensor or backlight, dimming less in brighter rooms, --alpha is the darkest it gets"
+  )]
+  pub ambient: bool,
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = ambient::DEFAULT_INTERVAL,
+    help = "How often to read the sensor for --ambient"
+  )]
+  pub ambient_interval: u64,
+  #[arg(
+    long,
+    value_name = "LUX",
+    default_value_t = ambient::DEFAULT_BRIGHT_LUX,
+    help = "The illuminance at which --ambient stops dimming"
+  )]
+  pub ambient_lux: f32,
+  #[arg(
+    long,
+    value_name = "EXPONENT",
+    default_value_t = ambient::DEFAULT_CURVE,
+    help = "Shapes the --ambient mapping, above 1 undims quicker as the room gets brighter"
+  )]
+  pub ambient_curve: f32,
+  #[arg(
+    long,
+    value_name = "SCHEDULE",
+    conflicts_with_all = ["ambient", "blackout"],
+    help = "Follow alphas at times of the day like 08:00=0.0,22:00=0.5, blending between them every minute"
+  )]
+  pub alpha_from_time: Option<schedule::Schedule>,
+  #[arg(
+    long,
+    conflicts_with_all = ["alpha", "radius", "invert_corners", "ambient"],
+    help = "Black out the outputs entirely, combine with --only to pick which, commands can't dim or hide it then"
+  )]
+  pub blackout: bool,
+  #[arg(
+    long,
+    value_parser = parse_alpha,
+    help = "The lowest alpha that runtime adjustments can reach, default is 0.0"
+  )]
+  pub min_alpha: Option<f32>,
+  #[arg(
+    long,
+    value_parser = parse_alpha,
+    help = "The highest alpha that runtime adjustments can reach, default is 1.0"
+  )]
+  pub max_alpha: Option<f32>,
+  #[arg(
+    long,
+    value_name = "MIN,MAX",
+    conflicts_with = "blackout",
+    help = "Never draw an alpha outside of this range, like 0.1,0.95, not even with --force, only a blackout goes beyond"
+  )]
+  pub alpha_clamp: Option<AlphaClamp>,
+  #[arg(
+    long,
+    value_name = "DURATION[:EASING]",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "",
+    help = "Animate radius changes made at runtime, optionally like =400ms:ease-out, see --easing"
+  )]
+  pub animate_radius: Option<Transition>,
+  #[arg(
+    long,
+    value_name = "MS",
+    default_value_t = DEFAULT_ANIMATE_DURATION,
+    help = "How long animations take unless they say otherwise"
+  )]
+  pub animate_duration: u64,
+  #[arg(
+    long,
+    visible_alias = "animation-easing",
+    default_value = "linear",
+    help = "How animations ease unless they say otherwise, linear, ease-in, ease-out, ease-in-out or cubic-bezier(x1,y1,x2,y2)"
+  )]
+  pub easing: Easing,
+  #[arg(
+    long,
+    help = "Dim outputs pushing more pixels per second less, as they tend to be brighter"
+  )]
+  pub mirror_alpha: bool,
+  #[arg(
+    long,
+    help = "Print when the compositor presents each frame, see wp_presentation"
+  )]
+  pub telemetry: bool,
+  #[arg(
+    long,
+    help = "Print every event dimland handles itself with the object it is for, WAYLAND_DEBUG=1 shows all of them"
+  )]
+  pub verbose_events: bool,
+  /// Draws a grid with marked corners and center instead of dimming,
+  /// to see how the buffer ends up scaled and placed on the output.
+  #[arg(long, hide = true)]
+  pub test_pattern: bool,
+  /// Draws the frame rate into the top right corner and redraws every frame,
+  /// to see how smooth animations are.
+  #[arg(long, hide = true)]
+  pub fps_display: bool,
+  #[arg(
+    long,
+    requires = "animate_radius",
+    help = "Time animations with a hidden surface instead of the overlay, which compositors may stop sending frames to when it is covered"
+  )]
+  pub frame_callback_surface: bool,
+  #[arg(
+    long,
+    help = "Wait for the compositor to get through the commits to all outputs before sending the next ones, so none of them runs ahead of the others"
+  )]
+  pub surface_sync_group: bool,
+  #[arg(
+    long,
+    help = "Let the compositor skip what is below the overlay even when it isn't opaque, which only looks right at alpha 1.0"
+  )]
+  pub opaque: bool,
+  #[arg(
+    long,
+    conflicts_with_all = ["capture_before_dim", "fps_display", "clipboard_indicator", "reading_strip", "scanlines", "preserve_wallpaper_colors"],
+    help = "Stretch a single pixel over every output if the compositor has wp_single_pixel_buffer_v1, saving memory but leaving corners, curtains, regions, strips and images out"
+  )]
+  pub prefer_single_pixel_buffer: bool,
+  #[arg(
+    long,
+    help = "Lay out curtains, regions and images across all outputs instead of on each of them"
+  )]
+  pub span: bool,
+  #[arg(
+    long,
+    help = "Only damage the part of the buffer that changed since the last frame instead of all of it"
+  )]
+  pub buffer_age: bool,
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    value_parser = clap::value_parser!(u64).range(1..),
+    help = "Print a line to stderr this often to show dimland is still alive"
+  )]
+  pub heartbeat: Option<u64>,
+  #[arg(
+    long,
+    help = "Exit once the last dimmed output is gone instead of waiting for the next one"
+  )]
+  pub exit_on_no_outputs: bool,
+  #[arg(
+    long,
+    help = "Apply DIMLAND_ALPHA from the environment of the process whenever it receives SIGUSR2"
+  )]
+  pub watch_env: bool,
+  #[arg(
+    long,
+    help = "Without layer shell, dim with fullscreen windows instead, which end up below other windows"
+  )]
+  pub fallback_window: bool,
+  #[arg(
+    long,
+    conflicts_with = "blackout",
+    help = "Start with this profile from $XDG_CONFIG_HOME/dimland/profiles.json"
+  )]
+  pub profile: Option<String>,
+  #[arg(
+    long,
+    help = "Start with the alpha, radius, toggle and output colors dimland last had, unless given, saving them to $XDG_STATE_HOME/dimland/state.json as they change"
+  )]
+  pub restore: bool,
+  #[arg(
+    long,
+    help = "Print the frames drawn, shm allocated and uptime to stderr when quitting, like dimland stats"
+  )]
+  pub stats: bool,
+  #[arg(
+    long,
+    value_name = "SECONDS",
+    default_value_t = 5,
+    value_parser = clap::value_parser!(u64).range(1..),
+    help = "Give up on outputs whose overlay the compositor doesn't configure within this time"
+  )]
+  pub configure_timeout: u64,
+  #[arg(
+    long,
+    conflicts_with_all = ["prefer_light_mode", "blackout"],
+    help = format!("Tint the overlay white for dark themes, with an alpha of {DEFAULT_WHITE_ALPHA} unless given")
+  )]
+  pub prefer_dark_mode: bool,
+  #[arg(
+    long,
+    conflicts_with = "blackout",
+    help = "Tint the overlay black even if --follow-theme finds a dark theme"
+  )]
+  pub prefer_light_mode: bool,
+  #[arg(
+    long,
+    conflicts_with = "blackout",
+    help = "Tint the overlay white like --prefer-dark-mode if GTK prefers dark themes"
+  )]
+  pub follow_theme: bool,
+  #[arg(
+    long,
+    value_name = "BYTES",
+    default_value_t = DEFAULT_MAX_BUFFER_BYTES,
+    help = "Leave outputs undimmed whose buffer would take more memory than this"
+  )]
+  pub max_buffer_bytes: u64,
+  #[arg(
+    long,
+    help = "Leave the output with the focused sway workspace undimmed and only dim the others"
+  )]
+  pub sway_focus: bool,
+  #[arg(
+    long,
+    value_name = "PATH",
+    help = "Also take commands like those of dimland set from this named pipe, one per line"
+  )]
+  pub fifo: Option<PathBuf>,
+  #[arg(
+    long,
+    help = "Don't dim anything until the first command arrives, like from a systemd socket activation"
+  )]
+  pub start_idle: bool,
+  #[arg(
+    long,
+    value_name = "HH:MM",
+    value_parser = schedule::parse_time,
+    help = "Stay connected but don't dim anything until this time of day, like 22:00"
+  )]
+  pub at: Option<f32>,
+  #[arg(
+    long,
+    value_name = "DURATION",
+    value_parser = control::parse_duration,
+    help = "Exit after dimming for this long, counted from --at if given, like 30m"
+  )]
+  pub duration: Option<Duration>,
+  #[arg(
+    long,
+    value_name = "DURATION",
+    default_value = "1h",
+    value_parser = control::parse_duration,
+    help = "Lift a dimland blackout by itself after this long, in case it is forgotten"
+  )]
+  pub blackout_timeout: Duration,
+  #[arg(
+    long,
+    conflicts_with_all = ["anchor", "width", "height", "geometry", "blackout"],
+    help = "Dim a screenshot taken right before the overlay appears, which then hides any changes below it"
+  )]
+  pub capture_before_dim: bool,
+  #[arg(
+    long,
+    conflicts_with_all = ["anchor", "width", "height", "geometry", "blackout"],
+    help = "Like --capture-before-dim, but darken the screenshot in CIELAB, which keeps colors as vivid while taking their lightness away"
+  )]
+  pub preserve_wallpaper_colors: bool,
+  #[arg(
+    long,
+    value_name = "COLOR",
+    value_delimiter = ',',
+    conflicts_with_all = ["blackout", "prefer_dark_mode", "follow_theme"],
+    help = "Slowly move the overlay color through a list like #ff8800,#0044ff, repainting every frame"
+  )]
+  pub color_cycle: Vec<Color>,
+  #[arg(
+    long,
+    value_name = "DURATION",
+    default_value = "60s",
+    value_parser = control::parse_duration,
+    help = "How long --color-cycle takes to get through all colors"
+  )]
+  pub color_cycle_period: Duration,
+  #[arg(
+    long,
+    value_name = "OUTPUT=COLOR",
+    help = "Draw the overlay of an output in its own color, like DP-1=#ffeedd or model:U2720Q=5000K, to match monitors with each other, can be given multiple times"
+  )]
+  pub output_color: Vec<OutputColor>,
+  #[arg(
+    long,
+    value_name = "OUTPUT=TRANSFORM",
+    help = "Take an output to be turned like 90, 180, 270 or flipped-90 instead of what it reports, for RDP or VNC outputs reporting the wrong one, can be given multiple times"
+  )]
+  pub output_transform: Vec<OutputTransform>,
+  #[arg(
+    long,
+    value_name = "OUTPUT=HZ",
+    help = "Draw animations on an output by name at most this many times a second, like HDMI-A-1=60, however fast it refreshes, can be given multiple times"
+  )]
+  pub output_refresh_rate: Vec<OutputRefreshRate>,
+  #[arg(
+    long,
+    help = "Show a small clipboard badge on the first output for a few seconds whenever something is copied, if the compositor has zwlr_data_control_manager_v1"
+  )]
+  pub clipboard_indicator: bool,
+  #[arg(
+    long,
+    overrides_with = "no_wl_idle_inhibit",
+    help = "Keep the screensaver and idle locking from starting while the overlay shows, if the compositor has zwp_idle_inhibit_manager_v1"
+  )]
+  pub wl_idle_inhibit: bool,
+  #[arg(
+    long,
+    overrides_with = "wl_idle_inhibit",
+    help = "Let the compositor go idle as usual, undoing an earlier --wl-idle-inhibit"
+  )]
+  pub no_wl_idle_inhibit: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DimlandCommand {
+  #[command(about = "Print completions for the given shell")]
+  Completions { shell: clap_complete::Shell },
+  #[command(about = "Print the man page in roff")]
+  Man,
+  #[command(about = "Print the names of the connected outputs, one per line")]
+  ListOutputs,
+  #[command(about = "Print which of the protocols dimland uses the compositor supports")]
+  CheckCompositor,
+  #[command(about = "Change the running dimland")]
+  #[command(arg_required_else_help = true)]
+  Set {
+    #[arg(
+      short,
+      long,
+      allow_hyphen_values = true,
+      help = "The new alpha, or a step from the current one like +0.1 or -0.1, clamped to --min-alpha and --max-alpha"
+    )]
+    alpha: Option<AlphaChange>,
+    #[arg(long, requires = "alpha", help = "Ignore --min-alpha and --max-alpha")]
+    force: bool,
+    #[arg(short, long, help = "The new corner radius, like 24 or 24x12")]
+    radius: Option<Radius>,
+    #[arg(
+      long,
+      allow_hyphen_values = true,
+      conflicts_with = "duration",
+      help = "The new curtain like bottom:40% or none, or move its edge like +5% or -20"
+    )]
+    curtain: Option<CurtainChange>,
+    #[arg(
+      long,
+      value_name = "PATH",
+      conflicts_with = "duration",
+      help = "Crossfade to another png, or none to take it down"
+    )]
+    image: Option<PathBuf>,
+    #[arg(
+      long,
+      conflicts_with_all = ["curtain", "image", "duration"],
+      help = "Draw --output or --region in this color, like #ffeedd or 5000K, or none to go back to the usual one"
+    )]
+    color: Option<String>,
+    #[arg(
+      long,
+      value_name = completions::OUTPUT_VALUE_NAME,
+      requires = "color",
+      conflicts_with_all = ["alpha", "radius"],
+      help = "The output --color is for, by name"
+    )]
+    output: Option<String>,
+    #[arg(
+      long,
+      conflicts_with_all = ["curtain", "image", "output", "duration"],
+      help = "Change only a named region, its alpha, radius or color"
+    )]
+    region: Option<String>,
+    #[arg(
+      long = "for",
+      value_name = "DURATION",
+      value_parser = control::parse_duration,
+      help = "Go back to the current state after this long, like 30s, 10m or 1h"
+    )]
+    duration: Option<Duration>,
+    #[arg(
+      long,
+      conflicts_with_all = ["alpha", "radius", "curtain", "image", "color", "region", "duration"],
+      help = "Go back to the state from before the pending --for override right away"
+    )]
+    cancel_override: bool,
+  },
+  #[command(about = "Hide or show the running dimland")]
+  Toggle {
+    #[arg(
+      long = "for",
+      value_name = "DURATION",
+      value_parser = control::parse_duration,
+      help = "Toggle back after this long, like 30s, 10m or 1h"
+    )]
+    duration: Option<Duration>,
+  },
+  #[command(about = "Dim the running dimland more")]
+  Increase {
+    #[arg(default_value_t = control::DEFAULT_STEP)]
+    step: f32,
+  },
+  #[command(about = "Dim the running dimland less")]
+  Decrease {
+    #[arg(default_value_t = control::DEFAULT_STEP)]
+    step: f32,
+  },
+  #[command(about = "Print the state of the running dimland")]
+  Status {
+    #[arg(
+      long,
+      help = "Print the response of the JSON protocol instead, for scripts"
+    )]
+    json: bool,
+  },
+  #[command(about = "Print what happens to the running dimland, one event per line")]
+  Watch,
+  #[command(about = "Print counters of the running dimland in the Prometheus text format")]
+  Metrics,
+  #[command(about = "Print the frames drawn, shm allocated and uptime of the running dimland")]
+  Stats,
+  #[command(about = "Switch the running dimland to a profile from the profiles file")]
+  Profile { name: String },
+  #[command(
+    about = "Black out the running dimland entirely and take all input, like a soft screen off"
+  )]
+  Blackout {
+    #[arg(long, help = "Lift the blackout and go back to the state from before")]
+    off: bool,
+    #[arg(
+      long = "for",
+      value_name = "DURATION",
+      value_parser = control::parse_duration,
+      conflicts_with = "off",
+      help = "Lift the blackout after this long instead of after --blackout-timeout"
+    )]
+    duration: Option<Duration>,
+  },
+  #[command(
+    about = "Slowly dim the running dimland towards an alpha, like towards the end of a work block"
+  )]
+  #[command(arg_required_else_help = true)]
+  Ramp {
+    #[arg(
+      long,
+      requires = "over",
+      required_unless_present_any = ["pause", "resume", "cancel"],
+      conflicts_with_all = ["pause", "resume", "cancel"],
+      help = "The alpha to end up at"
+    )]
+    to: Option<f32>,
+    #[arg(
+      long,
+      value_name = "DURATION",
+      value_parser = control::parse_duration,
+      requires = "to",
+      help = "How long to take, like 25m"
+    )]
+    over: Option<Duration>,
+    #[arg(
+      long = "then",
+      value_enum,
+      default_value_t,
+      help = "What to do once the alpha is reached"
+    )]
+    end: RampEnd,
+    #[arg(long, conflicts_with_all = ["resume", "cancel"], help = "Stop the ramp where it is")]
+    pause: bool,
+    #[arg(long, conflicts_with = "cancel", help = "Continue a paused ramp")]
+    resume: bool,
+    #[arg(long, help = "Stop the ramp and go back to where it started")]
+    cancel: bool,
+  },
+  #[command(
+    about = "Pulse the running dimland in a color a few times, like a visual bell when a build fails"
+  )]
+  Alert {
+    #[arg(long, default_value_t = alert::DEFAULT_COLOR, help = "The color to pulse in, like #aa0000 or 2000K")]
+    color: Color,
+    #[arg(
+      long,
+      default_value_t = alert::DEFAULT_COUNT,
+      value_parser = clap::value_parser!(u32).range(1..),
+      help = "How often to pulse"
+    )]
+    count: u32,
+  },
+  #[command(
+    about = "Show a thin bar along an edge of the running dimland for a moment, like for volume"
+  )]
+  Progress {
+    #[arg(help = "How much of the edge to fill, from 0 to 1")]
+    value: f32,
+    #[arg(long, default_value_t = progress::DEFAULT_EDGE, help = "The edge to draw along, top, bottom, left or right")]
+    edge: Edge,
+    #[arg(long, default_value_t = progress::DEFAULT_COLOR, help = "The color of the bar, like #88c0d0")]
+    color: Color,
+    #[arg(
+      long = "for",
+      value_name = "DURATION",
+      value_parser = control::parse_duration,
+      help = "Hide the bar after this long instead of after 1.5s"
+    )]
+    duration: Option<Duration>,
+  },
+  #[command(
+    about = "Move the reading strip of the running dimland, putting it up if there is none"
+  )]
+  Strip {
+    #[arg(
+      long,
+      help = "Where the middle of the strip goes, in pixels from the top"
+    )]
+    y: Option<u32>,
+    #[arg(
+      long,
+      value_parser = clap::value_parser!(u32).range(1..),
+      help = "How tall the strip is from now on, in pixels"
+    )]
+    height: Option<u32>,
+    #[arg(long, conflicts_with_all = ["y", "height"], help = "Take the strip down")]
+    off: bool,
+  },
+}
+
+impl DimlandCommand {
+  /// The lines to send over the control socket, for commands that talk to a running dimland.
+  fn control_lines(&self) -> Vec<String> {
+    match self {
+      DimlandCommand::Set {
+        cancel_override: true,
+        ..
+      } => vec!["cancel-override".to_string()],
+      DimlandCommand::Set {
+        alpha,
+        force,
+        radius,
+        curtain,
+        image,
+        color,
+        output,
+        region,
+        duration,
+        ..
+      } => {
+        let mut line = "set".to_string();
+        if let Some(region) = region {
+          line += &format!(" region {region}");
+        }
+        if let Some(alpha) = alpha {
+          line += &format!(" alpha {alpha}");
+        }
+        if let Some(radius) = radius {
+          line += &format!(" radius {radius}");
+        }
+        if let Some(curtain) = curtain {
+          line += &format!(" curtain {curtain}");
+        }
+        if let Some(image) = image {
+          // the running dimland may have been started in another directory
+          let image = match image.as_os_str() == "none" {
+            true => image.clone(),
+            false => std::path::absolute(image).unwrap_or_else(|_| image.clone()),
+          };
+          line += &format!(" image {}", image.display());
+        }
+        if let Some(color) = color {
+          line += &format!(" color {color}");
+        }
+        if let Some(output) = output {
+          line += &format!(" output {output}");
+        }
+        if *force {
+          line += " force";
+        }
+        if let Some(duration) = duration {
+          line += &format!(" for {}s", duration.as_secs_f64());
+        }
+        vec![line]
+      }
+      DimlandCommand::Toggle { duration } => vec![match duration {
+        Some(duration) => format!("toggle for {}s", duration.as_secs_f64()),
+        None => "toggle".to_string(),
+      }],
+      DimlandCommand::Increase { step } => vec![format!("increase {step}")],
+      DimlandCommand::Decrease { step } => vec![format!("decrease {step}")],
+      DimlandCommand::Status { json: false } => vec!["status".to_string()],
+      DimlandCommand::Status { json: true } => vec![r#"{"method":"status"}"#.to_string()],
+      DimlandCommand::Metrics => vec!["metrics".to_string()],
+      DimlandCommand::Stats => vec!["stats".to_string()],
+      DimlandCommand::Profile { name } => vec![format!("profile {name}")],
+      DimlandCommand::Ramp { pause: true, .. } => vec!["ramp pause".to_string()],
+      DimlandCommand::Ramp { resume: true, .. } => vec!["ramp resume".to_string()],
+      DimlandCommand::Ramp { cancel: true, .. } => vec!["ramp cancel".to_string()],
+      DimlandCommand::Ramp { to, over, end, .. } => match (to, over) {
+        (Some(to), Some(over)) => vec![format!(
+          "ramp to {to} over {}s then {}",
+          over.as_secs_f64(),
+          end.to_possible_value().unwrap().get_name()
+        )],
+        _ => Vec::new(),
+      },
+      DimlandCommand::Blackout { off: true, .. } => vec!["blackout off".to_string()],
+      DimlandCommand::Blackout { duration, .. } => vec![match duration {
+        Some(duration) => format!("blackout for {}s", duration.as_secs_f64()),
+        None => "blackout".to_string(),
+      }],
+      DimlandCommand::Progress {
+        value,
+        edge,
+        color,
+        duration,
+      } => {
+        let mut line = format!("progress {value} edge {edge} color {color}");
+        if let Some(duration) = duration {
+          line += &format!(" for {}s", duration.as_secs_f64());
+        }
+        vec![line]
+      }
+      DimlandCommand::Alert { color, count } => vec![format!("alert color {color} count {count}")],
+      DimlandCommand::Strip { off: true, .. } => vec!["strip off".to_string()],
+      DimlandCommand::Strip { y, height, .. } => {
+        let mut line = "strip".to_string();
+        if let Some(y) = y {
+          line += &format!(" y {y}");
+        }
+        if let Some(height) = height {
+          line += &format!(" height {height}");
+        }
+        vec![line]
+      }
+      DimlandCommand::Completions { .. }
+      | DimlandCommand::Man
+      | DimlandCommand::ListOutputs
+      | DimlandCommand::CheckCompositor
+      | DimlandCommand::Watch => Vec::new(),
+    }
+  }
+}
+
+fn main() {
+  let args = DimlandArgs::parse();
+  if args.verbose_events {
+    events::enable();
+  }
+
+  match args.command {
+    Some(DimlandCommand::Completions { shell }) => {
+      completions::print_completions(shell);
+      return;
+    }
+    Some(DimlandCommand::Man) => {
+      man::print_man();
+      return;
+    }
+    Some(DimlandCommand::ListOutputs) => {
+      let conn = connect();
+      for info in outputs::query_outputs(&conn) {
+        if let Some(name) = info.name {
+          println!("{name}");
+        }
+      }
+      return;
+    }
+    Some(DimlandCommand::CheckCompositor) => {
+      let conn = connect();
+      protocols::check_compositor(&conn);
+      return;
+    }
+    Some(DimlandCommand::Watch) => {
+      if let Err(err) = control::watch() {
+        eprintln!("{err}");
+        std::process::exit(1);
+      }
+      return;
+    }
+    Some(command) => {
+      for line in command.control_lines() {
+        match control::send(&line) {
+          Ok(answer) => print!("{answer}"),
+          Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+          }
+        }
+      }
+      return;
+    }
+    None => {}
+  }
+
+  if !(0.0..=1.0).contains(&args.grain) {
+    DimlandArgs::command()
+      .error(ErrorKind::InvalidValue, "--grain has to be between 0 and 1")
+      .exit();
+  }
+  if !(0.0..=1.0).contains(&args.scanlines) {
+    DimlandArgs::command()
+      .error(
+        ErrorKind::InvalidValue,
+        "--scanlines has to be between 0 and 1",
+      )
+      .exit();
+  }
+  if args.contrast < 0.0 {
+    DimlandArgs::command()
+      .error(ErrorKind::InvalidValue, "--contrast can't be negative")
+      .exit();
+  }
+  if args.min_alpha.unwrap_or(0.0) > args.max_alpha.unwrap_or(1.0) {
+    DimlandArgs::command()
+      .error(
+        ErrorKind::ArgumentConflict,
+        "--min-alpha can't be higher than --max-alpha",
+      )
+      .exit();
+  }
+
+  if args.output_json {
+    let conn = connect();
+    let reports: Vec<_> = outputs::query_outputs(&conn)
+      .iter()
+      .map(outputs::OutputReport::from)
+      .collect();
+    println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    return;
+  }
+  if args.list_formats {
+    let conn = connect();
+    protocols::list_formats(&conn);
+    return;
+  }
+
+  if args.color_cycle.len() == 1 || args.color_cycle_period.is_zero() {
+    DimlandArgs::command()
+      .error(
+        ErrorKind::InvalidValue,
+        "--color-cycle needs at least two colors and a period longer than 0",
+      )
+      .exit();
+  }
+  let mut settings = DimlandSettings::new(&args);
+  if args.opaque && settings.alpha < 1.0 {
+    eprintln!(
+      "--opaque at alpha {:.2} lets the compositor show stale or garbage pixels below the overlay",
+      settings.alpha
+    );
+  }
+  if args.keyboard == DimlandKeyboard::Exclusive {
+    eprintln!(
+      "WARNING: --keyboard exclusive takes ALL keyboard input while dimland runs, no other window \
+       gets any keys until it exits with {:?}, so keep another way to stop it at hand like \
+       `pkill dimland` from another tty or over ssh",
+      args.keyboard_shortcut_quit
+    );
+  }
+  if let Err(err) = settings.load_files(&args) {
+    eprintln!("{err}");
+    std::process::exit(1);
+  }
+  if args.restore {
+    match state::state_path() {
+      Some(path) => {
+        match state::load(&path) {
+          Ok(Some(restored)) => settings.restore(&args, restored),
+          Ok(None) => {}
+          // it gets overwritten with the next change
+          Err(err) => eprintln!("{err}, starting without it"),
+        }
+        settings.state_file = Some(StateFile::new(path));
+      }
+      None => eprintln!("neither XDG_STATE_HOME nor HOME is set, the state isn't restored"),
+    }
+  }
+  if let Some(name) = &args.profile {
+    if let Err(err) = settings.use_profile(name) {
+      DimlandArgs::command()
+        .error(ErrorKind::InvalidValue, err)
+        .exit();
+    }
+  }
+
+  if let Some([path, size]) = args.preview.as_deref() {
+    let size = debug::parse_size(size).unwrap_or_else(|err| {
+      DimlandArgs::command()
+        .error(ErrorKind::InvalidValue, err)
+        .exit()
+    });
+    debug::preview(&settings, path.as_ref(), size);
+    return;
+  }
+  if let Some(path) = &args.render_debug {
+    let conn = connect();
+    debug::render_debug(&conn, &settings, path);
+    return;
+  }
+
+  let mut event_loop = EventLoop::<DimlandData>::try_new().expect("no event loop");
+  let _control = control::listen(&event_loop.handle());
+  if let Some(ambient) = &settings.ambient {
+    ambient::watch(&event_loop.handle(), ambient.clone());
+  }
+  if let Some(schedule) = &args.alpha_from_time {
+    schedule::watch(&event_loop.handle(), schedule.clone());
+  }
+  if args.watch_env {
+    environment::watch(&event_loop.handle());
+  }
+  if args.grain_animate && args.grain > 0.0 {
+    grain::animate(&event_loop.handle());
+  }
+  if let Some(path) = &args.region_file {
+    regions::watch(&event_loop.handle(), path.clone());
+  }
+  let _fifo = args.fifo.clone().and_then(|path| {
+    fifo::exit_on_signals(&event_loop.handle());
+    fifo::open(&event_loop.handle(), path)
+  });
+  if let Some(seconds) = args.heartbeat {
+    heartbeat(&event_loop.handle(), Duration::from_secs(seconds));
+  }
+  if args.sway_focus {
+    sway::watch(&event_loop.handle());
+  }
+  match (args.at, args.duration) {
+    (Some(time), duration) => schedule::wake_at(&event_loop.handle(), time, duration),
+    (None, Some(duration)) => schedule::exit_after(&event_loop.handle(), duration),
+    (None, None) => {}
+  }
+
+  loop {
+    let conn = if args.reconnect {
+      connect_with_backoff()
+    } else {
+      connect()
+    };
+
+    // a restarting compositor can go away again right after accepting the connection
+    let (globals, mut event_queue) = match registry_queue_init(&conn) {
+      Ok(queue) => queue,
+      Err(err) if args.reconnect => {
+        eprintln!("lost the compositor while starting ({err}), reconnecting");
+        continue;
+      }
+      Err(err) => fail(DimlandError::Registry(err)),
+    };
+    let qh = event_queue.handle();
+
+    let (compositor, shell, shm) =
+      bind_globals(&globals, &qh, args.fallback_window).unwrap_or_else(|err| fail(err));
+
+    let mut data = DimlandData::new(
+      compositor,
+      &globals,
+      &qh,
+      shell,
+      shm,
+      settings,
+      event_loop.handle(),
+    );
+
+    // outputs only know their names after this
+    if let Err(err) = event_queue.roundtrip(&mut data) {
+      if !args.reconnect {
+        fail(DimlandError::Dispatch(err));
+      }
+      eprintln!("lost the compositor while starting ({err}), reconnecting");
+      settings = data.into_settings();
+      continue;
+    }
+    data.warn_missing_outputs();
+
+    let wayland = WaylandSource::new(conn.clone(), event_queue)
+      .insert(event_loop.handle())
+      .unwrap_or_else(|err| fail(DimlandError::EventLoop(err.error)));
+
+    let result = loop {
+      if data.should_exit() {
+        break Ok(());
+      }
+      if let Err(err) = event_loop.dispatch(None, &mut data) {
+        break Err(err);
+      }
+      data.commit_transaction();
+      data.persist_state();
+    };
+    event_loop.handle().remove(wayland);
+
+    match result {
+      Ok(()) => {
+        data.write_state();
+        if args.stats {
+          eprintln!("{}", data.settings.stats.get());
+        }
+        // views destroy their inhibitors and the rest as they drop, which still has to be sent
+        drop(data);
+        let _ = conn.flush();
+        return;
+      }
+      Err(err) if args.reconnect => {
+        eprintln!("lost the compositor ({err}), reconnecting");
+        settings = data.into_settings();
+      }
+      Err(err) => fail(DimlandError::EventLoop(err)),
+    }
+  }
+}
+
+/// Binds the globals dimland can't do without, falling back to windows without layer shell
+/// if `fallback_window` is set.
+fn bind_globals(
+  globals: &GlobalList,
+  qh: &QueueHandle<DimlandData>,
+  fallback_window: bool,
+) -> Result<(CompositorState, DimlandShell, Shm), DimlandError> {
+  let missing = |interface| move |source| DimlandError::MissingGlobal { interface, source };
+  let compositor = CompositorState::bind(globals, qh).map_err(missing("wl_compositor"))?;
+  let shell = match LayerShell::bind(globals, qh) {
+    Ok(layer_shell) => DimlandShell::Layer(layer_shell),
+    Err(_) if fallback_window => {
+      eprintln!("zwlr_layer_shell_v1 is not available, dimming with fullscreen windows");
+      DimlandShell::Window(XdgShell::bind(globals, qh).map_err(missing("xdg_wm_base"))?)
+    }
+    Err(err) => return Err(missing("zwlr_layer_shell_v1")(err)),
+  };
+  let shm = Shm::bind(globals, qh).map_err(missing("wl_shm"))?;
+  Ok((compositor, shell, shm))
+}
+
+/// Connects to the compositor of the session, or exits saying there is none.
+fn connect() -> Connection {
+  Connection::connect_to_env()
+    .map_err(DimlandError::Connect)
+    .unwrap_or_else(|err| fail(err))
+}
+
+/// Prints a setup error with what to do about it, if anything, and exits.
+fn fail(err: DimlandError) -> ! {
+  eprintln!("{err}");
+  if let DimlandError::MissingGlobal {
+    interface: "zwlr_layer_shell_v1",
+    ..
+  } = err
+  {
+    eprintln!(
+      "dimland draws its overlay with it. sway, Hyprland, river and KDE have it, GNOME doesn't. \
+       try --fallback-window there"
+    );
+  }
+  std::process::exit(1);
+}
+
+/// Prints how many outputs are dimmed and how much on a timer, so log watchers know dimland is alive.
+fn heartbeat(handle: &LoopHandle<DimlandData>, interval: Duration) {
+  handle
+    .insert_source(Timer::from_duration(interval), move |_, _, data| {
+      eprintln!(
+        "dimland alive: {} outputs, alpha={:.1}",
+        data.views.len(),
+        data.settings.alpha
+      );
+      TimeoutAction::ToDuration(interval)
+    })
+    .expect("can't start the heartbeat timer");
+}
+
+/// Retries connecting to the compositor until its socket is back,
+/// doubling the delay after every failed attempt.
+fn connect_with_backoff() -> Connection {
+  let mut delay = RECONNECT_DELAY_MIN;
+  loop {
+    match Connection::connect_to_env() {
+      Ok(conn) => return conn,
+      Err(err) => {
+        eprintln!("can't connect ({err}), retrying in {delay:?}");
+        std::thread::sleep(delay);
+        delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+      }
+    }
+  }
+}
+
+/// Everything that decides what the overlay looks like.
+///
+/// This outlives [`DimlandData`] so a reconnect brings back the same state,
+/// including anything that was changed after startup.
+#[derive(Debug, Clone)]
+pub struct DimlandSettings {
+  pub alpha: f32,
+  pub radius: Radius,
+  pub invert_corners: bool,
+  pub fillets: Option<Fillets>,
+  pub layer: Layer,
+  pub keyboard: KeyboardInteractivity,
+  /// Exits dimland while the overlay has keyboard focus.
+  pub quit_key: Keysym,
+  pub above_other_layers: bool,
+  pub anchor: Anchor,
+  pub width: Option<Extent>,
+  pub height: Option<Extent>,
+  pub only: Vec<OutputMatcher>,
+  pub brightness_curve: BrightnessCurve,
+  pub gamma: f32,
+  pub contrast: f32,
+  pub curtain: Option<Curtain>,
+  pub curtain_alpha: f32,
+  pub curtain_feather: u32,
+  /// The reading strip while it is up.
+  pub strip: Option<strip::Strip>,
+  /// The height a strip is put up with, the last one it had.
+  pub strip_height: u32,
+  /// From `--region-file`, later ones win where they overlap.
+  pub regions: Vec<Region>,
+  pub image: Option<Rc<Image>>,
+  pub image_fit: ImageFit,
+  /// The image before the last `set image`, while it fades out.
+  pub image_fade: Option<ImageFade>,
+  pub grain: f32,
+  /// Moved on by `--grain-animate`.
+  pub grain_seed: u32,
+  pub scanlines: Option<Scanlines>,
+  pub ambient: Option<AmbientSettings>,
+  pub blackout: bool,
+  pub min_alpha: f32,
+  pub max_alpha: f32,
+  /// Holds even for forced changes, unlike `min_alpha` and `max_alpha`.
+  pub alpha_clamp: AlphaClamp,
+  /// How runtime radius changes move, none when they are instant.
+  pub radius_animation: Option<(Duration, Easing)>,
+  /// How fades without an easing of their own move, like the one of `set image`.
+  pub easing: Easing,
+  pub mirror_alpha: bool,
+  /// Toggled off at runtime, drawn fully transparent.
+  pub hidden: bool,
+  pub pending_override: Option<control::PendingOverride>,
+  /// Where `--restore` saves the state to.
+  pub state_file: Option<StateFile>,
+  pub ramp: Option<Ramp>,
+  /// The blinking after a ramp with `--then flash`.
+  pub flash: Option<ramp::Flash>,
+  /// Set during `dimland blackout`, which is something else than `--blackout`.
+  pub blacked_out: Option<control::Blackout>,
+  pub blackout_timeout: Duration,
+  pub telemetry: bool,
+  pub test_pattern: bool,
+  pub fps_display: bool,
+  pub watchers: control::Watchers,
+  pub metrics: metrics::Metrics,
+  pub stats: stats::Stats,
+  pub frame_callback_surface: bool,
+  pub surface_sync_group: bool,
+  /// Set the opaque region regardless of alpha, otherwise it is only set when nothing shines through.
+  pub opaque: bool,
+  pub prefer_single_pixel_buffer: bool,
+  pub span: bool,
+  pub buffer_age: bool,
+  pub exit_on_no_outputs: bool,
+  pub profiles: BTreeMap<String, Profile>,
+  /// The profile switched to last, if any.
+  pub profile: Option<String>,
+  pub configure_timeout: Duration,
+  pub tint: Tint,
+  pub max_buffer_bytes: u64,
+  /// What sway shows where, with `--sway-focus`.
+  pub workspaces: Option<sway::Workspaces>,
+  pub capture_before_dim: bool,
+  /// Darkens the screenshot of `--capture-before-dim` in CIELAB instead of blending the overlay color.
+  pub preserve_wallpaper_colors: bool,
+  pub clipboard_indicator: bool,
+  /// Whether views keep the compositor from going idle, see `--wl-idle-inhibit`.
+  pub idle_inhibit: bool,
+  pub color_cycle: Option<ColorCycle>,
+  pub alerts: alert::Alerts,
+  /// The bar from `dimland progress` while it shows.
+  pub progress: Option<progress::Progress>,
+  /// Colors of single outputs, the last one matching an output wins.
+  pub output_colors: Vec<OutputColor>,
+  /// Colors of single outputs from `set color`, by monitor so they outlast unplugging it
+  /// and go before `output_colors`.
+  pub remembered_colors: Vec<RememberedColor>,
+  /// Transforms of outputs that report the wrong one, the last one matching an output wins.
+  pub output_transforms: Vec<OutputTransform>,
+  /// Frame rate caps of animations from `--output-refresh-rate` by output name.
+  pub refresh_rates: HashMap<String, u32>,
+  /// The rectangles from `--geometry`, outputs get a view for each one matching them.
+  pub rects: Vec<OutputRect>,
+  /// The regions from the named regions file by name, views show them like `--geometry`.
+  pub named_regions: BTreeMap<String, NamedRegion>,
+  /// Set with `--start-idle` until the first command, no outputs get views meanwhile.
+  pub idle: bool,
+  /// Set with `--at` until that time comes, commands don't end it early.
+  pub scheduled: bool,
+}
+
+impl DimlandSettings {
+  pub fn new(args: &DimlandArgs) -> Self {
+    let tint = if args.prefer_dark_mode
+      || (args.follow_theme && !args.prefer_light_mode && theme::prefers_dark())
+    {
+      Tint::White
+    } else {
+      Tint::Black
+    };
+
+    Self {
+      alpha: match (args.blackout, tint) {
+        (true, _) => 1.0,
+        (false, tint) => args.alpha_clamp.unwrap_or_default().apply(match tint {
+          Tint::Black => args.alpha.unwrap_or(DEFAULT_ALPHA),
+          Tint::White => args.alpha.unwrap_or(DEFAULT_WHITE_ALPHA),
+        }),
+      },
+      radius: if args.blackout {
+        Radius::circle(0)
+      } else {
+        args.radius.unwrap_or(DEFAULT_RADIUS)
+      },
+      invert_corners: args.invert_corners,
+      fillets: args.inverse_corners,
+      layer: if args.background_dim {
+        Layer::Background
+      } else {
+        args.layer.into()
+      },
+      keyboard: args.keyboard.into(),
+      quit_key: args.keyboard_shortcut_quit,
+      above_other_layers: args.above_other_layers,
+      anchor: if args.anchor.is_empty() {
+        Anchor::all()
+      } else {
+        args.anchor.iter().copied().map(Anchor::from).collect()
+      },
+      width: args.width,
+      height: args.height,
+      only: args.only.clone(),
+      brightness_curve: args.brightness_curve,
+      gamma: args.gamma.unwrap_or(DEFAULT_GAMMA),
+      contrast: args.contrast,
+      curtain: args.curtain,
+      curtain_alpha: args.curtain_alpha.clamp(0.0, 1.0),
+      curtain_feather: args.curtain_feather,
+      strip: args.reading_strip.then_some(strip::Strip {
+        y: None,
+        height: args.reading_strip_height,
+      }),
+      strip_height: args.reading_strip_height,
+      regions: Vec::new(),
+      image: None,
+      image_fit: args.image_fit,
+      image_fade: None,
+      grain: args.grain,
+      grain_seed: 0,
+      scanlines: (args.scanlines > 0.0).then_some(Scanlines {
+        strength: args.scanlines,
+        spacing: args.scanline_spacing,
+      }),
+      ambient: args.ambient.then(|| AmbientSettings {
+        darkest_alpha: args.alpha.unwrap_or(DEFAULT_ALPHA),
+        bright_lux: args.ambient_lux,
+        curve: args.ambient_curve,
+        interval: Duration::from_secs(args.ambient_interval),
+      }),
+      blackout: args.blackout,
+      min_alpha: args.min_alpha.unwrap_or(0.0),
+      max_alpha: args.max_alpha.unwrap_or(1.0),
+      alpha_clamp: args.alpha_clamp.unwrap_or_default(),
+      radius_animation: args.animate_radius.map(|transition| {
+        (
+          transition
+            .duration
+            .unwrap_or(Duration::from_millis(args.animate_duration)),
+          transition.easing.unwrap_or(args.easing),
+        )
+      }),
+      easing: args.easing,
+      mirror_alpha: args.mirror_alpha,
+      hidden: false,
+      pending_override: None,
+      state_file: None,
+      blacked_out: None,
+      ramp: None,
+      flash: None,
+      blackout_timeout: args.blackout_timeout,
+      telemetry: args.telemetry,
+      test_pattern: args.test_pattern,
+      fps_display: args.fps_display,
+      watchers: control::Watchers::default(),
+      metrics: metrics::Metrics::default(),
+      stats: stats::Stats::default(),
+      frame_callback_surface: args.frame_callback_surface,
+      surface_sync_group: args.surface_sync_group,
+      opaque: args.opaque,
+      prefer_single_pixel_buffer: args.prefer_single_pixel_buffer,
+      span: args.span,
+      buffer_age: args.buffer_age,
+      exit_on_no_outputs: args.exit_on_no_outputs,
+      profiles: BTreeMap::new(),
+      profile: None,
+      configure_timeout: Duration::from_secs(args.configure_timeout),
+      tint,
+      max_buffer_bytes: args.max_buffer_bytes,
+      workspaces: args.sway_focus.then(sway::Workspaces::default),
+      capture_before_dim: args.capture_before_dim || args.preserve_wallpaper_colors,
+      preserve_wallpaper_colors: args.preserve_wallpaper_colors,
+      clipboard_indicator: args.clipboard_indicator,
+      idle_inhibit: args.wl_idle_inhibit && !args.no_wl_idle_inhibit,
+      color_cycle: (!args.color_cycle.is_empty()).then(|| ColorCycle {
+        colors: args.color_cycle.iter().map(|color| color.0).collect(),
+        period: args.color_cycle_period,
+        start: Instant::now(),
+      }),
+      output_colors: args.output_color.clone(),
+      remembered_colors: Vec::new(),
+      output_transforms: args.output_transform.clone(),
+      refresh_rates: args
+        .output_refresh_rate
+        .iter()
+        .map(|rate| (rate.name.clone(), rate.hz))
+        .collect(),
+      rects: args.geometry.clone(),
+      named_regions: BTreeMap::new(),
+      alerts: alert::Alerts::default(),
+      progress: None,
+      idle: args.start_idle,
+      scheduled: args.at.is_some(),
+    }
+  }
+
+  /// The color the overlay is drawn in right now.
+  pub fn overlay_color(&self) -> [u8; 3] {
+    if self.blacked_out.is_some() {
+      return Tint::Black.rgb();
+    }
+    match &self.color_cycle {
+      Some(cycle) => cycle.color(Instant::now()),
+      None => self.tint.rgb(),
+    }
+  }
+
+  /// The color of this output from `--output-color` or `set color`, if it has its own.
+  pub fn output_color(&self, info: Option<&OutputInfo>) -> Option<[u8; 3]> {
+    let info = info?;
+    if let Some(remembered) = self.remembered_color(info) {
+      return remembered.color.map(|color| color.0);
+    }
+    self
+      .output_colors
+      .iter()
+      .rev()
+      .find(|color| color.matcher.matches(info))?
+      .color
+      .map(|color| color.0)
+  }
+
+  /// The info of an output with the transform from `--output-transform` instead of the reported one.
+  pub fn transformed(&self, mut info: OutputInfo) -> OutputInfo {
+    if let Some(transform) = self
+      .output_transforms
+      .iter()
+      .rev()
+      .find(|transform| transform.matcher.matches(&info))
+    {
+      transform.apply(&mut info);
+    }
+    info
+  }
+
+  /// The least time between two animation frames on an output, none without a cap.
+  pub fn frame_interval(&self, info: Option<&OutputInfo>) -> Option<Duration> {
+    let hz = self.refresh_rates.get(info?.name.as_ref()?)?;
+    Some(Duration::from_secs_f64(1.0 / *hz as f64))
+  }
+
+  /// The color `set color` gave this monitor, also on a connector it was on before.
+  pub fn remembered_color(&self, info: &OutputInfo) -> Option<&RememberedColor> {
+    let identity = OutputIdentity::of(info);
+    self
+      .remembered_colors
+      .iter()
+      .find(|remembered| remembered.identity.same_monitor(&identity))
+  }
+
+  /// Where the color of an output comes from for `status`, none for the usual one.
+  pub fn color_source(&self, info: &OutputInfo) -> Option<&'static str> {
+    if self.remembered_color(info).is_some() {
+      Some("remembered")
+    } else if self
+      .output_colors
+      .iter()
+      .any(|color| color.matcher.matches(info))
+    {
+      Some("configured")
+    } else {
+      None
+    }
+  }
+
+  /// Whether views repaint every frame, even when nothing is animating.
+  pub fn continuous(&self) -> bool {
+    self.fps_display || self.color_cycle.is_some()
+  }
+
+  /// Whether a buffer of this size stays within `--max-buffer-bytes`.
+  pub fn buffer_fits(&self, width: u32, height: u32) -> bool {
+    width as u64 * height as u64 * 4 <= self.max_buffer_bytes
+  }
+
+  /// Like [`Self::buffer_fits`], with an error to tell why not.
+  pub fn check_buffer(&self, width: u32, height: u32) -> Result<(), DimlandError> {
+    match self.buffer_fits(width, height) {
+      true => Ok(()),
+      false => Err(DimlandError::BufferTooLarge {
+        width,
+        height,
+        max_bytes: self.max_buffer_bytes,
+      }),
+    }
+  }
+
+  /// Reads the region file, image and profiles the arguments point at, and the named regions.
+  pub fn load_files(&mut self, args: &DimlandArgs) -> Result<(), DimlandError> {
+    if let Some(path) = &args.region_file {
+      self.regions = regions::load(path).map_err(DimlandError::InvalidConfig)?;
+    }
+    if let Some(path) = &args.image {
+      self.image = Some(Rc::new(
+        Image::load(path).map_err(DimlandError::InvalidConfig)?,
+      ));
+    }
+    self.profiles = profiles::load().map_err(DimlandError::InvalidConfig)?;
+    self.named_regions = named_regions::load().map_err(DimlandError::InvalidConfig)?;
+    // the same as --geometry conflicts with, which clap can't check for a file
+    let conflicting = !args.anchor.is_empty()
+      || args.width.is_some()
+      || args.height.is_some()
+      || args.fallback_window
+      || args.capture_before_dim
+      || args.preserve_wallpaper_colors;
+    if !self.named_regions.is_empty() && conflicting {
+      return Err(DimlandError::InvalidConfig(
+        "named regions can't be used with --anchor, --width, --height, --fallback-window, \
+         --capture-before-dim or --preserve-wallpaper-colors"
+          .to_string(),
+      ));
+    }
+    Ok(())
+  }
+
+  /// Whether outputs go without views for now, see `--start-idle` and `--at`.
+  pub fn dormant(&self) -> bool {
+    self.idle || self.scheduled
+  }
+
+  /// Whether the output is selected by `--only`.
+  pub fn wants_output(&self, info: Option<&OutputInfo>) -> bool {
+    self.only.is_empty() || info.is_some_and(|info| self.only.iter().any(|only| only.matches(info)))
+  }
+
+  /// The rectangles to give views on an output together with the named region they show,
+  /// a single one covering all of it without `--geometry` or named regions.
+  pub fn view_rects(&self, info: Option<&OutputInfo>) -> Vec<(Option<Rect>, Option<String>)> {
+    if self.rects.is_empty() && self.named_regions.is_empty() {
+      return vec![(None, None)];
+    }
+    let matches = |rect: &OutputRect| info.is_some_and(|info| rect.matcher.matches(info));
+    let rects = self
+      .rects
+      .iter()
+      .filter(|rect| matches(rect))
+      .map(|rect| (Some(rect.rect), None));
+    let named = self
+      .named_regions
+      .iter()
+      .filter(|(_, region)| matches(&region.geometry))
+      .map(|(name, region)| (Some(region.geometry.rect), Some(name.clone())));
+    rects.chain(named).collect()
+  }
+
+  /// The anchors and size to ask for on an output with the given logical size.
+  ///
+  /// Dimensions that cover the whole output are left at 0 and anchored to both edges,
+  /// so the compositor picks the real size and tells us in the configure.
+  pub fn layer_geometry(&self, logical_size: Option<(i32, i32)>) -> (Anchor, u32, u32) {
+    let mut anchor = self.anchor;
+    let mut resolve =
+      |extent: Option<Extent>, full: Option<i32>, edges: Anchor| match (extent, full) {
+        // a size of 0 is only allowed with both edges anchored, tiny percentages stay a pixel
+        (Some(extent), Some(full)) => extent.resolve(full as u32).max(1),
+        (Some(Extent::Pixels(pixels)), None) => pixels,
+        _ => {
+          anchor |= edges;
+          0
+        }
+      };
+    let width = resolve(
+      self.width,
+      logical_size.map(|(width, _)| width),
+      Anchor::LEFT | Anchor::RIGHT,
+    );
+    let height = resolve(
+      self.height,
+      logical_size.map(|(_, height)| height),
+      Anchor::TOP | Anchor::BOTTOM,
+    );
+    (anchor, width, height)
+  }
+
+  /// The size of the overlay on an output with the given logical size.
+  pub fn overlay_size(&self, logical_size: Option<(i32, i32)>) -> (u32, u32) {
+    let (width, height) =
+      logical_size.map_or((0, 0), |(width, height)| (width as u32, height as u32));
+    (
+      self.width.map_or(width, |extent| extent.resolve(width)),
+      self.height.map_or(height, |extent| extent.resolve(height)),
+    )
+  }
+}
+
+pub struct DimlandData {
+  compositor: CompositorState,
+  registry_state: RegistryState,
+  output_state: OutputState,
+  /// Keyboards to hear the quit key on, only asked for with `--keyboard`.
+  seats: Seats,
+  shell: DimlandShell,
+  /// Scales buffers to the surface size, without it buffers match the surface exactly.
+  viewporter: Option<SimpleGlobal<WpViewporter, 1>>,
+  settings: DimlandSettings,
+  views: Vec<DimlandView>,
+  exit: bool,
+  shm: Shm,
+  qh: QueueHandle<Self>,
+  loop_handle: LoopHandle<'static, Self>,
+  telemetry: Option<Telemetry>,
+  screencopy: Option<Screencopy>,
+  /// Watches the clipboard for `--clipboard-indicator`.
+  clipboard: Option<Clipboard>,
+  /// Gives views their inhibitor with `--wl-idle-inhibit`.
+  idle_inhibit: Option<IdleInhibit>,
+  repaint_scheduled: bool,
+  clock: Option<ClockSurface>,
+  /// The geometry of each output as of its last update, compositors repeat unchanged ones.
+  geometries: HashMap<WlOutput, OutputGeometry>,
+  sync_group: Option<SyncGroup>,
+  /// Creates the one-pixel buffers of `--prefer-single-pixel-buffer`, which needs the viewporter too.
+  single_pixel: Option<SimpleGlobal<WpSinglePixelBufferManagerV1, 1>>,
+}
+
+impl ShmHandler for DimlandData {
+  fn shm_state(&mut self) -> &mut Shm {
+    &mut self.shm
+  }
+}
+
+struct DimlandView {
+  /// Until the first frame is drawn, which waits for the screenshot with `--capture-before-dim`.
+  first_configure: bool,
+  /// Whether the compositor configured the view at all.
+  configured: bool,
+  /// Views still waiting for their first configure after `--configure-timeout` get dropped.
+  created_at: Instant,
+  width: u32,
+  height: u32,
+  /// Allocated with the size of the first configure.
+  buffer: Option<DimlandBuffer>,
+  viewport: Option<WpViewport>,
+  surface: OverlaySurface,
+  output: WlOutput,
+  bump: Option<BumpSurface>,
+  /// The radius drawn right now, which lags behind the target while animating.
+  current_radius: Radius,
+  /// Moves the horizontal and vertical radius towards the target.
+  radius_tweens: Option<[Tween; 2]>,
+  /// Scales the alpha of this output, see `--mirror-alpha`.
+  alpha_factor: f32,
+  tracker: Option<Tracker>,
+  /// Alpha, radius and hidden state the buffer was last rendered with.
+  painted: Option<(f32, Radius, bool)>,
+  /// Whether a frame callback was asked for on the surface that hasn't arrived yet.
+  frame_pending: bool,
+  /// Counts frames for `--fps-display`.
+  fps: Option<FpsCounter>,
+  /// The cap from `--output-refresh-rate`, frame callbacks arriving sooner draw nothing.
+  frame_interval: Option<Duration>,
+  /// When the next animation frame may be drawn with a cap.
+  next_frame: Option<Instant>,
+  /// Whether it was already said that the radius doesn't fit on this output.
+  warned_radius: bool,
+  /// The screenshot for `--capture-before-dim`, the view isn't shown while it is taken.
+  capture: Option<Capture>,
+  /// Drawn but not committed yet, waiting for the burst after the current dispatch.
+  commit_held: bool,
+  /// The size the opaque region was set to, none while there is none.
+  opaque_region: Option<(u32, u32)>,
+  /// Where the output sits among all of them with `--span`.
+  span: Option<Span>,
+  /// The color of this output alone, see `--output-color`.
+  color: Option<[u8; 3]>,
+  /// When the clipboard badge started showing on this view, see `--clipboard-indicator`.
+  badge: Option<Instant>,
+  /// The part of the output this view covers with `--geometry`, none for all of it.
+  rect: Option<Rect>,
+  /// The named region this view shows, see [`named_regions`].
+  region: Option<String>,
+  /// Keeps the compositor from going idle while the view shows, see `--wl-idle-inhibit`.
+  inhibitor: Option<ZwpIdleInhibitorV1>,
+}
+
+/// How a view paces the commits of what it drew.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommitMode {
+  /// Commits every change with the next burst and never asks for frame callbacks,
+  /// a static overlay only changes when told to and has nothing to pace.
+  Immediate,
+  /// Asks for a frame callback with every commit and draws again once it arrives.
+  FrameDriven,
+}
+
+/// An shm buffer together with the pool backing it, so it can be drawn again.
+struct DimlandBuffer {
+  pool: RawPool,
+  usage: stats::PoolUsage,
+  buffer: WlBuffer,
+  width: u32,
+  height: u32,
+  format: PixelFormat,
+  /// What changed since the buffer was last committed.
+  damage: Option<Damage>,
+  /// With `--prefer-single-pixel-buffer` the pool only holds the one pixel rendered,
+  /// and the buffer is made from its color.
+  single_pixel: Option<(WpSinglePixelBufferManagerV1, QueueHandle<DimlandData>)>,
+}
+
+impl DimlandBuffer {
+  /// Replaces the buffer with one of the new size, growing the pool if it's too small.
+  fn resize(
+    &mut self,
+    qh: &QueueHandle<DimlandData>,
+    width: u32,
+    height: u32,
+    opaque: bool,
+  ) -> Result<(), DimlandError> {
+    self
+      .pool
+      .resize(width as usize * height as usize * 4)
+      .map_err(|err| DimlandError::Shm {
+        width,
+        height,
+        source: err.into(),
+      })?;
+    self.usage.grow(&self.pool);
+    self.buffer.destroy();
+    self.buffer = self.pool.create_buffer(
+      0,
+      width as i32,
+      height as i32,
+      width as i32 * 4,
+      self.format.wl_format(opaque),
+      (),
+      qh,
+    );
+    (self.width, self.height) = (width, height);
+    self.damage = Some(Damage::full(width, height));
+    Ok(())
+  }
+
+  /// Replaces a single-pixel buffer with one of the color just rendered to the pool.
+  fn refresh_single_pixel(&mut self) {
+    let Some((manager, qh)) = &self.single_pixel else {
+      return;
+    };
+    // created as Argb8888, so premultiplied and in little endian
+    let [b, g, r, a] = self.pool.mmap()[..4] else {
+      return;
+    };
+    let channel = |value: u8| value as u32 * 0x0101_0101;
+    self.buffer.destroy();
+    self.buffer =
+      manager.create_u32_rgba_buffer(channel(r), channel(g), channel(b), channel(a), qh, ());
+  }
+
+  /// Damages what changed since the last commit, or all of it without `--buffer-age`.
+  fn damage_surface(&mut self, surface: &WlSurface, buffer_age: bool) {
+    let Some(damage) = self.damage.take() else {
+      return;
+    };
+    let damage = if buffer_age {
+      damage
+    } else {
+      Damage::full(self.width, self.height)
+    };
+    surface.damage_buffer(
+      damage.x as i32,
+      damage.y as i32,
+      damage.width as i32,
+      damage.height as i32,
+    );
+  }
+}
+
+/// Transparent 1x1 overlay surface mapped next to the dim surface.
+///
+/// Some compositors only re-sort the overlay layer when a surface maps into it,
+/// so mapping this one after the dim surface was created pushes the dim surface
+/// back above layer surfaces that appeared before dimland did.
+struct BumpSurface {
+  layer: LayerSurface,
+  buffer: WlBuffer,
+}
+
+/// Hidden 1x1 surface without a role that only exists to time animations.
+///
+/// Frame callbacks on the overlay itself can stall while the overlay is covered,
+/// see `--frame-callback-surface`.
+struct ClockSurface {
+  surface: WlSurface,
+  buffer: WlBuffer,
+  frame_pending: bool,
+}
+
+impl ClockSurface {
+  fn new(
+    compositor: &CompositorState,
+    shm: &Shm,
+    stats: &stats::Stats,
+    qh: &QueueHandle<DimlandData>,
+  ) -> Result<Self, DimlandError> {
+    let mut pool = RawPool::new(4, shm).map_err(|source| DimlandError::Shm {
+      width: 1,
+      height: 1,
+      source,
+    })?;
+    let surface = compositor.create_surface(qh);
+    let _usage = stats.map(&pool);
+    pool.mmap().fill(0);
+    let buffer = pool.create_buffer(0, 1, 1, 4, Format::Argb8888, (), qh);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.commit();
+
+    Ok(Self {
+      surface,
+      buffer,
+      frame_pending: false,
+    })
+  }
+
+  /// Asks for the next frame, once no matter how many views are animating.
+  fn request_frame(&mut self, qh: &QueueHandle<DimlandData>) {
+    if self.frame_pending {
+      return;
+    }
+
+    self.frame_pending = true;
+    self.surface.frame(qh, self.surface.clone());
+    self.surface.commit();
+  }
+}
+
+impl DimlandData {
+  pub fn new(
+    compositor: CompositorState,
+    globals: &GlobalList,
+    qh: &QueueHandle<Self>,
+    shell: DimlandShell,
+    shm: Shm,
+    settings: DimlandSettings,
+    loop_handle: LoopHandle<'static, Self>,
+  ) -> Self {
+    let clock = settings
+      .frame_callback_surface
+      .then(|| ClockSurface::new(&compositor, &shm, &settings.stats, qh))
+      .and_then(|clock| {
+        clock
+          .map_err(|err| eprintln!("{err}, timing animations with the overlay instead"))
+          .ok()
+      });
+    let telemetry = settings
+      .telemetry
+      .then(|| Telemetry::bind(globals, qh))
+      .flatten();
+    let screencopy = settings
+      .capture_before_dim
+      .then(|| Screencopy::bind(globals, qh))
+      .flatten();
+    let clipboard = settings
+      .clipboard_indicator
+      .then(|| Clipboard::bind(globals, qh))
+      .flatten();
+    let idle_inhibit = settings
+      .idle_inhibit
+      .then(|| IdleInhibit::bind(globals, qh))
+      .flatten();
+    let sync_group = settings.surface_sync_group.then(|| SyncGroup::new(globals));
+    let viewporter = SimpleGlobal::<wp_viewporter::WpViewporter, 1>::bind(globals, qh)
+      .inspect_err(|_| {
+        eprintln!(
+          "wp_viewporter is not available, buffers are sized to the surface instead of scaled"
+        )
+      })
+      .ok();
+    let single_pixel = match settings.prefer_single_pixel_buffer && viewporter.is_some() {
+      true => SimpleGlobal::bind(globals, qh)
+        .inspect_err(|_| {
+          eprintln!("wp_single_pixel_buffer_manager_v1 is not available, rendering whole buffers")
+        })
+        .ok(),
+      false => None,
+    };
+    Self {
+      compositor,
+      registry_state: RegistryState::new(globals),
+      output_state: OutputState::new(globals, qh),
+      seats: Seats::new(SeatState::new(globals, qh)),
+      shell,
+      viewporter,
+      settings,
+      views: Vec::new(),
+      exit: false,
+      shm,
+      qh: qh.clone(),
+      loop_handle,
+      telemetry,
+      screencopy,
+      clipboard,
+      idle_inhibit,
+      repaint_scheduled: false,
+      clock,
+      geometries: HashMap::new(),
+      sync_group,
+      single_pixel,
+    }
+  }
+
+  pub fn should_exit(&self) -> bool {
+    self.exit
+  }
+
+  /// What the overlay goes by for an output, its transform overridden by `--output-transform`.
+  fn output_info(&self, output: &WlOutput) -> Option<OutputInfo> {
+    let info = self.output_state.info(output)?;
+    Some(self.settings.transformed(info))
+  }
+
+  /// Whether this output should get a view at all.
+  fn wants_output(&self, output: &WlOutput) -> bool {
+    self
+      .settings
+      .wants_output(self.output_state.info(output).as_ref())
+  }
+
+  /// Complains about `--only` values that don't match any output.
+  pub fn warn_missing_outputs(&self) {
+    let infos: Vec<OutputInfo> = self
+      .output_state
+      .outputs()
+      .filter_map(|output| self.output_state.info(&output))
+      .collect();
+    let names: Vec<&str> = infos
+      .iter()
+      .filter_map(|info| info.name.as_deref())
+      .collect();
+    let missing: Vec<String> = self
+      .settings
+      .only
+      .iter()
+      .filter(|only| !infos.iter().any(|info| only.matches(info)))
+      .map(OutputMatcher::to_string)
+      .collect();
+
+    if !missing.is_empty() {
+      eprintln!(
+        "no output matches {}, the connected outputs are {}",
+        missing.join(", "),
+        names.join(", ")
+      );
+    }
+  }
+
+  /// Changes the alpha at runtime, within `--min-alpha` and `--max-alpha`.
+  pub fn set_alpha(&mut self, alpha: f32) {
+    self.force_alpha(alpha.clamp(self.settings.min_alpha, self.settings.max_alpha));
+  }
+
+  /// Changes the alpha at runtime and schedules a repaint with it.
+  ///
+  /// Only `--alpha-clamp` still holds, except during a blackout.
+  pub fn force_alpha(&mut self, alpha: f32) {
+    let alpha = match self.settings.blacked_out {
+      Some(_) => alpha.clamp(0.0, 1.0),
+      None => self.settings.alpha_clamp.apply(alpha),
+    };
+    if alpha == self.settings.alpha {
+      return;
+    }
+
+    let previous = std::mem::replace(&mut self.settings.alpha, alpha);
+    self
+      .settings
+      .watchers
+      .emit(&format!("alpha {previous:.2}→{alpha:.2}"));
+    self.schedule_repaint();
+  }
+
+  /// Puts up, moves or takes down the curtain at runtime.
+  pub fn set_curtain(&mut self, curtain: Option<Curtain>) {
+    if curtain == self.settings.curtain {
+      return;
+    }
+
+    self.settings.curtain = curtain;
+    self.settings.watchers.emit(&match curtain {
+      Some(curtain) => format!("curtain {curtain}"),
+      None => "curtain none".to_string(),
+    });
+    // the curtain isn't part of what views compare to know whether they are up to date
+    self.repaint_all();
+  }
+
+  /// Replaces the regions from `--region-file`.
+  pub fn set_regions(&mut self, regions: Vec<Region>) {
+    if regions == self.settings.regions {
+      return;
+    }
+    self.settings.regions = regions;
+    self
+      .settings
+      .watchers
+      .emit(&format!("regions {}", self.settings.regions.len()));
+    self.repaint_all();
+  }
+
+  /// Repaints every view, even those that look up to date.
+  fn repaint_all(&mut self) {
+    for view in &mut self.views {
+      view.painted = None;
+    }
+    self.schedule_repaint();
+  }
+
+  /// Changes the corner radius at runtime, animating it if `--animate-radius` is given.
+  pub fn set_radius(&mut self, radius: Radius) {
+    if radius == self.settings.radius {
+      return;
+    }
+
+    let previous = std::mem::replace(&mut self.settings.radius, radius);
+    self
+      .settings
+      .watchers
+      .emit(&format!("radius {previous}→{radius}"));
+    for view in &mut self.views {
+      // named regions with a radius of their own keep it
+      if self.settings.region_radius(view.region.as_deref()) == radius {
+        view.set_radius(&self.qh, radius, &self.settings);
+      }
+    }
+    self.settle_radius();
+  }
+
+  /// Warns about radii that don't fit and drives the animations [`DimlandView::set_radius`] started.
+  fn settle_radius(&mut self) {
+    for index in 0..self.views.len() {
+      self.warn_radius(index);
+    }
+    if let Some(clock) = &mut self.clock {
+      if self.views.iter().any(|view| view.radius_tweens.is_some()) {
+        clock.request_frame(&self.qh);
+      }
+    }
+    self.schedule_repaint();
+  }
+
+  /// The factor for the alpha of this output, 1 unless `--mirror-alpha` or `--sway-focus` is given.
+  fn alpha_factor(&self, output: &WlOutput) -> f32 {
+    if self.settings.blacked_out.is_some() {
+      return 1.0;
+    }
+    let focused = self
+      .settings
+      .workspaces
+      .as_ref()
+      .and_then(|workspaces| workspaces.focused.as_ref());
+    let name = self.output_state.info(output).and_then(|info| info.name);
+    if focused.is_some() && focused == name.as_ref() {
+      return 0.0;
+    }
+    if !self.settings.mirror_alpha {
+      return 1.0;
+    }
+
+    let infos: Vec<_> = self
+      .views
+      .iter()
+      .map(|view| &view.output)
+      .chain([output])
+      .filter_map(|output| self.output_state.info(output))
+      .collect();
+    self
+      .output_state
+      .info(output)
+      .map_or(1.0, |info| outputs::mirror_alpha_factor(&info, &infos))
+  }
+
+  /// Recomputes the alpha factors after the set of outputs changed.
+  fn refresh_alpha_factors(&mut self) {
+    if !self.settings.mirror_alpha && self.settings.workspaces.is_none() {
+      return;
+    }
+
+    let factors: Vec<f32> = self
+      .views
+      .iter()
+      .map(|view| self.alpha_factor(&view.output))
+      .collect();
+    for (view, factor) in self.views.iter_mut().zip(factors) {
+      if view.alpha_factor != factor {
+        view.alpha_factor = factor;
+        view.redraw(&self.settings);
+      }
+    }
+  }
+
+  /// Recomputes where every view sits among all outputs after they moved, see `--span`.
+  fn refresh_spans(&mut self) {
+    if !self.settings.span {
+      return;
+    }
+
+    let infos: Vec<_> = self
+      .output_state
+      .outputs()
+      .filter_map(|output| self.output_info(&output))
+      .collect();
+    let spans: Vec<Option<Span>> = self
+      .views
+      .iter()
+      .map(|view| {
+        let info = self.output_info(&view.output)?;
+        let span = outputs::span(&info, &infos)?;
+        Some(view.rect.map_or(span, |rect| rect.within(span)))
+      })
+      .collect();
+    for (view, span) in self.views.iter_mut().zip(spans) {
+      if view.span != span {
+        view.span = span;
+        view.painted = None;
+      }
+    }
+    self.schedule_repaint();
+  }
+
+  /// Looks up the color of every view again after outputs or their colors changed.
+  fn refresh_colors(&mut self) {
+    let colors: Vec<Option<[u8; 3]>> = self
+      .views
+      .iter()
+      .map(|view| {
+        let info = self.output_state.info(&view.output);
+        self
+          .settings
+          .region_color(view.region.as_deref(), info.as_ref())
+      })
+      .collect();
+    for (view, color) in self.views.iter_mut().zip(colors) {
+      if view.color != color {
+        view.color = color;
+        view.painted = None;
+      }
+    }
+    self.schedule_repaint();
+  }
+
+  /// Gives the output named `output` its own color at runtime, or takes it away with none.
+  ///
+  /// The color sticks to the monitor, so it comes back with it after unplugging or standby
+  /// even on another connector.
+  pub fn set_output_color(&mut self, output: &str, color: Option<Color>) -> Result<(), String> {
+    let info = self
+      .views
+      .iter()
+      .filter_map(|view| self.output_state.info(&view.output))
+      .find(|info| {
+        info
+          .name
+          .as_ref()
+          .is_some_and(|name| name.eq_ignore_ascii_case(output))
+      })
+      .ok_or_else(|| format!("no dimmed output named {output}"))?;
+
+    let identity = OutputIdentity::of(&info);
+    // an earlier color for the same monitor would only pile up behind this one
+    self
+      .settings
+      .remembered_colors
+      .retain(|remembered| !remembered.identity.same_monitor(&identity));
+    self
+      .settings
+      .remembered_colors
+      .push(RememberedColor { identity, color });
+    self.settings.watchers.emit(&match color {
+      Some(color) => format!("color {output} {color}"),
+      None => format!("color {output} none"),
+    });
+    self.refresh_colors();
+    Ok(())
+  }
+
+  /// Hides or shows the overlay at runtime.
+  pub fn set_hidden(&mut self, hidden: bool) {
+    if hidden == self.settings.hidden {
+      return;
+    }
 
-fn main() {
-  let args = DimlandArgs::parse();
+    self.settings.hidden = hidden;
+    self.settings.watchers.emit(match hidden {
+      true => "toggle off",
+      false => "toggle on",
+    });
+    self.schedule_repaint();
+  }
 
-  let conn = Connection::connect_to_env().expect("where are you running this");
+  /// Creates the views held back by `--start-idle`.
+  pub fn wake(&mut self) {
+    if !std::mem::take(&mut self.settings.idle) || self.settings.scheduled {
+      return;
+    }
+    self.add_views();
+  }
 
-  let (globals, mut event_queue) = registry_queue_init(&conn).expect("queueless");
-  let qh = event_queue.handle();
+  /// Creates the views held back by `--at` once its time comes, even without a command.
+  pub fn wake_scheduled(&mut self) {
+    self.settings.idle = false;
+    self.settings.scheduled = false;
+    self.add_views();
+  }
 
-  let compositor = CompositorState::bind(&globals, &qh).expect("no compositor :sukia:");
-  let layer_shell = LayerShell::bind(&globals, &qh).expect("huh?");
-  let shm = Shm::bind(&globals, &qh).expect("wl_shm is not available");
+  fn add_views(&mut self) {
+    let outputs: Vec<WlOutput> = self.output_state.outputs().collect();
+    for output in outputs {
+      self.add_view(output);
+    }
+  }
 
-  let alpha = args.alpha.unwrap_or(DEFAULT_ALPHA);
-  let radius = args.radius.unwrap_or(DEFAULT_RADIUS);
-  let mut data = DimlandData::new(compositor, &globals, &qh, layer_shell, alpha, radius, shm);
+  /// Gives a selected output a view for each of its rectangles, unless its buffer would be too large.
+  fn add_view(&mut self, output: WlOutput) {
+    if !self.wants_output(&output) {
+      return;
+    }
 
-  while !data.should_exit() {
-    event_queue.blocking_dispatch(&mut data).expect("sus");
+    let info = self.output_info(&output);
+    for (rect, region) in self.settings.view_rects(info.as_ref()) {
+      // the buffer gets the logical size, refuse before asking for a surface that large
+      let size = match rect {
+        Some(rect) => Some((rect.width, rect.height)),
+        None => info
+          .as_ref()
+          .and_then(|info| info.logical_size)
+          .map(|(width, height)| (width as u32, height as u32)),
+      };
+      if let Some((width, height)) = size {
+        if let Err(err) = self.settings.check_buffer(width, height) {
+          self.warn_too_large(&output, err);
+          continue;
+        }
+      }
+      let mut view = self.create_view(&self.qh, output.clone(), rect, region);
+      view.frame_interval = self.settings.frame_interval(info.as_ref());
+      self.views.push(view);
+    }
+    self.refresh_alpha_factors();
+    self.refresh_spans();
+    self.refresh_colors();
   }
-}
 
-pub struct DimlandData {
-  compositor: CompositorState,
-  registry_state: RegistryState,
-  output_state: OutputState,
-  layer_shell: LayerShell,
-  viewporter: SimpleGlobal<WpViewporter, 1>,
-  alpha: f32,
-  radius: u32,
-  views: Vec<DimlandView>,
-  exit: bool,
-  shm: Shm,
-}
+  /// Repaints the views once the event loop is done with the current batch of events.
+  ///
+  /// A flood of commands then costs one repaint per loop iteration instead of one each.
+  fn schedule_repaint(&mut self) {
+    if self.repaint_scheduled {
+      return;
+    }
 
-impl ShmHandler for DimlandData {
-  fn shm_state(&mut self) -> &mut Shm {
-    &mut self.shm
+    self.repaint_scheduled = true;
+    self.loop_handle.insert_idle(|data| {
+      data.repaint_scheduled = false;
+      for index in 0..data.views.len() {
+        let view = &data.views[index];
+        let (width, height) = (view.width, view.height);
+        if view.buffer.is_some() {
+          data.update_opaque_region(index, width, height);
+        }
+      }
+      for view in &mut data.views {
+        view.refresh(&data.settings);
+      }
+    });
   }
-}
 
-struct DimlandView {
-  first_configure: bool,
-  width: u32,
-  height: u32,
-  buffer: WlBuffer,
-  viewport: WpViewport,
-  layer: LayerSurface,
-  output: WlOutput,
-}
+  fn emit_output_event(&self, event: &str, output: &WlOutput) {
+    let name = self.output_state.info(output).and_then(|info| info.name);
+    self
+      .settings
+      .watchers
+      .emit(&format!("{event} {}", name.as_deref().unwrap_or("unknown")));
+  }
 
-impl DimlandData {
-  pub fn new(
-    compositor: CompositorState,
-    globals: &GlobalList,
+  /// Tears down all views and hands back the settings they were drawn with.
+  pub fn into_settings(mut self) -> DimlandSettings {
+    self.views.clear();
+    self.settings
+  }
+
+  fn create_view(
+    &self,
     qh: &QueueHandle<Self>,
-    layer_shell: LayerShell,
-    alpha: f32,
-    radius: u32,
-    shm: Shm,
-  ) -> Self {
-    Self {
-      compositor,
-      registry_state: RegistryState::new(globals),
-      output_state: OutputState::new(globals, qh),
-      layer_shell,
-      viewporter: SimpleGlobal::<wp_viewporter::WpViewporter, 1>::bind(globals, qh)
-        .expect("wp_viewporter not available"),
-      radius,
-      alpha,
-      views: Vec::new(),
-      exit: false,
-      shm,
+    output: WlOutput,
+    rect: Option<Rect>,
+    region: Option<String>,
+  ) -> DimlandView {
+    let surface = match &self.shell {
+      DimlandShell::Layer(layer_shell) => {
+        OverlaySurface::Layer(self.create_layer_surface(layer_shell, qh, &output, rect))
+      }
+      DimlandShell::Window(xdg_shell) => {
+        let surface = self.compositor.create_surface(qh);
+        // clicks go to whatever is below, as far as the compositor lets them
+        let region = self.compositor.wl_compositor().create_region(qh, ());
+        surface.set_input_region(Some(&region));
+        OverlaySurface::Window(shell::create_window(xdg_shell, surface, qh, &output))
+      }
+    };
+
+    let viewport = self.viewporter.as_ref().map(|viewporter| {
+      viewporter
+        .get()
+        .expect("wp_viewporter failed")
+        .get_viewport(surface.wl_surface(), qh, ())
+    });
+
+    let alpha_factor = self.alpha_factor(&output);
+    let bump = match &self.shell {
+      DimlandShell::Layer(layer_shell) if self.settings.above_other_layers => {
+        match self.create_bump_surface(layer_shell, qh, &output) {
+          Ok(bump) => Some(bump),
+          Err(err) => {
+            eprintln!("{err}, staying below other overlays");
+            None
+          }
+        }
+      }
+      _ => None,
+    };
+
+    let tracker = self.telemetry.as_ref().map(|telemetry| {
+      let name = self.output_state.info(&output).and_then(|info| info.name);
+      telemetry.tracker(qh, name.unwrap_or_else(|| "unknown".to_string()))
+    });
+
+    let capture = self
+      .screencopy
+      .as_ref()
+      .map(|screencopy| screencopy.capture(qh, &output));
+
+    let mut view = DimlandView::new(
+      viewport,
+      surface,
+      output,
+      bump,
+      self.settings.region_radius(region.as_deref()),
+      alpha_factor,
+    );
+    view.tracker = tracker;
+    view.fps = self.settings.fps_display.then(FpsCounter::default);
+    view.capture = capture;
+    view.rect = rect;
+    view.region = region;
+    view.inhibitor = self
+      .idle_inhibit
+      .as_ref()
+      .map(|inhibit| inhibit.inhibit(view.surface.wl_surface(), qh));
+    if self.settings.blacked_out.is_some() {
+      self.set_grab(&view, true);
     }
+
+    self
+      .loop_handle
+      .insert_source(
+        Timer::from_duration(self.settings.configure_timeout),
+        |_, _, data| {
+          data.remove_unconfigured_views();
+          TimeoutAction::Drop
+        },
+      )
+      .expect("can't start the configure timer");
+    view
   }
 
-  pub fn should_exit(&self) -> bool {
-    self.exit
+  /// Drops views the compositor silently rejected by never configuring them.
+  fn remove_unconfigured_views(&mut self) {
+    let timeout = self.settings.configure_timeout;
+    let (zombies, views): (Vec<_>, Vec<_>) = std::mem::take(&mut self.views)
+      .into_iter()
+      .partition(|view| !view.configured && view.created_at.elapsed() >= timeout);
+    self.views = views;
+    if zombies.is_empty() {
+      return;
+    }
+
+    for zombie in &zombies {
+      let name = self
+        .output_state
+        .info(&zombie.output)
+        .and_then(|info| info.name);
+      eprintln!(
+        "the compositor didn't configure the overlay on {} within {}s, giving up on it",
+        name.as_deref().unwrap_or("unknown"),
+        timeout.as_secs()
+      );
+    }
+    self.refresh_alpha_factors();
   }
 
-  fn create_view(&self, qh: &QueueHandle<Self>, output: WlOutput) -> DimlandView {
-    let layer = self.layer_shell.create_layer_surface(
+  fn create_layer_surface(
+    &self,
+    layer_shell: &LayerShell,
+    qh: &QueueHandle<Self>,
+    output: &WlOutput,
+    rect: Option<Rect>,
+  ) -> LayerSurface {
+    let layer = layer_shell.create_layer_surface(
       qh,
       self.compositor.create_surface(qh),
-      Layer::Overlay,
+      self.settings.layer,
       Some("dimland_layer"),
-      Some(&output),
+      Some(output),
     );
 
-    let (width, height) = if let Some((width, height)) = self
-      .output_state
-      .info(&output)
-      .and_then(|info| info.logical_size)
-    {
-      (width as u32, height as u32)
-    } else {
-      (0, 0)
+    let (anchor, width, height) = match rect {
+      // margins count from the edge of the output, as the exclusive zone below ignores panels
+      Some(rect) => {
+        layer.set_margin(rect.y as i32, 0, 0, rect.x as i32);
+        (Anchor::TOP | Anchor::LEFT, rect.width, rect.height)
+      }
+      None => self
+        .settings
+        .layer_geometry(self.output_info(output).and_then(|info| info.logical_size)),
     };
 
-    layer.set_exclusive_zone(-1);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
-    let region = self.compositor.wl_compositor().create_region(qh, ());
-    layer.set_input_region(Some(&region));
+    layer.set_anchor(anchor);
+    layer.set_keyboard_interactivity(self.settings.keyboard);
+    if self.settings.layer == Layer::Background {
+      // behave like a wallpaper: respect panels and take input
+      layer.set_exclusive_zone(0);
+    } else {
+      layer.set_exclusive_zone(-1);
+      let region = self.compositor.wl_compositor().create_region(qh, ());
+      layer.set_input_region(Some(&region));
+    }
     layer.set_size(width, height);
     layer.commit();
+    layer
+  }
 
-    let viewport = self
-      .viewporter
-      .get()
-      .expect("wp_viewporter failed")
-      .get_viewport(layer.wl_surface(), qh, ());
+  /// Says once per output when the radius is too large for it and gets shrunk.
+  fn warn_radius(&mut self, index: usize) {
+    // a single pixel has no corners, which --prefer-single-pixel-buffer already says
+    if self.single_pixel.is_some() {
+      return;
+    }
+    let view = &self.views[index];
+    let radius = self.settings.region_radius(view.region.as_deref());
+    let Some(effective) = view.effective_radius(radius) else {
+      return;
+    };
+    if view.warned_radius || effective == radius {
+      return;
+    }
 
-    let mut pool = RawPool::new(width as usize * height as usize * 4, &self.shm).unwrap();
-    let canvas = pool.mmap();
+    let name = self
+      .output_state
+      .info(&view.output)
+      .and_then(|info| info.name);
+    eprintln!(
+      "radius {radius} is more than half the size of {}, using {effective}",
+      name.as_deref().unwrap_or("unknown")
+    );
+    self.views[index].warned_radius = true;
+  }
 
-    // TODO: corner calc is kinda wrong?
-    // see file:///stuff/screenshots/24-05-02T20-36-18.png
-    // can't be bothered right now though for it is good enough
+  fn warn_too_large(&self, output: &WlOutput, err: DimlandError) {
+    let name = self.output_state.info(output).and_then(|info| info.name);
+    eprintln!(
+      "not dimming {}, {err}",
+      name.as_deref().unwrap_or("unknown")
+    );
+  }
 
-    {
-      let corner_radius = self.radius;
-
-      canvas
-        .chunks_exact_mut(4)
-        .enumerate()
-        .for_each(|(index, chunk)| {
-          let x = (index as u32) % width;
-          let y = (index as u32) / width;
-
-          let mut color = 0x00000000u32;
-          let alpha = (self.alpha * 255.0) as u32;
-          color |= alpha << 24;
-
-          if (x < corner_radius
-            && y < corner_radius
-            && (corner_radius - x).pow(2) + (corner_radius - y).pow(2) > corner_radius.pow(2))
-            || (x > width - corner_radius
-              && y < corner_radius
-              && (x - (width - corner_radius)).pow(2) + (corner_radius - y).pow(2)
-                > corner_radius.pow(2))
-            || (x < corner_radius
-              && y > height - corner_radius
-              && (corner_radius - x).pow(2) + (y - (height - corner_radius)).pow(2)
-                > corner_radius.pow(2))
-            || (x > width - corner_radius
-              && y > height - corner_radius
-              && (x - (width - corner_radius)).pow(2) + (y - (height - corner_radius)).pow(2)
-                > corner_radius.pow(2))
-          {
-            color = 0xFF000000u32;
-          }
+  /// Sizes the buffer of a view to what its surface was configured with, showing it the first time.
+  fn configure_view(&mut self, qh: &QueueHandle<Self>, index: usize, width: u32, height: u32) {
+    if let Err(err) = self.settings.check_buffer(width, height) {
+      self.warn_too_large(&self.views[index].output, err);
+      self.views.remove(index);
+      self.refresh_alpha_factors();
+      return;
+    }
+
+    // the viewport stretches a single pixel over the whole surface
+    let (buffer_width, buffer_height) = match self.single_pixel {
+      Some(_) => (1, 1),
+      None => (width, height),
+    };
+    let view = &self.views[index];
+    let size = view
+      .buffer
+      .as_ref()
+      .map(|buffer| (buffer.width, buffer.height));
+    if width > 0 && height > 0 && size != Some((buffer_width, buffer_height)) {
+      self.update_opaque_region(index, width, height);
+      let view = &self.views[index];
+      let sized = match size {
+        None => self
+          .create_buffer(qh, buffer_width, buffer_height, view)
+          .map(|buffer| self.views[index].buffer = Some(buffer)),
+        Some(_) => self.views[index].resize(qh, buffer_width, buffer_height, &self.settings),
+      };
+      if let Err(err) = sized {
+        eprintln!("{err}, dropping the overlay");
+        self.views.remove(index);
+        self.refresh_alpha_factors();
+        return;
+      }
+      self.warn_radius(index);
+    }
+
+    let view = &mut self.views[index];
+    view.configured = true;
+    (view.width, view.height) = (width, height);
+
+    if let Some(viewport) = &view.viewport {
+      viewport.set_destination(view.width as _, view.height as _);
+    }
+
+    let capturing = matches!(view.capture, Some(Capture::Pending(_)));
+    if view.first_configure && view.buffer.is_some() && !capturing {
+      view.draw(qh, &self.settings);
+      view.first_configure = false;
+    }
+  }
+
+  /// Whether nothing shines through a view, so the compositor can skip whatever is below.
+  fn is_opaque(&self, view: &DimlandView) -> bool {
+    if self.settings.blackout || self.settings.opaque {
+      return true;
+    }
+    // the target radius counts too, so an animation towards it doesn't start out opaque
+    !self.settings.hidden
+      && self.settings.curtain.is_none()
+      && self.settings.reading_strip().is_none()
+      && self.settings.regions.is_empty()
+      && self.settings.grain == 0.0
+      && view.alpha(&self.settings) >= 1.0
+      && view.current_radius == Radius::default()
+      && self.settings.region_radius(view.region.as_deref()) == Radius::default()
+  }
 
-          let array: &mut [u8; 4] = chunk.try_into().unwrap();
-          *array = color.to_le_bytes();
-        });
+  /// Sets or unsets the opaque region of a view with the given size, which applies with its next commit.
+  fn update_opaque_region(&mut self, index: usize, width: u32, height: u32) {
+    let view = &self.views[index];
+    let wanted = self.is_opaque(view).then_some((width, height));
+    if view.opaque_region == wanted {
+      return;
+    }
+
+    let surface = view.surface.wl_surface();
+    if wanted.is_some() {
+      let region = self.compositor.wl_compositor().create_region(&self.qh, ());
+      region.add(0, 0, width as i32, height as i32);
+      surface.set_opaque_region(Some(&region));
+    } else {
+      surface.set_opaque_region(None);
+    }
+    self.views[index].opaque_region = wanted;
+  }
+
+  /// Makes a view take all input for `dimland blackout`, or lets it through again.
+  ///
+  /// The control socket doesn't go through the compositor, so it keeps working either way.
+  fn set_grab(&self, view: &DimlandView, grab: bool) {
+    let surface = view.surface.wl_surface();
+    if grab {
+      surface.set_input_region(None);
+    } else if self.settings.layer != Layer::Background || view.surface.layer().is_none() {
+      // background layers take input like a wallpaper anyway
+      let region = self.compositor.wl_compositor().create_region(&self.qh, ());
+      surface.set_input_region(Some(&region));
+    }
+    if let Some(layer) = view.surface.layer() {
+      layer.set_keyboard_interactivity(match grab {
+        true => KeyboardInteractivity::Exclusive,
+        false => self.settings.keyboard,
+      });
     }
+    view.surface.commit();
+  }
+
+  /// Renders a buffer of the given size for `view`, which can't be empty.
+  ///
+  /// Views only get their buffer on the first configure with a size,
+  /// until then there is nothing to create it for.
+  fn create_buffer(
+    &self,
+    qh: &QueueHandle<Self>,
+    width: u32,
+    height: u32,
+    view: &DimlandView,
+  ) -> Result<DimlandBuffer, DimlandError> {
+    let single_pixel = self
+      .single_pixel
+      .as_ref()
+      .and_then(|manager| manager.get().ok());
+    let format = match single_pixel {
+      Some(_) => PixelFormat::Argb8888,
+      None => {
+        PixelFormat::negotiate(self.shm.formats(), self.settings.blackout).ok_or_else(|| {
+          DimlandError::NoShmFormat {
+            offered: self.shm.formats().to_vec(),
+          }
+        })?
+      }
+    };
+    let mut pool =
+      RawPool::new(width as usize * height as usize * 4, &self.shm).map_err(|source| {
+        DimlandError::Shm {
+          width,
+          height,
+          source,
+        }
+      })?;
+    let usage = self.settings.stats.map(&pool);
+    let frame = Frame {
+      width,
+      height,
+      format,
+      alpha: view.alpha(&self.settings),
+      radius: view.current_radius,
+      underlay: view.underlay(),
+      span: view.span,
+      color: view.color,
+      badge: None,
+    };
+    render::render(pool.mmap(), frame, &self.settings);
 
+    // a blackout has no use for an alpha channel
     let buffer = pool.create_buffer(
       0,
       width as i32,
       height as i32,
       width as i32 * 4,
-      Format::Argb8888,
+      format.wl_format(self.settings.blackout),
       (),
       qh,
     );
 
-    DimlandView::new(qh, buffer, viewport, layer, output)
+    let mut buffer = DimlandBuffer {
+      pool,
+      usage,
+      buffer,
+      width,
+      height,
+      format,
+      damage: Some(Damage::full(width, height)),
+      single_pixel: single_pixel.map(|manager| (manager.clone(), qh.clone())),
+    };
+    buffer.refresh_single_pixel();
+    Ok(buffer)
+  }
+
+  fn create_bump_surface(
+    &self,
+    layer_shell: &LayerShell,
+    qh: &QueueHandle<Self>,
+    output: &WlOutput,
+  ) -> Result<BumpSurface, DimlandError> {
+    let mut pool = RawPool::new(4, &self.shm).map_err(|source| DimlandError::Shm {
+      width: 1,
+      height: 1,
+      source,
+    })?;
+    let _usage = self.settings.stats.map(&pool);
+    pool.mmap().fill(0);
+    let buffer = pool.create_buffer(0, 1, 1, 4, Format::Argb8888, (), qh);
+
+    let layer = layer_shell.create_layer_surface(
+      qh,
+      self.compositor.create_surface(qh),
+      Layer::Overlay,
+      Some("dimland_bump"),
+      Some(output),
+    );
+
+    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+    let region = self.compositor.wl_compositor().create_region(qh, ());
+    layer.set_input_region(Some(&region));
+    layer.set_size(1, 1);
+    layer.commit();
+
+    Ok(BumpSurface { layer, buffer })
   }
 }
 
 impl DimlandView {
   fn new(
-    _qh: &QueueHandle<DimlandData>,
-    buffer: WlBuffer,
-    viewport: WpViewport,
-    layer: LayerSurface,
+    viewport: Option<WpViewport>,
+    surface: OverlaySurface,
     output: WlOutput,
+    bump: Option<BumpSurface>,
+    radius: Radius,
+    alpha_factor: f32,
   ) -> Self {
     Self {
       first_configure: true,
+      configured: false,
+      created_at: Instant::now(),
       width: 0,
       height: 0,
-      buffer,
+      buffer: None,
       viewport,
-      layer,
+      surface,
       output,
+      bump,
+      current_radius: radius,
+      radius_tweens: None,
+      alpha_factor,
+      tracker: None,
+      painted: None,
+      frame_pending: false,
+      fps: None,
+      frame_interval: None,
+      next_frame: None,
+      warned_radius: false,
+      capture: None,
+      commit_held: false,
+      opaque_region: None,
+      span: None,
+      color: None,
+      badge: None,
+      rect: None,
+      region: None,
+      inhibitor: None,
+    }
+  }
+
+  /// Commits the attached buffer, with presentation feedback for `--telemetry`.
+  fn commit(&self) {
+    if let Some(tracker) = &self.tracker {
+      tracker.track(self.surface.wl_surface());
+    }
+    self.surface.commit();
+  }
+
+  /// Frame callbacks only pace views that change by themselves, the rest commit right away.
+  ///
+  /// A radius animation driven by `--frame-callback-surface` doesn't count, the clock paces it.
+  fn commit_mode(&self, settings: &DimlandSettings) -> CommitMode {
+    let animating = self.radius_tweens.is_some() && !settings.frame_callback_surface;
+    match settings.continuous() || animating {
+      true => CommitMode::FrameDriven,
+      false => CommitMode::Immediate,
     }
   }
 
-  fn draw(&mut self, _qh: &QueueHandle<DimlandData>) {
+  /// Shows the first buffer once the surface is configured, every later change goes through
+  /// [`Self::redraw`].
+  ///
+  /// The frame callback asked for in [`CommitMode::FrameDriven`] starts the loop in `frame`,
+  /// which keeps asking for the next one as long as the view changes by itself.
+  fn draw(&mut self, qh: &QueueHandle<DimlandData>, settings: &DimlandSettings) {
     if !self.first_configure {
       return;
     }
+    let commit_mode = self.commit_mode(settings);
+    let Some(buffer) = &mut self.buffer else {
+      return;
+    };
+
+    let surface = self.surface.wl_surface();
+    surface.attach(Some(&buffer.buffer), 0, 0);
+    buffer.damage_surface(surface, settings.buffer_age);
+    if commit_mode == CommitMode::FrameDriven {
+      self.request_frame(qh);
+    }
+    self.commit_held = true;
+  }
+
+  /// Asks for a frame callback on the surface, sent with the next commit.
+  fn request_frame(&mut self, qh: &QueueHandle<DimlandData>) {
+    if self.frame_pending {
+      return;
+    }
+
+    self.frame_pending = true;
+    let surface = self.surface.wl_surface();
+    surface.frame(qh, surface.clone());
+  }
+
+  /// Whether the cap of `--output-refresh-rate` lets a frame be drawn now, counting it as drawn if so.
+  ///
+  /// Deadlines follow each other by the interval rather than the frames drawn,
+  /// so outputs refreshing at no multiple of the cap still average out at it.
+  fn frame_due(&mut self, now: Instant) -> bool {
+    let Some(interval) = self.frame_interval else {
+      return true;
+    };
+    let next = match self.next_frame {
+      Some(next) if now + FRAME_SLACK < next => return false,
+      Some(next) if next + interval > now => next + interval,
+      // after a pause the deadlines start over instead of catching up
+      _ => now + interval,
+    };
+    self.next_frame = Some(next);
+    true
+  }
+
+  /// Renders the buffer again and, once the surface is mapped, attaches it for the burst
+  /// of commits after the current dispatch, see [`DimlandData::commit_transaction`].
+  ///
+  /// In [`CommitMode::FrameDriven`] the frame callback calls this after asking for the next one,
+  /// so that request goes along with the commit.
+  fn redraw(&mut self, settings: &DimlandSettings) {
+    let alpha = self.alpha(settings);
+    let Some(DimlandBuffer {
+      pool,
+      width,
+      height,
+      format,
+      damage,
+      ..
+    }) = &mut self.buffer
+    else {
+      return;
+    };
+    let underlay = match &self.capture {
+      Some(Capture::Done(underlay)) => Some(underlay),
+      _ => None,
+    };
+    let badge = self
+      .badge
+      .and_then(|start| clipboard::opacity(start, Instant::now()));
+    let frame = Frame {
+      width: *width,
+      height: *height,
+      format: *format,
+      alpha,
+      radius: self.current_radius,
+      underlay,
+      span: self.span,
+      color: self.color,
+      badge,
+    };
+    let mut changed = render::render(pool.mmap(), frame, settings);
+    if let Some(fps) = &self.fps {
+      if let Some(counter) = fps::draw(pool.mmap(), *width, *height, *format, fps.fps()) {
+        changed = Some(changed.map_or(counter, |changed| changed.union(counter)));
+      }
+    }
+    *damage = match (*damage, changed) {
+      (Some(damage), Some(changed)) => Some(damage.union(changed)),
+      (damage, changed) => damage.or(changed),
+    };
+    self.painted = Some((alpha, self.current_radius, settings.hidden));
+    settings.metrics.count_repaint();
+    if changed.is_some() {
+      if let Some(buffer) = &mut self.buffer {
+        buffer.refresh_single_pixel();
+      }
+    }
+
+    if self.first_configure {
+      return;
+    }
+
+    let Some(buffer) = &mut self.buffer else {
+      return;
+    };
+    let surface = self.surface.wl_surface();
+    surface.attach(Some(&buffer.buffer), 0, 0);
+    buffer.damage_surface(surface, settings.buffer_age);
+    self.commit_held = true;
+  }
+
+  /// The alpha drawn on this view, which named regions can have their own of.
+  fn alpha(&self, settings: &DimlandSettings) -> f32 {
+    settings.region_alpha(self.region.as_deref()) * self.alpha_factor * settings.flash_opacity()
+  }
+
+  fn underlay(&self) -> Option<&Underlay> {
+    match &self.capture {
+      Some(Capture::Done(underlay)) => Some(underlay),
+      _ => None,
+    }
+  }
+
+  /// What `radius` shrinks to on this view, once it knows its size.
+  fn effective_radius(&self, radius: Radius) -> Option<Radius> {
+    let buffer = self.buffer.as_ref()?;
+    Some(radius.fit(buffer.width, buffer.height))
+  }
+
+  /// Redraws only if the buffer would look different than the last time it was rendered.
+  fn refresh(&mut self, settings: &DimlandSettings) {
+    let wanted = (self.alpha(settings), self.current_radius, settings.hidden);
+    if self.painted != Some(wanted) {
+      self.redraw(settings);
+    }
+  }
+
+  /// Resizes the buffer to a newly configured size without recreating the layer surface.
+  fn resize(
+    &mut self,
+    qh: &QueueHandle<DimlandData>,
+    width: u32,
+    height: u32,
+    settings: &DimlandSettings,
+  ) -> Result<(), DimlandError> {
+    let Some(buffer) = &mut self.buffer else {
+      return Ok(());
+    };
+    if (width, height) == (buffer.width, buffer.height) {
+      return Ok(());
+    }
+
+    buffer.resize(qh, width, height, settings.blackout)?;
+    self.redraw(settings);
+    Ok(())
+  }
+
+  /// Moves the radius towards `radius`, animated if there is a duration,
+  /// otherwise it is drawn with the next repaint.
+  fn set_radius(
+    &mut self,
+    qh: &QueueHandle<DimlandData>,
+    radius: Radius,
+    settings: &DimlandSettings,
+  ) {
+    match settings.radius_animation {
+      Some((duration, easing)) if !self.first_configure => {
+        let tween = |from: u32, to: u32| Tween::new(from as f32, to as f32, duration, easing);
+        self.radius_tweens = Some([
+          tween(self.current_radius.horizontal, radius.horizontal),
+          tween(self.current_radius.vertical, radius.vertical),
+        ]);
+        if !settings.frame_callback_surface {
+          self.request_frame(qh);
+          self.surface.commit();
+        }
+      }
+      _ => self.current_radius = radius,
+    }
+  }
 
-    self.layer.wl_surface().attach(Some(&self.buffer), 0, 0);
-    self.layer.commit();
+  /// Advances the radius animation by a frame, returning whether it needs another one.
+  ///
+  /// Without `--frame-callback-surface` the next frame is asked for here.
+  fn animate(&mut self, qh: &QueueHandle<DimlandData>, settings: &DimlandSettings) -> bool {
+    let Some([horizontal, vertical]) = self.radius_tweens else {
+      return false;
+    };
+
+    let now = Instant::now();
+    self.current_radius = Radius {
+      horizontal: horizontal.value(now).round() as u32,
+      vertical: vertical.value(now).round() as u32,
+    };
+    let finished = horizontal.finished(now);
+    if finished {
+      self.radius_tweens = None;
+    } else if !settings.frame_callback_surface {
+      self.request_frame(qh);
+    }
+    self.redraw(settings);
+    !finished
   }
 }
 
@@ -266,20 +3311,27 @@ impl LayerShellHandler for DimlandData {
     configure: smithay_client_toolkit::shell::wlr_layer::LayerSurfaceConfigure,
     _serial: u32,
   ) {
-    let Some(view) = self.views.iter_mut().find(|view| &view.layer == layer) else {
+    if let Some(bump) = self
+      .views
+      .iter()
+      .filter_map(|view| view.bump.as_ref())
+      .find(|bump| &bump.layer == layer)
+    {
+      bump.layer.wl_surface().attach(Some(&bump.buffer), 0, 0);
+      bump.layer.commit();
       return;
-    };
-
-    (view.width, view.height) = configure.new_size;
+    }
 
-    view
-      .viewport
-      .set_destination(view.width as _, view.height as _);
+    let Some(index) = self
+      .views
+      .iter()
+      .position(|view| view.surface.layer() == Some(layer))
+    else {
+      return;
+    };
 
-    if view.first_configure {
-      view.draw(qh);
-      view.first_configure = false;
-    }
+    let (width, height) = configure.new_size;
+    self.configure_view(qh, index, width, height);
   }
 }
 
@@ -291,23 +3343,60 @@ impl OutputHandler for DimlandData {
   fn new_output(
     &mut self,
     _conn: &smithay_client_toolkit::reexports::client::Connection,
-    qh: &QueueHandle<Self>,
+    _qh: &QueueHandle<Self>,
     output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
   ) {
-    self.views.push(self.create_view(qh, output));
+    self.emit_output_event("output-added", &output);
+    if let Some(info) = self.output_info(&output) {
+      self
+        .geometries
+        .insert(output.clone(), OutputGeometry::from(&info));
+    }
+    if !self.settings.dormant() {
+      self.add_view(output);
+    }
   }
 
   fn update_output(
     &mut self,
     _conn: &smithay_client_toolkit::reexports::client::Connection,
-    qh: &QueueHandle<Self>,
+    _qh: &QueueHandle<Self>,
     output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
   ) {
-    let new_view = self.create_view(qh, output);
+    let Some(info) = self.output_info(&output) else {
+      return;
+    };
+    let geometry = OutputGeometry::from(&info);
+    if self.geometries.insert(output.clone(), geometry.clone()) == Some(geometry) {
+      return;
+    }
+    // the others move within the box around all outputs as well
+    self.refresh_spans();
+    // the name or description may have come late, --only decides on them again
+    if !self.wants_output(&output) {
+      self.views.retain(|view| view.output != output);
+      return;
+    }
+    if !self.settings.dormant() && !self.views.iter().any(|view| view.output == output) {
+      self.add_view(output);
+      return;
+    }
 
-    if let Some(view) = self.views.iter_mut().find(|v| v.output == new_view.output) {
-      *view = new_view;
+    // only sizes relative to the output change here, the compositor configures the new size
+    // rectangles from --geometry stay where they were put
+    let (anchor, width, height) = self.settings.layer_geometry(info.logical_size);
+    for layer in self
+      .views
+      .iter()
+      .filter(|view| view.output == output && view.rect.is_none())
+      .filter_map(|view| view.surface.layer())
+    {
+      layer.set_anchor(anchor);
+      layer.set_size(width, height);
+      layer.commit();
     }
+    self.refresh_alpha_factors();
+    self.refresh_colors();
   }
 
   fn output_destroyed(
@@ -316,7 +3405,24 @@ impl OutputHandler for DimlandData {
     _qh: &QueueHandle<Self>,
     output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
   ) {
+    self.emit_output_event("output-removed", &output);
+    self.geometries.remove(&output);
     self.views.retain(|v| v.output != output);
+    self.refresh_alpha_factors();
+    self.refresh_spans();
+
+    if self.settings.exit_on_no_outputs && self.views.is_empty() {
+      self
+        .loop_handle
+        .insert_source(Timer::from_duration(NO_OUTPUTS_GRACE), |_, _, data| {
+          if data.views.is_empty() {
+            eprintln!("no outputs left, exiting");
+            data.exit = true;
+          }
+          TimeoutAction::Drop
+        })
+        .expect("can't start the exit timer");
+    }
   }
 }
 
@@ -342,10 +3448,57 @@ impl CompositorHandler for DimlandData {
   fn frame(
     &mut self,
     _conn: &smithay_client_toolkit::reexports::client::Connection,
-    _qh: &QueueHandle<Self>,
-    _surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
+    qh: &QueueHandle<Self>,
+    surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
     _time: u32,
   ) {
+    self.settings.metrics.count_frame_callback();
+    if let Some(clock) = self
+      .clock
+      .as_mut()
+      .filter(|clock| &clock.surface == surface)
+    {
+      clock.frame_pending = false;
+      let now = Instant::now();
+      let mut animating = false;
+      for view in &mut self.views {
+        animating |= match view.frame_due(now) {
+          true => view.animate(qh, &self.settings),
+          false => view.radius_tweens.is_some(),
+        };
+      }
+      if animating {
+        clock.request_frame(qh);
+      }
+      return;
+    }
+
+    if let Some(view) = self
+      .views
+      .iter_mut()
+      .find(|view| view.surface.wl_surface() == surface)
+    {
+      view.frame_pending = false;
+      let now = Instant::now();
+      if !view.frame_due(now) {
+        // too soon for this output, wait for the next frame without drawing
+        view.request_frame(qh);
+        view.surface.commit();
+        return;
+      }
+      let animating = view.radius_tweens.is_some();
+      if let Some(fps) = &mut view.fps {
+        fps.tick(now);
+      }
+      if self.settings.continuous() {
+        // asked for before animating, whose redraw commits it
+        view.request_frame(qh);
+      }
+      view.animate(qh, &self.settings);
+      if self.settings.continuous() && !animating {
+        view.redraw(&self.settings);
+      }
+    }
   }
 }
 
@@ -354,55 +3507,84 @@ delegate_output!(DimlandData);
 delegate_registry!(DimlandData);
 delegate_compositor!(DimlandData);
 delegate_simple!(DimlandData, WpViewporter, 1);
+delegate_simple!(DimlandData, WpSinglePixelBufferManagerV1, 1);
 delegate_shm!(DimlandData);
+delegate_seat!(DimlandData);
+delegate_keyboard!(DimlandData);
 
 impl ProvidesRegistryState for DimlandData {
   fn registry(&mut self) -> &mut RegistryState {
     &mut self.registry_state
   }
 
-  registry_handlers![OutputState];
+  registry_handlers![OutputState, SeatState];
 }
 
 impl Dispatch<WpViewport, ()> for DimlandData {
   fn event(
     _: &mut Self,
-    _: &WpViewport,
-    _: wp_viewport::Event,
+    viewport: &WpViewport,
+    event: wp_viewport::Event,
     _: &(),
     _: &Connection,
     _: &QueueHandle<Self>,
   ) {
+    events::log(viewport, &event);
   }
 }
 
 impl Dispatch<WlBuffer, ()> for DimlandData {
   fn event(
     _: &mut Self,
-    _: &WlBuffer,
-    _: wl_buffer::Event,
+    buffer: &WlBuffer,
+    event: wl_buffer::Event,
     _: &(),
     _: &Connection,
     _: &QueueHandle<Self>,
   ) {
+    events::log(buffer, &event);
   }
 }
 
 impl Dispatch<WlRegion, ()> for DimlandData {
   fn event(
     _: &mut Self,
-    _: &WlRegion,
-    _: <WlRegion as smithay_client_toolkit::reexports::client::Proxy>::Event,
+    region: &WlRegion,
+    event: <WlRegion as smithay_client_toolkit::reexports::client::Proxy>::Event,
     _: &(),
     _: &Connection,
     _: &QueueHandle<Self>,
   ) {
+    events::log(region, &event);
   }
 }
 
 impl Drop for DimlandView {
   fn drop(&mut self) {
-    self.viewport.destroy();
+    if let Some(viewport) = &self.viewport {
+      viewport.destroy();
+    }
+    if let Some(inhibitor) = &self.inhibitor {
+      inhibitor.destroy();
+    }
+  }
+}
+
+impl Drop for DimlandBuffer {
+  fn drop(&mut self) {
+    self.buffer.destroy();
+  }
+}
+
+impl Drop for BumpSurface {
+  fn drop(&mut self) {
+    self.buffer.destroy();
+  }
+}
+
+impl Drop for ClockSurface {
+  fn drop(&mut self) {
+    self.surface.destroy();
     self.buffer.destroy();
   }
 }
@@ -0,0 +1,107 @@
+//! The man page, generated from the clap definitions like the completions.
+
+use std::fmt::Write as _;
+
+use clap::{Arg, CommandFactory};
+
+use crate::DimlandArgs;
+
+pub fn print_man() {
+  let mut command = DimlandArgs::command();
+  command.build();
+  let name = command.get_name().to_string();
+
+  let mut page = String::new();
+  writeln!(
+    page,
+    ".TH {} 1 \"\" \"{name} {}\"",
+    name.to_uppercase(),
+    env!("CARGO_PKG_VERSION")
+  )
+  .unwrap();
+  writeln!(
+    page,
+    ".SH NAME\n{name} \\- dims outputs with a wayland overlay"
+  )
+  .unwrap();
+  writeln!(
+    page,
+    ".SH SYNOPSIS\n.B {name}\n[\\fIOPTIONS\\fR] [\\fICOMMAND\\fR]"
+  )
+  .unwrap();
+
+  writeln!(page, ".SH OPTIONS").unwrap();
+  for arg in command
+    .get_arguments()
+    .filter(|arg| !arg.is_hide_set() && !arg.is_positional())
+  {
+    writeln!(page, ".TP\n{}", synopsis(arg)).unwrap();
+    if let Some(help) = arg.get_help() {
+      writeln!(page, "{}", escape(&help.to_string())).unwrap();
+    }
+    let values: Vec<_> = arg
+      .get_possible_values()
+      .into_iter()
+      .filter(|value| !value.is_hide_set())
+      .map(|value| value.get_name().to_string())
+      .collect();
+    if arg.get_action().takes_values() && !values.is_empty() {
+      writeln!(page, ".br\none of {}", escape(&values.join(", "))).unwrap();
+    }
+  }
+
+  writeln!(page, ".SH COMMANDS").unwrap();
+  for subcommand in command
+    .get_subcommands()
+    .filter(|subcommand| !subcommand.is_hide_set() && subcommand.get_name() != "help")
+  {
+    writeln!(
+      page,
+      ".TP\n\\fB{name} {}\\fR",
+      escape(subcommand.get_name())
+    )
+    .unwrap();
+    if let Some(about) = subcommand.get_about() {
+      writeln!(page, "{}", escape(&about.to_string())).unwrap();
+    }
+  }
+
+  print!("{page}");
+}
+
+/// The spellings of an argument in bold followed by its value in italics, like `-a, --alpha ALPHA`.
+fn synopsis(arg: &Arg) -> String {
+  let mut spellings = Vec::new();
+  if let Some(short) = arg.get_short() {
+    spellings.push(format!("\\fB\\-{short}\\fR"));
+  }
+  if let Some(long) = arg.get_long() {
+    spellings.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+  }
+  let mut synopsis = spellings.join(", ");
+  if arg.get_action().takes_values() {
+    let value = match arg.get_value_names() {
+      Some(names) => names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>()
+        .join(" "),
+      None => arg.get_id().to_string().to_uppercase(),
+    };
+    write!(synopsis, " \\fI{}\\fR", escape(&value)).unwrap();
+  }
+  synopsis
+}
+
+/// Keeps roff from reading text as requests or escapes, and dashes as hyphens.
+fn escape(text: &str) -> String {
+  let text = text.replace('\\', "\\e").replace('-', "\\-");
+  text
+    .lines()
+    .map(|line| match line.starts_with(['.', '\'']) {
+      true => format!("\\&{line}"),
+      false => line.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
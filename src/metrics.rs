@@ -0,0 +1,67 @@
+//! Counters for `dimland metrics`, answered in the Prometheus text format.
+
+use std::{cell::Cell, fmt::Display, fmt::Write as _, rc::Rc};
+
+use crate::DimlandData;
+
+/// Counts what dimland does, kept across reconnects.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Rc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+  repaints: Cell<u64>,
+  frame_callbacks: Cell<u64>,
+}
+
+impl Metrics {
+  pub fn count_repaint(&self) {
+    self.0.repaints.set(self.0.repaints.get() + 1);
+  }
+
+  pub fn count_frame_callback(&self) {
+    self.0.frame_callbacks.set(self.0.frame_callbacks.get() + 1);
+  }
+}
+
+impl DimlandData {
+  pub fn metrics(&self) -> String {
+    let counters = &self.settings.metrics.0;
+    let mut metrics = String::new();
+    write_metric(
+      &mut metrics,
+      "dimland_alpha",
+      "gauge",
+      "The alpha the outputs are dimmed with",
+      self.settings.alpha,
+    );
+    write_metric(
+      &mut metrics,
+      "dimland_views",
+      "gauge",
+      "The number of outputs being dimmed",
+      self.views.len(),
+    );
+    write_metric(
+      &mut metrics,
+      "dimland_repaints_total",
+      "counter",
+      "How often a buffer was rendered again",
+      counters.repaints.get(),
+    );
+    write_metric(
+      &mut metrics,
+      "dimland_frame_callbacks_total",
+      "counter",
+      "How many frame callbacks animations were timed with",
+      counters.frame_callbacks.get(),
+    );
+    metrics
+  }
+}
+
+fn write_metric(metrics: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+  writeln!(metrics, "# HELP {name} {help}").unwrap();
+  writeln!(metrics, "# TYPE {name} {kind}").unwrap();
+  writeln!(metrics, "{name} {value}").unwrap();
+}
@@ -0,0 +1,231 @@
+//! Named regions, rectangles like `--geometry` with an alpha, color and corner radius of their own.
+//!
+//! They live at `$XDG_CONFIG_HOME/dimland/named-regions.json`, which looks like
+//! `{"clockshade": {"geometry": "DP-1:1700,0,220,60", "alpha": 0.9, "radius": 8}}`.
+//! Each region gets a surface of its own on every output it matches, also on outputs plugged
+//! in later, and where regions overlap the compositor blends them. Whatever a region leaves
+//! out follows the rest of the overlay, `dimland set --region` changes it at runtime.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Deserialize;
+use smithay_client_toolkit::output::OutputInfo;
+
+use crate::{
+  control::AlphaChange,
+  outputs::OutputRect,
+  profiles::{self, RadiusEntry},
+  render::{Color, Radius},
+  DimlandData, DimlandSettings,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedRegion {
+  pub geometry: OutputRect,
+  pub alpha: Option<f32>,
+  pub color: Option<Color>,
+  pub radius: Option<Radius>,
+}
+
+/// A region as written in the file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamedRegionEntry {
+  geometry: String,
+  alpha: Option<f32>,
+  color: Option<String>,
+  radius: Option<RadiusEntry>,
+}
+
+impl TryFrom<NamedRegionEntry> for NamedRegion {
+  type Error = String;
+
+  fn try_from(entry: NamedRegionEntry) -> Result<Self, Self::Error> {
+    if let Some(alpha) = entry.alpha.filter(|alpha| !(0.0..=1.0).contains(alpha)) {
+      return Err(format!("{alpha} is not an alpha from 0 to 1"));
+    }
+    Ok(Self {
+      geometry: entry.geometry.parse()?,
+      alpha: entry.alpha,
+      color: entry.color.map(|color| color.parse()).transpose()?,
+      radius: entry.radius.map(Radius::try_from).transpose()?,
+    })
+  }
+}
+
+/// Where the named regions are read from.
+pub fn path() -> Option<PathBuf> {
+  Some(
+    profiles::config_dir()?
+      .join("dimland")
+      .join("named-regions.json"),
+  )
+}
+
+fn parse(text: &str) -> Result<BTreeMap<String, NamedRegion>, String> {
+  let entries: BTreeMap<String, NamedRegionEntry> =
+    serde_json::from_str(text).map_err(|err| err.to_string())?;
+  entries
+    .into_iter()
+    .map(|(name, entry)| {
+      let region = NamedRegion::try_from(entry).map_err(|err| format!("region {name}: {err}"))?;
+      Ok((name, region))
+    })
+    .collect()
+}
+
+/// Reads the named regions, the file doesn't have to exist.
+pub fn load() -> Result<BTreeMap<String, NamedRegion>, String> {
+  let Some(path) = path() else {
+    return Ok(BTreeMap::new());
+  };
+  let text = match std::fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+    Err(err) => return Err(format!("can't read {} ({err})", path.display())),
+  };
+  parse(&text).map_err(|err| format!("{} is not a valid regions file ({err})", path.display()))
+}
+
+impl DimlandSettings {
+  /// The named region a view shows, none for whole outputs and `--geometry`.
+  pub fn named_region(&self, name: Option<&str>) -> Option<&NamedRegion> {
+    self.named_regions.get(name?)
+  }
+
+  /// The alpha of a region, before `--mirror-alpha`, a blackout covers its own one.
+  pub fn region_alpha(&self, name: Option<&str>) -> f32 {
+    match self.named_region(name).and_then(|region| region.alpha) {
+      Some(alpha) if self.blacked_out.is_none() => alpha,
+      _ => self.alpha,
+    }
+  }
+
+  pub fn region_radius(&self, name: Option<&str>) -> Radius {
+    self
+      .named_region(name)
+      .and_then(|region| region.radius)
+      .unwrap_or(self.radius)
+  }
+
+  /// The color of a region, or the one of the output it is on without its own.
+  pub fn region_color(&self, name: Option<&str>, info: Option<&OutputInfo>) -> Option<[u8; 3]> {
+    match self.named_region(name).and_then(|region| region.color) {
+      Some(color) => Some(color.0),
+      None => self.output_color(info),
+    }
+  }
+}
+
+impl DimlandData {
+  /// Changes a named region on every output it shows on, the alpha within
+  /// `--min-alpha` and `--max-alpha` unless forced.
+  pub fn change_region(
+    &mut self,
+    name: &str,
+    alpha: Option<AlphaChange>,
+    force: bool,
+    radius: Option<Radius>,
+    color: Option<Option<Color>>,
+  ) -> Result<(), String> {
+    let current = self.settings.region_alpha(Some(name));
+    let alpha = alpha.map(|change| {
+      let alpha = change.apply(current);
+      let alpha = match force {
+        true => alpha,
+        false => alpha.clamp(self.settings.min_alpha, self.settings.max_alpha),
+      };
+      self.settings.alpha_clamp.apply(alpha)
+    });
+    let Some(region) = self.settings.named_regions.get_mut(name) else {
+      let names: Vec<&str> = self
+        .settings
+        .named_regions
+        .keys()
+        .map(String::as_str)
+        .collect();
+      return Err(match names.is_empty() {
+        true => format!("no region {name}, there are no named regions"),
+        false => format!("no region {name}, there are {}", names.join(", ")),
+      });
+    };
+
+    let mut changes = Vec::new();
+    if let Some(alpha) = alpha.filter(|&alpha| region.alpha != Some(alpha)) {
+      region.alpha = Some(alpha);
+      changes.push(format!("alpha {alpha:.2}"));
+    }
+    let radius = radius.filter(|&radius| region.radius != Some(radius));
+    if let Some(radius) = radius {
+      region.radius = Some(radius);
+      changes.push(format!("radius {radius}"));
+    }
+    let color = color.filter(|&color| region.color != color);
+    if let Some(color) = color {
+      region.color = color;
+      changes.push(match color {
+        Some(color) => format!("color {color}"),
+        None => "color none".to_string(),
+      });
+    }
+    if changes.is_empty() {
+      return Ok(());
+    }
+
+    self
+      .settings
+      .watchers
+      .emit(&format!("region {name} {}", changes.join(" ")));
+    if let Some(radius) = radius {
+      for view in &mut self.views {
+        if view.region.as_deref() == Some(name) {
+          view.set_radius(&self.qh, radius, &self.settings);
+        }
+      }
+      self.settle_radius();
+    }
+    if color.is_some() {
+      self.refresh_colors();
+    }
+    self.schedule_repaint();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::outputs::{OutputMatcher, Rect};
+
+  #[test]
+  fn parse_named_regions() {
+    let regions = parse(
+      r##"{"clockshade": {"geometry": "DP-1:1700,0,220,60", "alpha": 0.9, "color": "#000000", "radius": 8}}"##,
+    )
+    .unwrap();
+    assert_eq!(
+      regions["clockshade"],
+      NamedRegion {
+        geometry: OutputRect {
+          matcher: OutputMatcher::Name("DP-1".to_string()),
+          rect: Rect {
+            x: 1700,
+            y: 0,
+            width: 220,
+            height: 60,
+          },
+        },
+        alpha: Some(0.9),
+        color: Some(Color([0, 0, 0])),
+        radius: Some(Radius::circle(8)),
+      }
+    );
+  }
+
+  #[test]
+  fn reject_broken_regions() {
+    assert!(parse(r#"{"a": {"geometry": "DP-1:0,0,10,10", "alpha": 1.5}}"#).is_err());
+    assert!(parse(r#"{"a": {"geometry": "0,0,10,10"}}"#).is_err());
+    assert!(parse(r#"{"a": {"geometry": "DP-1:0,0,10,10", "opacity": 0.5}}"#).is_err());
+  }
+}
@@ -0,0 +1,592 @@
+//! Querying outputs without creating any surfaces.
+
+use serde::Serialize;
+use smithay_client_toolkit::{
+  delegate_output, delegate_registry,
+  output::{OutputHandler, OutputInfo, OutputState},
+  reexports::client::{
+    globals::registry_queue_init,
+    protocol::wl_output::{Transform, WlOutput},
+    Connection, QueueHandle,
+  },
+  registry::{ProvidesRegistryState, RegistryState},
+  registry_handlers,
+};
+
+use crate::render::{Color, Span};
+
+/// Connects, waits until every output has sent its info and returns it.
+pub fn query_outputs(conn: &Connection) -> Vec<OutputInfo> {
+  let (globals, mut event_queue) = registry_queue_init(conn).expect("queueless");
+  let qh = event_queue.handle();
+
+  let mut lister = OutputLister {
+    registry_state: RegistryState::new(&globals),
+    output_state: OutputState::new(&globals, &qh),
+  };
+
+  // the first roundtrip binds the outputs, the second one collects their info
+  event_queue.roundtrip(&mut lister).expect("sus");
+  event_queue.roundtrip(&mut lister).expect("sus");
+
+  lister
+    .output_state
+    .outputs()
+    .filter_map(|output| lister.output_state.info(&output))
+    .collect()
+}
+
+/// What `--output-json` prints for every output.
+#[derive(Debug, Serialize)]
+pub struct OutputReport {
+  pub name: Option<String>,
+  pub description: Option<String>,
+  pub make: String,
+  pub model: String,
+  pub x: i32,
+  pub y: i32,
+  pub width: Option<i32>,
+  pub height: Option<i32>,
+  pub logical_width: Option<i32>,
+  pub logical_height: Option<i32>,
+  pub scale: i32,
+  pub physical_width_mm: i32,
+  pub physical_height_mm: i32,
+  pub dpi_x: Option<f64>,
+  pub dpi_y: Option<f64>,
+}
+
+impl From<&OutputInfo> for OutputReport {
+  fn from(info: &OutputInfo) -> Self {
+    let mode = info.modes.iter().find(|mode| mode.current);
+    let (physical_width_mm, physical_height_mm) = info.physical_size;
+
+    Self {
+      name: info.name.clone(),
+      description: info.description.clone(),
+      make: info.make.clone(),
+      model: info.model.clone(),
+      x: info.location.0,
+      y: info.location.1,
+      width: mode.map(|mode| mode.dimensions.0),
+      height: mode.map(|mode| mode.dimensions.1),
+      logical_width: info.logical_size.map(|(width, _)| width),
+      logical_height: info.logical_size.map(|(_, height)| height),
+      scale: info.scale_factor,
+      physical_width_mm,
+      physical_height_mm,
+      dpi_x: mode.and_then(|mode| dpi(mode.dimensions.0, physical_width_mm)),
+      dpi_y: mode.and_then(|mode| dpi(mode.dimensions.1, physical_height_mm)),
+    }
+  }
+}
+
+/// What the overlay on an output depends on, to skip updates that change none of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputGeometry {
+  /// Name, description, make and model, which `--only`, `--output-color` and the like match on.
+  identity: OutputIdentity,
+  /// Only matters to `--span`.
+  location: (i32, i32),
+  logical_size: Option<(i32, i32)>,
+  /// Dimensions and refresh rate of the current mode, which `--mirror-alpha` compares.
+  mode: Option<((i32, i32), i32)>,
+  scale_factor: i32,
+  transform: Transform,
+}
+
+impl From<&OutputInfo> for OutputGeometry {
+  fn from(info: &OutputInfo) -> Self {
+    Self {
+      identity: OutputIdentity::of(info),
+      location: info.location,
+      logical_size: info.logical_size,
+      mode: info
+        .modes
+        .iter()
+        .find(|mode| mode.current)
+        .map(|mode| (mode.dimensions, mode.refresh_rate)),
+      scale_factor: info.scale_factor,
+      transform: info.transform,
+    }
+  }
+}
+
+/// Pixels per second of the current mode, in millihertz like the refresh rate.
+pub fn pixel_clock(info: &OutputInfo) -> Option<f64> {
+  let mode = info.modes.iter().find(|mode| mode.current)?;
+  let clock = mode.dimensions.0 as f64 * mode.dimensions.1 as f64 * mode.refresh_rate as f64;
+  (clock > 0.0).then_some(clock)
+}
+
+/// How much of the base alpha an output gets with `--mirror-alpha`,
+/// outputs pushing more pixels are assumed to be brighter and dimmed less.
+pub fn mirror_alpha_factor<'a>(
+  info: &OutputInfo,
+  all: impl IntoIterator<Item = &'a OutputInfo>,
+) -> f32 {
+  let min_clock = all.into_iter().filter_map(pixel_clock).reduce(f64::min);
+  match (pixel_clock(info), min_clock) {
+    (Some(clock), Some(min_clock)) => (min_clock / clock).sqrt() as f32,
+    _ => 1.0,
+  }
+}
+
+/// Where an output sits in the box around all outputs for `--span`, none without a logical size.
+pub fn span<'a>(info: &OutputInfo, all: impl IntoIterator<Item = &'a OutputInfo>) -> Option<Span> {
+  let rect = |info: &OutputInfo| {
+    let (width, height) = info.logical_size?;
+    Some((info.location.0, info.location.1, width, height))
+  };
+  Some(span_of(rect(info)?, all.into_iter().filter_map(rect)))
+}
+
+/// [`span`] on rectangles given as x, y, width and height.
+fn span_of(own: (i32, i32, i32, i32), all: impl Iterator<Item = (i32, i32, i32, i32)>) -> Span {
+  let (left, top, right, bottom) = all.chain([own]).fold(
+    (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+    |(left, top, right, bottom), (x, y, width, height)| {
+      (
+        left.min(x),
+        top.min(y),
+        right.max(x + width),
+        bottom.max(y + height),
+      )
+    },
+  );
+  Span {
+    x: (own.0 - left) as u32,
+    y: (own.1 - top) as u32,
+    width: (right - left) as u32,
+    height: (bottom - top) as u32,
+  }
+}
+
+/// Picks outputs for `--only`, by connector name unless prefixed.
+///
+/// `model:`, `make:` and `name:` compare the whole value, `description:` looks for
+/// the text anywhere in the description. All of them ignore case.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputMatcher {
+  Name(String),
+  Make(String),
+  Model(String),
+  Description(String),
+}
+
+impl OutputMatcher {
+  pub fn matches(&self, info: &OutputInfo) -> bool {
+    match self {
+      OutputMatcher::Name(name) => info
+        .name
+        .as_ref()
+        .is_some_and(|actual| actual.eq_ignore_ascii_case(name)),
+      OutputMatcher::Make(make) => info.make.eq_ignore_ascii_case(make),
+      OutputMatcher::Model(model) => info.model.eq_ignore_ascii_case(model),
+      OutputMatcher::Description(description) => info
+        .description
+        .as_ref()
+        .is_some_and(|actual| actual.to_lowercase().contains(&description.to_lowercase())),
+    }
+  }
+}
+
+impl std::str::FromStr for OutputMatcher {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (kind, value) = s.split_once(':').unwrap_or(("name", s));
+    let value = value.to_string();
+    match kind {
+      "name" => Ok(OutputMatcher::Name(value)),
+      "make" => Ok(OutputMatcher::Make(value)),
+      "model" => Ok(OutputMatcher::Model(value)),
+      "description" => Ok(OutputMatcher::Description(value)),
+      _ => Err(format!(
+        "unknown prefix {kind}:, use name:, make:, model: or description:"
+      )),
+    }
+  }
+}
+
+impl std::fmt::Display for OutputMatcher {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      OutputMatcher::Name(name) => write!(f, "{name}"),
+      OutputMatcher::Make(make) => write!(f, "make:{make}"),
+      OutputMatcher::Model(model) => write!(f, "model:{model}"),
+      OutputMatcher::Description(description) => write!(f, "description:{description}"),
+    }
+  }
+}
+
+/// `--output-color`, a color for the outputs a matcher picks, or `none` for the usual one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputColor {
+  pub matcher: OutputMatcher,
+  pub color: Option<Color>,
+}
+
+impl std::fmt::Display for OutputColor {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.color {
+      Some(color) => write!(f, "{}={color}", self.matcher),
+      None => write!(f, "{}=none", self.matcher),
+    }
+  }
+}
+
+impl std::str::FromStr for OutputColor {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (matcher, color) = s
+      .rsplit_once('=')
+      .ok_or_else(|| format!("{s} is not an output and a color like DP-1=3500K"))?;
+    Ok(Self {
+      matcher: matcher.parse()?,
+      color: match color {
+        "none" => None,
+        color => Some(color.parse()?),
+      },
+    })
+  }
+}
+
+/// Which monitor an output is, to know it again when it comes back on another connector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputIdentity {
+  pub name: Option<String>,
+  pub make: String,
+  pub model: String,
+  /// The description without the connector name at its end, compositors put the serial number in it.
+  pub monitor: Option<String>,
+}
+
+impl OutputIdentity {
+  pub fn of(info: &OutputInfo) -> Self {
+    let monitor = info.description.as_ref().map(|description| {
+      let connector = info.name.as_ref().map(|name| format!(" ({name})"));
+      connector
+        .and_then(|connector| description.strip_suffix(&connector))
+        .unwrap_or(description)
+        .to_string()
+    });
+    Self {
+      name: info.name.clone(),
+      make: info.make.clone(),
+      model: info.model.clone(),
+      monitor,
+    }
+  }
+
+  /// Whether both are the same monitor, by its description where both have one,
+  /// otherwise only on the same connector.
+  pub fn same_monitor(&self, other: &Self) -> bool {
+    if self.make != other.make || self.model != other.model {
+      return false;
+    }
+    match (&self.monitor, &other.monitor) {
+      (Some(monitor), Some(other)) => monitor == other,
+      _ => self.name == other.name,
+    }
+  }
+}
+
+/// A color given to one output at runtime, which it gets back when it is plugged in again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RememberedColor {
+  pub identity: OutputIdentity,
+  pub color: Option<Color>,
+}
+
+/// `--output-transform`, the transform of the outputs a matcher picks, for the ones that
+/// report the wrong one like some RDP and VNC backed outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputTransform {
+  pub matcher: OutputMatcher,
+  pub transform: Transform,
+}
+
+impl OutputTransform {
+  /// Takes over `info` with this transform, turning its logical size along
+  /// if one of the transforms is a quarter turn and the other isn't.
+  pub fn apply(&self, info: &mut OutputInfo) {
+    if quarter_turn(info.transform) != quarter_turn(self.transform) {
+      info.logical_size = info.logical_size.map(|(width, height)| (height, width));
+    }
+    info.transform = self.transform;
+  }
+}
+
+fn quarter_turn(transform: Transform) -> bool {
+  matches!(
+    transform,
+    Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270
+  )
+}
+
+impl std::str::FromStr for OutputTransform {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (matcher, transform) = s
+      .rsplit_once('=')
+      .ok_or_else(|| format!("{s} is not an output and a transform like DP-1=90"))?;
+    let transform = match transform {
+      "normal" | "0" => Transform::Normal,
+      "90" => Transform::_90,
+      "180" => Transform::_180,
+      "270" => Transform::_270,
+      "flipped" => Transform::Flipped,
+      "flipped-90" => Transform::Flipped90,
+      "flipped-180" => Transform::Flipped180,
+      "flipped-270" => Transform::Flipped270,
+      _ => {
+        return Err(format!(
+          "{transform} is not a transform, use 0, 90, 180, 270 or flipped, flipped-90, flipped-180, flipped-270"
+        ))
+      }
+    };
+    Ok(Self {
+      matcher: matcher.parse()?,
+      transform,
+    })
+  }
+}
+
+/// `--output-refresh-rate`, the most frames a second animations draw on an output by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputRefreshRate {
+  pub name: String,
+  pub hz: u32,
+}
+
+impl std::str::FromStr for OutputRefreshRate {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, hz) = s
+      .rsplit_once('=')
+      .ok_or_else(|| format!("{s} is not an output and a refresh rate like HDMI-A-1=60"))?;
+    match hz.parse() {
+      Ok(hz) if hz > 0 => Ok(Self {
+        name: name.to_string(),
+        hz,
+      }),
+      _ => Err(format!("{hz} is not a refresh rate in Hz like 60")),
+    }
+  }
+}
+
+/// A rectangle of an output in logical pixels from its top left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Rect {
+  /// Moves the span of the whole output to where the rectangle sits in it.
+  pub fn within(self, span: Span) -> Span {
+    Span {
+      x: span.x + self.x,
+      y: span.y + self.y,
+      ..span
+    }
+  }
+}
+
+/// `--geometry`, a rectangle to dim on the outputs a matcher picks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputRect {
+  pub matcher: OutputMatcher,
+  pub rect: Rect,
+}
+
+impl std::str::FromStr for OutputRect {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("{s} is not an output and a rectangle like DP-1:100,100,800,600");
+    // matchers like model:U2720Q have colons of their own
+    let (matcher, rect) = s.rsplit_once(':').ok_or_else(invalid)?;
+    let numbers: Vec<u32> = rect
+      .split(',')
+      .map(|number| number.trim().parse())
+      .collect::<Result<_, _>>()
+      .map_err(|_| invalid())?;
+    let [x, y, width, height] = numbers[..] else {
+      return Err(invalid());
+    };
+    if width == 0 || height == 0 {
+      return Err(format!("{s} is empty"));
+    }
+    Ok(Self {
+      matcher: matcher.parse()?,
+      rect: Rect {
+        x,
+        y,
+        width,
+        height,
+      },
+    })
+  }
+}
+
+/// Virtual outputs report a physical size of 0, they have no meaningful dpi.
+fn dpi(pixels: i32, millimeters: i32) -> Option<f64> {
+  (millimeters > 0).then(|| pixels as f64 / (millimeters as f64 / 25.4))
+}
+
+struct OutputLister {
+  registry_state: RegistryState,
+  output_state: OutputState,
+}
+
+impl OutputHandler for OutputLister {
+  fn output_state(&mut self) -> &mut OutputState {
+    &mut self.output_state
+  }
+
+  fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+
+  fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+
+  fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+}
+
+delegate_output!(OutputLister);
+delegate_registry!(OutputLister);
+
+impl ProvidesRegistryState for OutputLister {
+  fn registry(&mut self) -> &mut RegistryState {
+    &mut self.registry_state
+  }
+
+  registry_handlers![OutputState];
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_output_colors() {
+    let color: OutputColor = "model:U2720Q=#ffeedd".parse().unwrap();
+    assert_eq!(color.matcher, OutputMatcher::Model("U2720Q".to_string()));
+    assert_eq!(color.color, Some(Color([0xFF, 0xEE, 0xDD])));
+    assert_eq!("DP-1=none".parse::<OutputColor>().unwrap().color, None);
+    assert_eq!(color.to_string(), "model:U2720Q=#ffeedd");
+    assert!("DP-1".parse::<OutputColor>().is_err());
+    assert!("DP-1=warm".parse::<OutputColor>().is_err());
+  }
+
+  fn identity(name: &str, description: Option<&str>) -> OutputIdentity {
+    OutputIdentity {
+      name: Some(name.to_string()),
+      make: "Sony".to_string(),
+      model: "KD-55X80J".to_string(),
+      monitor: description.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn know_monitors_on_another_connector() {
+    let tv = identity("HDMI-A-1", Some("Sony KD-55X80J 0x01010101"));
+    assert!(tv.same_monitor(&identity("HDMI-A-2", Some("Sony KD-55X80J 0x01010101"))));
+    assert!(!tv.same_monitor(&identity("HDMI-A-1", Some("Sony KD-55X80J 0x02020202"))));
+    // without a description only the connector tells them apart
+    assert!(identity("HDMI-A-1", None).same_monitor(&identity("HDMI-A-1", None)));
+    assert!(!identity("HDMI-A-1", None).same_monitor(&identity("HDMI-A-2", None)));
+    let other_model = OutputIdentity {
+      model: "KD-65X80J".to_string(),
+      ..tv.clone()
+    };
+    assert!(!tv.same_monitor(&other_model));
+  }
+
+  #[test]
+  fn parse_output_transforms() {
+    let transform: OutputTransform = "RDP-1=90".parse().unwrap();
+    assert_eq!(transform.matcher, OutputMatcher::Name("RDP-1".to_string()));
+    assert_eq!(transform.transform, Transform::_90);
+    assert_eq!(
+      "model:VNC=flipped-270"
+        .parse::<OutputTransform>()
+        .unwrap()
+        .transform,
+      Transform::Flipped270
+    );
+    assert!("RDP-1=45".parse::<OutputTransform>().is_err());
+    assert!("90".parse::<OutputTransform>().is_err());
+  }
+
+  #[test]
+  fn parse_output_refresh_rates() {
+    assert_eq!(
+      "HDMI-A-1=60".parse(),
+      Ok(OutputRefreshRate {
+        name: "HDMI-A-1".to_string(),
+        hz: 60
+      })
+    );
+    assert!("HDMI-A-1=0".parse::<OutputRefreshRate>().is_err());
+    assert!("HDMI-A-1=59.94".parse::<OutputRefreshRate>().is_err());
+    assert!("60".parse::<OutputRefreshRate>().is_err());
+  }
+
+  #[test]
+  fn quarter_turns_swap_the_size() {
+    assert!(quarter_turn(Transform::Flipped90));
+    assert!(!quarter_turn(Transform::_180));
+    assert!(!quarter_turn(Transform::Flipped));
+  }
+
+  #[test]
+  fn parse_output_rects() {
+    let rect: OutputRect = "model:U2720Q:100,50,800,600".parse().unwrap();
+    assert_eq!(rect.matcher, OutputMatcher::Model("U2720Q".to_string()));
+    assert_eq!(
+      rect.rect,
+      Rect {
+        x: 100,
+        y: 50,
+        width: 800,
+        height: 600
+      }
+    );
+    assert!("DP-1:100,100,800".parse::<OutputRect>().is_err());
+    assert!("DP-1:100,100,0,600".parse::<OutputRect>().is_err());
+    assert!("100,100,800,600".parse::<OutputRect>().is_err());
+  }
+
+  #[test]
+  fn spans_the_box_around_all_outputs() {
+    // a laptop below and to the right of a wide monitor
+    let monitor = (0, 0, 2560, 1080);
+    let laptop = (1920, 1080, 1280, 800);
+    let all = || [monitor, laptop].into_iter();
+    assert_eq!(
+      span_of(laptop, all()),
+      Span {
+        x: 1920,
+        y: 1080,
+        width: 3200,
+        height: 1880
+      }
+    );
+    assert_eq!(
+      (span_of(monitor, all()).x, span_of(monitor, all()).y),
+      (0, 0)
+    );
+    // negative positions count from the leftmost output
+    assert_eq!(span_of((-1280, 0, 1280, 800), all()).x, 0);
+    assert_eq!(
+      span_of((0, 0, 2560, 1080), [(-1280, 0, 1280, 800)].into_iter()).x,
+      1280
+    );
+  }
+}
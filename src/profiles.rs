@@ -0,0 +1,214 @@
+//! Named sets of alpha and radius from a profiles file, switchable at runtime.
+//!
+//! The file lives at `$XDG_CONFIG_HOME/dimland/profiles.json` and looks like
+//! `{"work": {"alpha": 0.3}, "movie": {"alpha": 0.8, "radius": "24x12"}}`.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{render::Radius, DimlandData, DimlandSettings};
+
+/// What a profile changes, anything left out stays as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+  pub alpha: Option<f32>,
+  pub radius: Option<Radius>,
+}
+
+/// A profile as written in the file, where radii can be numbers or strings.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileEntry {
+  alpha: Option<f32>,
+  radius: Option<RadiusEntry>,
+}
+
+/// A radius as written in a file, a number for a circle or a string like `24x12`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RadiusEntry {
+  Circle(u32),
+  Text(String),
+}
+
+impl TryFrom<RadiusEntry> for Radius {
+  type Error = String;
+
+  fn try_from(entry: RadiusEntry) -> Result<Self, Self::Error> {
+    match entry {
+      RadiusEntry::Circle(radius) => Ok(Radius::circle(radius)),
+      RadiusEntry::Text(radius) => radius.parse(),
+    }
+  }
+}
+
+impl TryFrom<ProfileEntry> for Profile {
+  type Error = String;
+
+  fn try_from(entry: ProfileEntry) -> Result<Self, Self::Error> {
+    Ok(Self {
+      alpha: entry.alpha,
+      radius: entry.radius.map(Radius::try_from).transpose()?,
+    })
+  }
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` without it.
+pub fn config_dir() -> Option<PathBuf> {
+  std::env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+/// Where the profiles are read from.
+pub fn profiles_path() -> Option<PathBuf> {
+  Some(config_dir()?.join("dimland").join("profiles.json"))
+}
+
+/// Reads the profiles file, which doesn't have to exist.
+pub fn load() -> Result<BTreeMap<String, Profile>, String> {
+  let Some(path) = profiles_path() else {
+    return Ok(BTreeMap::new());
+  };
+  let text = match std::fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+    Err(err) => return Err(format!("can't read {} ({err})", path.display())),
+  };
+  let entries: BTreeMap<String, ProfileEntry> = serde_json::from_str(&text)
+    .map_err(|err| format!("{} is not a valid profiles file ({err})", path.display()))?;
+  entries
+    .into_iter()
+    .map(|(name, entry)| {
+      let profile = Profile::try_from(entry)
+        .map_err(|err| format!("profile {name} in {} ({err})", path.display()))?;
+      Ok((name, profile))
+    })
+    .collect()
+}
+
+/// Looks up a profile, naming the ones there are if it doesn't exist.
+pub fn find(profiles: &BTreeMap<String, Profile>, name: &str) -> Result<Profile, String> {
+  profiles.get(name).copied().ok_or_else(|| {
+    let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+    match names.is_empty() {
+      true => format!("no profile {name}, there are no profiles"),
+      false => format!("no profile {name}, there are {}", names.join(", ")),
+    }
+  })
+}
+
+impl DimlandSettings {
+  /// Starts out with the named profile.
+  pub fn use_profile(&mut self, name: &str) -> Result<(), String> {
+    let profile = find(&self.profiles, name)?;
+    self.profile = Some(name.to_string());
+    if let Some(alpha) = profile.alpha {
+      self.alpha = self
+        .alpha_clamp
+        .apply(alpha.clamp(self.min_alpha, self.max_alpha));
+    }
+    if let Some(radius) = profile.radius {
+      self.radius = radius;
+    }
+    Ok(())
+  }
+}
+
+impl DimlandData {
+  /// Switches to the named profile, animating the radius if `--animate-radius` is given.
+  pub fn set_profile(&mut self, name: &str) -> Result<(), String> {
+    let profile = find(&self.settings.profiles, name)?;
+
+    if self.settings.profile.as_deref() != Some(name) {
+      self.settings.profile = Some(name.to_string());
+      self.settings.watchers.emit(&format!("profile {name}"));
+    }
+    if let Some(alpha) = profile.alpha {
+      self.set_alpha(alpha);
+    }
+    if let Some(radius) = profile.radius {
+      self.set_radius(radius);
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(text: &str) -> Result<BTreeMap<String, Profile>, String> {
+    let entries: BTreeMap<String, ProfileEntry> =
+      serde_json::from_str(text).map_err(|err| err.to_string())?;
+    entries
+      .into_iter()
+      .map(|(name, entry)| Ok((name, Profile::try_from(entry)?)))
+      .collect()
+  }
+
+  #[test]
+  fn parses_profiles() {
+    let profiles =
+      parse(r#"{"work": {"alpha": 0.3}, "movie": {"alpha": 0.8, "radius": "24x12"}}"#).unwrap();
+    assert_eq!(
+      profiles["work"],
+      Profile {
+        alpha: Some(0.3),
+        radius: None
+      }
+    );
+    assert_eq!(
+      profiles["movie"],
+      Profile {
+        alpha: Some(0.8),
+        radius: Some(Radius {
+          horizontal: 24,
+          vertical: 12
+        })
+      }
+    );
+  }
+
+  #[test]
+  fn radius_entries_round_trip() {
+    assert_eq!(
+      Radius::try_from(RadiusEntry::Circle(16)),
+      Ok(Radius::circle(16))
+    );
+    for radius in [
+      Radius::circle(8),
+      "24x12".parse().unwrap(),
+      "0x6".parse().unwrap(),
+    ] {
+      assert_eq!(
+        Radius::try_from(RadiusEntry::Text(radius.to_string())),
+        Ok(radius)
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_profiles() {
+    assert!(Radius::try_from(RadiusEntry::Text("24y12".to_string())).is_err());
+    assert!(Radius::try_from(RadiusEntry::Text("-4".to_string())).is_err());
+    assert!(parse(r#"{"work": {"radius": "wide"}}"#).is_err());
+    assert!(parse(r#"{"work": {"radius": -4}}"#).is_err());
+    assert!(parse(r#"{"work": {"alpha": 0.3, "dim": true}}"#).is_err());
+  }
+
+  #[test]
+  fn names_the_profiles_there_are() {
+    let profiles = parse(r#"{"work": {}, "movie": {}}"#).unwrap();
+    assert!(find(&profiles, "work").is_ok());
+    assert_eq!(
+      find(&profiles, "game"),
+      Err("no profile game, there are movie, work".to_string())
+    );
+    assert_eq!(
+      find(&BTreeMap::new(), "game"),
+      Err("no profile game, there are no profiles".to_string())
+    );
+  }
+}
@@ -0,0 +1,123 @@
+//! `dimland progress`, a thin bar along an edge, like for volume or brightness.
+//!
+//! The bar is drawn over the dim and goes away by itself after a moment.
+//! Only the pixels it changes are repainted, so the damage stays a thin strip.
+
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  RegistrationToken,
+};
+
+use crate::{
+  render::{Color, Edge},
+  DimlandData, DimlandSettings,
+};
+
+/// How thick the bar is in pixels of the overlay.
+pub const THICKNESS: u32 = 4;
+pub const DEFAULT_EDGE: Edge = Edge::Bottom;
+pub const DEFAULT_COLOR: Color = Color([0xFF, 0xFF, 0xFF]);
+/// How long a bar shows after its last update.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bar {
+  /// How much of the edge it fills, from 0 to 1.
+  pub value: f32,
+  pub edge: Edge,
+  pub color: Color,
+}
+
+impl Bar {
+  /// Whether the bar covers a pixel, filling horizontal edges from the left and vertical ones from the bottom.
+  pub fn covers(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+    let filled = |length: u32| (length as f32 * self.value).round() as u32;
+    match self.edge {
+      Edge::Top => y < THICKNESS && x < filled(width),
+      Edge::Bottom => y >= height.saturating_sub(THICKNESS) && x < filled(width),
+      Edge::Left => x < THICKNESS && y >= height - filled(height),
+      Edge::Right => x >= width.saturating_sub(THICKNESS) && y >= height - filled(height),
+    }
+  }
+}
+
+/// A bar showing until its timer runs out.
+#[derive(Debug, Clone)]
+pub struct Progress {
+  pub bar: Bar,
+  timer: RegistrationToken,
+}
+
+impl DimlandSettings {
+  /// The bar to draw right now, none during a blackout.
+  pub fn progress_bar(&self) -> Option<Bar> {
+    if self.blacked_out.is_some() {
+      return None;
+    }
+    self.progress.as_ref().map(|progress| progress.bar)
+  }
+}
+
+impl DimlandData {
+  /// Shows a bar for `timeout`, replacing the one showing and starting its timeout over.
+  pub fn show_progress(&mut self, bar: Bar, timeout: Duration) {
+    if let Some(progress) = self.settings.progress.take() {
+      self.loop_handle.remove(progress.timer);
+    }
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::from_duration(timeout), |_, _, data| {
+        data.settings.progress = None;
+        data.repaint_all();
+        TimeoutAction::Drop
+      })
+      .expect("can't start the progress timer");
+    self.settings.progress = Some(Progress { bar, timer });
+    self
+      .settings
+      .watchers
+      .emit(&format!("progress {:.2} {}", bar.value, bar.edge));
+    // the bar isn't part of what views compare to know whether they are up to date
+    self.repaint_all();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn bar(value: f32, edge: Edge) -> Bar {
+    Bar {
+      value,
+      edge,
+      color: DEFAULT_COLOR,
+    }
+  }
+
+  #[test]
+  fn fills_along_the_edge() {
+    let bottom = bar(0.63, Edge::Bottom);
+    assert!(bottom.covers(0, 99, 100, 100));
+    assert!(bottom.covers(62, 96, 100, 100));
+    assert!(!bottom.covers(63, 99, 100, 100));
+    assert!(!bottom.covers(0, 95, 100, 100));
+
+    // vertical bars fill up from the bottom
+    let right = bar(0.25, Edge::Right);
+    assert!(right.covers(99, 99, 100, 100));
+    assert!(right.covers(96, 75, 100, 100));
+    assert!(!right.covers(99, 74, 100, 100));
+    assert!(!right.covers(95, 99, 100, 100));
+  }
+
+  #[test]
+  fn empty_and_full() {
+    let empty = bar(0.0, Edge::Left);
+    assert!((0..100).all(|y| !empty.covers(0, y, 100, 100)));
+    let full = bar(1.0, Edge::Top);
+    assert!((0..100).all(|x| full.covers(x, 0, 100, 100)));
+  }
+}
@@ -0,0 +1,128 @@
+//! Probing which protocols the compositor offers, for `dimland check-compositor`,
+//! and which shm formats, for `--list-formats`.
+
+use smithay_client_toolkit::{
+  delegate_shm,
+  reexports::client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::wl_registry::{self, WlRegistry},
+    Connection, Dispatch, QueueHandle,
+  },
+  shm::{Shm, ShmHandler},
+};
+
+use crate::{events, render::PixelFormat};
+
+/// The protocols dimland cares about and what they are good for.
+const PROTOCOLS: &[(&str, &str)] = &[
+  (
+    "zwlr_layer_shell_v1",
+    "the overlay itself, without it only --fallback-window works",
+  ),
+  ("xdg_wm_base", "--fallback-window"),
+  (
+    "wp_viewporter",
+    "optional, without it buffers are rendered at the surface size instead of stretched",
+  ),
+  ("wp_fractional_scale_manager_v1", "not used yet"),
+  (
+    "wp_single_pixel_buffer_manager_v1",
+    "--prefer-single-pixel-buffer",
+  ),
+  ("wp_presentation", "--telemetry"),
+  ("xdg_activation_v1", "not used yet"),
+  ("zwlr_data_control_manager_v1", "--clipboard-indicator"),
+  ("zwlr_screencopy_manager_v1", "--capture-before-dim"),
+  ("zwp_idle_inhibit_manager_v1", "--wl-idle-inhibit"),
+];
+
+/// Collects nothing, the global list is all that's needed.
+struct Prober;
+
+/// Prints a table of the protocols dimland uses and whether the compositor has them.
+pub fn check_compositor(conn: &Connection) {
+  let (globals, _) = registry_queue_init::<Prober>(conn).expect("queueless");
+  let offered: Vec<(String, u32)> = globals.contents().with_list(|list| {
+    list
+      .iter()
+      .map(|global| (global.interface.clone(), global.version))
+      .collect()
+  });
+
+  let width = PROTOCOLS
+    .iter()
+    .map(|(protocol, _)| protocol.len())
+    .max()
+    .unwrap_or_default();
+  for (protocol, feature) in PROTOCOLS {
+    match offered.iter().find(|(interface, _)| interface == protocol) {
+      Some((_, version)) => println!("✓ {protocol:width$} v{version:<2} {feature}"),
+      None => println!("✗ {protocol:width$}     {feature}"),
+    }
+  }
+}
+
+/// Holds on to the shm state while the compositor lists its formats.
+struct FormatLister {
+  shm: Shm,
+}
+
+impl ShmHandler for FormatLister {
+  fn shm_state(&mut self) -> &mut Shm {
+    &mut self.shm
+  }
+}
+
+delegate_shm!(FormatLister);
+
+/// Prints the shm formats the compositor advertises one per line,
+/// and on stderr which of them dimland would draw with.
+pub fn list_formats(conn: &Connection) {
+  let (globals, mut queue) = registry_queue_init::<FormatLister>(conn).expect("queueless");
+  let shm = Shm::bind(&globals, &queue.handle()).unwrap_or_else(|err| {
+    eprintln!("the compositor doesn't support wl_shm ({err})");
+    std::process::exit(1);
+  });
+  let mut lister = FormatLister { shm };
+  // the formats follow right after binding
+  queue
+    .roundtrip(&mut lister)
+    .expect("the compositor went away");
+
+  let formats = lister.shm.formats();
+  for format in formats {
+    println!("{format:?}");
+  }
+  for (opaque, overlay) in [(false, "the overlay"), (true, "--blackout")] {
+    match PixelFormat::negotiate(formats, opaque) {
+      Some(format) => eprintln!("{overlay} is drawn with {:?}", format.wl_format(opaque)),
+      None => eprintln!("{overlay} can't be drawn, none of the formats fit"),
+    }
+  }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for FormatLister {
+  fn event(
+    _: &mut Self,
+    registry: &WlRegistry,
+    event: wl_registry::Event,
+    _: &GlobalListContents,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(registry, &event);
+  }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Prober {
+  fn event(
+    _: &mut Self,
+    registry: &WlRegistry,
+    event: wl_registry::Event,
+    _: &GlobalListContents,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(registry, &event);
+  }
+}
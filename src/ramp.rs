@@ -0,0 +1,276 @@
+//! `dimland ramp`, slowly dimming towards an alpha over a work block.
+//!
+//! Ramps take minutes, so a timer steps them once a second instead of every frame.
+//! Progress is measured on the wall clock, which keeps running while the machine
+//! is suspended, so a ramp resumes where it should be by now instead of where it was.
+
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::ValueEnum;
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  RegistrationToken,
+};
+
+use crate::{animation::Easing, DimlandData, DimlandSettings};
+
+const STEP: Duration = Duration::from_secs(1);
+/// How often the overlay blinks at the end of a ramp with `--then flash`.
+const FLASHES: u32 = 3;
+const FLASH_INTERVAL: Duration = Duration::from_millis(300);
+/// How often the overlay is drawn again while it flashes.
+const FLASH_STEP: Duration = Duration::from_millis(16);
+
+/// What happens once a ramp reaches its alpha.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RampEnd {
+  /// Blink a few times, then go back to where the ramp started.
+  #[default]
+  Flash,
+  /// Stay at the alpha the ramp reached.
+  Hold,
+  /// Go back to where the ramp started right away.
+  Revert,
+}
+
+/// Where a ramp is, separate from its timer so it can be reasoned about on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RampTween {
+  pub from: f32,
+  pub to: f32,
+  over: Duration,
+  /// How long it ran until it was last paused.
+  elapsed: Duration,
+  /// Since when it runs, none while paused.
+  resumed: Option<SystemTime>,
+}
+
+impl RampTween {
+  pub fn new(from: f32, to: f32, over: Duration, now: SystemTime) -> Self {
+    Self {
+      from,
+      to,
+      over,
+      elapsed: Duration::ZERO,
+      resumed: Some(now),
+    }
+  }
+
+  /// How far along it is, from 0 to 1.
+  pub fn progress(&self, now: SystemTime) -> f32 {
+    // a clock set backwards counts as no time passing, not as going back
+    let running = self.resumed.map_or(Duration::ZERO, |resumed| {
+      now.duration_since(resumed).unwrap_or_default()
+    });
+    if self.over.is_zero() {
+      return 1.0;
+    }
+    ((self.elapsed + running).as_secs_f32() / self.over.as_secs_f32()).min(1.0)
+  }
+
+  pub fn alpha(&self, now: SystemTime) -> f32 {
+    let progress = self.progress(now);
+    self.from * (1.0 - progress) + self.to * progress
+  }
+
+  pub fn paused(&self) -> bool {
+    self.resumed.is_none()
+  }
+
+  pub fn pause(&mut self, now: SystemTime) {
+    if let Some(resumed) = self.resumed.take() {
+      self.elapsed += now.duration_since(resumed).unwrap_or_default();
+    }
+  }
+
+  pub fn resume(&mut self, now: SystemTime) {
+    self.resumed.get_or_insert(now);
+  }
+}
+
+/// The blinking at the end of a ramp with `--then flash`, fading the overlay out and
+/// back in along `--easing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flash {
+  start: Instant,
+  easing: Easing,
+}
+
+impl Flash {
+  fn elapsed(&self) -> Duration {
+    self.start.elapsed()
+  }
+
+  fn done(&self) -> bool {
+    self.elapsed() >= FLASH_INTERVAL * 2 * FLASHES
+  }
+}
+
+/// How much of the overlay shows `elapsed` into a flash, gone after every interval
+/// and back after the next one.
+fn flash_opacity(elapsed: Duration, easing: Easing) -> f32 {
+  let blinks = elapsed.as_secs_f32() / (FLASH_INTERVAL * 2).as_secs_f32();
+  if blinks >= FLASHES as f32 {
+    return 1.0;
+  }
+  let t = blinks.fract();
+  1.0 - easing.apply(1.0 - (2.0 * t - 1.0).abs())
+}
+
+impl DimlandSettings {
+  /// How much of the alpha shows right now, less than all of it while a ramp flashes.
+  ///
+  /// A blackout doesn't flash.
+  pub fn flash_opacity(&self) -> f32 {
+    match self.flash {
+      Some(flash) if self.blacked_out.is_none() => flash_opacity(flash.elapsed(), flash.easing),
+      _ => 1.0,
+    }
+  }
+}
+
+/// A running ramp with the timer stepping it.
+#[derive(Debug, Clone)]
+pub struct Ramp {
+  pub tween: RampTween,
+  end: RampEnd,
+  timer: RegistrationToken,
+}
+
+impl DimlandData {
+  /// Starts dimming towards `to` over `over`, replacing a ramp that is already running.
+  pub fn start_ramp(&mut self, to: f32, over: Duration, end: RampEnd) {
+    if let Some(ramp) = self.settings.ramp.take() {
+      self.loop_handle.remove(ramp.timer);
+    }
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::immediate(), |_, _, data| data.step_ramp())
+      .expect("can't start the ramp timer");
+    self.settings.ramp = Some(Ramp {
+      tween: RampTween::new(self.settings.alpha, to, over, SystemTime::now()),
+      end,
+      timer,
+    });
+    self.settings.watchers.emit(&format!("ramp start {to:.2}"));
+  }
+
+  pub fn pause_ramp(&mut self) -> Result<(), String> {
+    let ramp = self.settings.ramp.as_mut().ok_or("no ramp running")?;
+    ramp.tween.pause(SystemTime::now());
+    self.settings.watchers.emit("ramp pause");
+    Ok(())
+  }
+
+  pub fn resume_ramp(&mut self) -> Result<(), String> {
+    let ramp = self.settings.ramp.as_mut().ok_or("no ramp running")?;
+    ramp.tween.resume(SystemTime::now());
+    self.settings.watchers.emit("ramp resume");
+    Ok(())
+  }
+
+  /// Stops the ramp and goes back to where it started.
+  pub fn cancel_ramp(&mut self) -> Result<(), String> {
+    let ramp = self.settings.ramp.take().ok_or("no ramp running")?;
+    self.loop_handle.remove(ramp.timer);
+    self.set_alpha(ramp.tween.from);
+    self.settings.watchers.emit("ramp cancel");
+    Ok(())
+  }
+
+  fn step_ramp(&mut self) -> TimeoutAction {
+    let Some(ramp) = &self.settings.ramp else {
+      return TimeoutAction::Drop;
+    };
+    if ramp.tween.paused() {
+      return TimeoutAction::ToDuration(STEP);
+    }
+
+    let now = SystemTime::now();
+    let (alpha, done) = (ramp.tween.alpha(now), ramp.tween.progress(now) >= 1.0);
+    self.set_alpha(alpha);
+    if !done {
+      return TimeoutAction::ToDuration(STEP);
+    }
+
+    let Some(ramp) = self.settings.ramp.take() else {
+      return TimeoutAction::Drop;
+    };
+    self.settings.watchers.emit("ramp end");
+    match ramp.end {
+      RampEnd::Hold => {}
+      RampEnd::Revert => self.set_alpha(ramp.tween.from),
+      RampEnd::Flash => self.flash(ramp.tween.from),
+    }
+    TimeoutAction::Drop
+  }
+
+  /// Blinks the overlay, then sets the alpha back to `alpha`.
+  fn flash(&mut self, alpha: f32) {
+    self.settings.flash = Some(Flash {
+      start: Instant::now(),
+      easing: self.settings.easing,
+    });
+    self
+      .loop_handle
+      .insert_source(Timer::immediate(), move |_, _, data| {
+        let done = data.settings.flash.is_none_or(|flash| flash.done());
+        if done {
+          data.settings.flash = None;
+          data.set_alpha(alpha);
+        }
+        // the flash isn't part of the alpha views compare to know whether they are up to date
+        data.repaint_all();
+        match done {
+          true => TimeoutAction::Drop,
+          false => TimeoutAction::ToDuration(FLASH_STEP),
+        }
+      })
+      .expect("can't start the flash timer");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+  }
+
+  #[test]
+  fn flashes_out_and_back_in() {
+    let at = |millis| flash_opacity(Duration::from_millis(millis), Easing::Linear);
+    assert_eq!(at(0), 1.0);
+    assert_eq!(at(300), 0.0);
+    assert_eq!(at(600), 1.0);
+    assert_eq!(at(150), 0.5);
+    assert!((at(600) - 1.0).abs() < 1e-4);
+    assert!(flash_opacity(Duration::from_millis(150), Easing::EaseIn) > 0.5);
+  }
+
+  #[test]
+  fn follows_the_wall_clock() {
+    let tween = RampTween::new(0.2, 0.8, Duration::from_secs(100), at(1000));
+    assert_eq!(tween.alpha(at(1000)), 0.2);
+    assert_eq!(tween.progress(at(1050)), 0.5);
+    // like after a suspend, it is where it would be by now
+    assert_eq!(tween.alpha(at(5000)), 0.8);
+  }
+
+  #[test]
+  fn pausing_stops_progress() {
+    let mut tween = RampTween::new(0.0, 1.0, Duration::from_secs(100), at(0));
+    tween.pause(at(25));
+    assert_eq!(tween.progress(at(80)), 0.25);
+    tween.resume(at(80));
+    assert_eq!(tween.progress(at(105)), 0.5);
+  }
+
+  #[test]
+  fn a_clock_set_backwards_does_not_go_back() {
+    let tween = RampTween::new(0.0, 1.0, Duration::from_secs(100), at(1000));
+    assert_eq!(tween.progress(at(500)), 0.0);
+  }
+}
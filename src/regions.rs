@@ -0,0 +1,109 @@
+//! `--region-file`, rectangles of the overlay with their own alpha.
+//!
+//! The file is a JSON list like `[{"x": 0, "y": 0, "width": 200, "height": 100, "alpha": 0.0}]`,
+//! in pixels of the overlay. Later regions win where they overlap earlier ones, and regions
+//! without an alpha go back to the normal one. It is read again on SIGHUP.
+
+use std::{
+  io::Read,
+  path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use smithay_client_toolkit::reexports::calloop::{
+  generic::Generic, Interest, LoopHandle, Mode, PostAction,
+};
+
+use crate::{environment, DimlandData};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  pub alpha: Option<f32>,
+}
+
+impl Region {
+  pub fn contains(&self, x: u32, y: u32) -> bool {
+    (self.x..self.x.saturating_add(self.width)).contains(&x)
+      && (self.y..self.y.saturating_add(self.height)).contains(&y)
+  }
+}
+
+/// The index of the region a pixel gets its alpha from, the last one covering it.
+pub fn covering(regions: &[Region], x: u32, y: u32) -> Option<usize> {
+  regions.iter().rposition(|region| region.contains(x, y))
+}
+
+pub fn load(path: &Path) -> Result<Vec<Region>, String> {
+  let text = std::fs::read_to_string(path)
+    .map_err(|err| format!("can't read {} ({err})", path.display()))?;
+  let regions: Vec<Region> = serde_json::from_str(&text)
+    .map_err(|err| format!("{} is not a valid region file ({err})", path.display()))?;
+  if let Some(region) = regions.iter().find(|region| {
+    region
+      .alpha
+      .is_some_and(|alpha| !(0.0..=1.0).contains(&alpha))
+  }) {
+    return Err(format!(
+      "{} has a region with an alpha outside of 0 to 1 at {},{}",
+      path.display(),
+      region.x,
+      region.y
+    ));
+  }
+  Ok(regions)
+}
+
+/// Reads the file again on SIGHUP, keeping the regions as they are if it is broken.
+///
+/// Has to be called before any threads are spawned, like [`environment::watch`].
+pub fn watch(handle: &LoopHandle<DimlandData>, path: PathBuf) {
+  let Some(signals) = environment::signal_fd(&[libc::SIGHUP]) else {
+    eprintln!(
+      "can't watch for SIGHUP ({}), {} is only read once",
+      std::io::Error::last_os_error(),
+      path.display()
+    );
+    return;
+  };
+
+  handle
+    .insert_source(
+      Generic::new(signals, Interest::READ, Mode::Level),
+      move |_, signals, data| {
+        let mut info = [0; std::mem::size_of::<libc::signalfd_siginfo>()];
+        while (&**signals).read(&mut info).is_ok_and(|read| read > 0) {}
+        match load(&path) {
+          Ok(regions) => data.set_regions(regions),
+          Err(err) => eprintln!("{err}, keeping the regions from before"),
+        }
+        Ok(PostAction::Continue)
+      },
+    )
+    .expect("can't watch for SIGHUP");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn later_regions_win() {
+    let regions: Vec<Region> = serde_json::from_str(
+      r#"[
+        {"x": 0, "y": 0, "width": 100, "height": 100, "alpha": 0.0},
+        {"x": 50, "y": 50, "width": 100, "height": 100}
+      ]"#,
+    )
+    .unwrap();
+    assert_eq!(regions[0].alpha, Some(0.0));
+    assert_eq!(covering(&regions, 10, 10), Some(0));
+    assert_eq!(covering(&regions, 75, 75), Some(1));
+    assert_eq!(covering(&regions, 149, 149), Some(1));
+    assert_eq!(covering(&regions, 150, 10), None);
+  }
+}
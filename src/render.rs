@@ -0,0 +1,959 @@
+//! Drawing the overlay into a buffer, independent of any surface.
+
+use clap::ValueEnum;
+use smithay_client_toolkit::reexports::client::protocol::wl_shm::Format;
+
+use crate::{
+  alert, clipboard, grain, image, lab, regions, screencopy::Underlay, DimlandSettings, Extent,
+};
+
+/// The layouts a buffer can be written in, all premultiplied, little-endian and 4 bytes per pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+  #[default]
+  Argb8888,
+  Abgr8888,
+}
+
+impl PixelFormat {
+  /// Prefers Argb8888 and falls back to Abgr8888 if only that one is advertised,
+  /// looking for their X variants instead when the buffer is `opaque`.
+  /// None if the compositor has none of them.
+  pub fn negotiate(formats: &[Format], opaque: bool) -> Option<Self> {
+    [PixelFormat::Argb8888, PixelFormat::Abgr8888]
+      .into_iter()
+      .find(|format| formats.contains(&format.wl_format(opaque)))
+  }
+
+  /// The format to create buffers with, without an alpha channel when the buffer is opaque anyway.
+  pub fn wl_format(self, opaque: bool) -> Format {
+    match (self, opaque) {
+      (PixelFormat::Argb8888, false) => Format::Argb8888,
+      (PixelFormat::Argb8888, true) => Format::Xrgb8888,
+      (PixelFormat::Abgr8888, false) => Format::Abgr8888,
+      (PixelFormat::Abgr8888, true) => Format::Xbgr8888,
+    }
+  }
+
+  /// Premultiplies a straight rgb color with `alpha` and packs it into the bytes of one pixel.
+  pub fn pack(self, [r, g, b]: [u8; 3], alpha: u8) -> [u8; 4] {
+    let premultiply = |c: u8| (c as u32 * alpha as u32 / 255) as u8;
+    match self {
+      PixelFormat::Argb8888 => [premultiply(b), premultiply(g), premultiply(r), alpha],
+      PixelFormat::Abgr8888 => [premultiply(r), premultiply(g), premultiply(b), alpha],
+    }
+  }
+}
+
+const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+const WHITE: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+/// The color the overlay is drawn in, the corners stay black either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tint {
+  #[default]
+  Black,
+  /// Washes out dark themes instead of darkening them further.
+  White,
+}
+
+impl Tint {
+  pub fn rgb(self) -> [u8; 3] {
+    match self {
+      Tint::Black => BLACK,
+      Tint::White => WHITE,
+    }
+  }
+}
+
+/// A color given as `#rrggbb`, `rrggbb`, `black`, `white` or a color temperature like `3500K`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub [u8; 3]);
+
+impl std::str::FromStr for Color {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "black" => return Ok(Color(BLACK)),
+      "white" => return Ok(Color(WHITE)),
+      _ => {}
+    }
+    if let Some(kelvin) = s.strip_suffix(['K', 'k']) {
+      return match kelvin.parse() {
+        Ok(kelvin @ 1000..=40000) => Ok(Color(temperature(kelvin))),
+        _ => Err(format!("{s} is not a temperature from 1000K to 40000K")),
+      };
+    }
+
+    let invalid = || format!("{s} is not a color like #ff8800");
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+      return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid());
+    Ok(Color([channel(0)?, channel(1)?, channel(2)?]))
+  }
+}
+
+impl std::fmt::Display for Color {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let [r, g, b] = self.0;
+    write!(f, "#{r:02x}{g:02x}{b:02x}")
+  }
+}
+
+/// The color of a black body at `kelvin`, by the usual curve fitted to the CIE tables.
+fn temperature(kelvin: u32) -> [u8; 3] {
+  let t = kelvin as f64 / 100.0;
+  let red = match t {
+    t if t <= 66.0 => 255.0,
+    t => 329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2),
+  };
+  let green = match t {
+    t if t <= 66.0 => 99.470_802_586_1 * t.ln() - 161.119_568_166_1,
+    t => 288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2),
+  };
+  let blue = match t {
+    t if t >= 66.0 => 255.0,
+    t if t <= 19.0 => 0.0,
+    t => 138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7,
+  };
+  [red, green, blue].map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+}
+
+/// How the configured alpha maps to the alpha that ends up in the buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BrightnessCurve {
+  /// Alpha is used as is, 0.5 lets through half of the light
+  #[default]
+  Linear,
+  /// The remaining brightness is an sRGB value, 0.5 looks half as bright
+  Srgb,
+  /// The remaining brightness is raised to the power of `--gamma`
+  Gamma,
+}
+
+impl BrightnessCurve {
+  /// Converts the configured alpha to the physical alpha to blend with.
+  pub fn apply(self, alpha: f32, gamma: f32) -> f32 {
+    let brightness = 1.0 - alpha.clamp(0.0, 1.0);
+    let brightness = match self {
+      BrightnessCurve::Linear => brightness,
+      BrightnessCurve::Srgb => srgb_to_linear(brightness),
+      BrightnessCurve::Gamma => brightness.powf(gamma),
+    };
+    1.0 - brightness
+  }
+}
+
+/// Spreads alphas away from 0.5 by `contrast`, or pulls them towards it below 1,
+/// as `(alpha - 0.5) * contrast + 0.5`.
+///
+/// The one exception is an alpha of 0, which stays 0 rather than becoming 0.25 at a contrast
+/// of 0.5, so `set --alpha 0` and hiding still leave the screen undimmed.
+pub fn apply_contrast(alpha: f32, contrast: f32) -> f32 {
+  if alpha <= 0.0 {
+    return 0.0;
+  }
+  ((alpha - 0.5) * contrast + 0.5).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+  Top,
+  Bottom,
+  Left,
+  Right,
+}
+
+impl std::str::FromStr for Edge {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "top" => Ok(Edge::Top),
+      "bottom" => Ok(Edge::Bottom),
+      "left" => Ok(Edge::Left),
+      "right" => Ok(Edge::Right),
+      _ => Err(format!("{s} is not top, bottom, left or right")),
+    }
+  }
+}
+
+impl std::fmt::Display for Edge {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      Edge::Top => "top",
+      Edge::Bottom => "bottom",
+      Edge::Left => "left",
+      Edge::Right => "right",
+    })
+  }
+}
+
+/// A band along an edge drawn at `--curtain-alpha`, like `bottom:40%` or `top:200`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curtain {
+  pub edge: Edge,
+  pub extent: Extent,
+}
+
+impl Curtain {
+  /// How much the curtain covers a pixel, from 0 outside to 1 inside,
+  /// blending across `feather` pixels around its inner edge.
+  pub fn coverage(self, x: u32, y: u32, width: u32, height: u32, feather: u32) -> f32 {
+    // the distance from the edge the curtain hangs from
+    let (position, full) = match self.edge {
+      Edge::Top => (y, height),
+      Edge::Bottom => (height - 1 - y, height),
+      Edge::Left => (x, width),
+      Edge::Right => (width - 1 - x, width),
+    };
+    let depth = self.extent.resolve(full) as f32 - (position as f32 + 0.5);
+    if feather == 0 {
+      return if depth > 0.0 { 1.0 } else { 0.0 };
+    }
+    (depth / feather as f32 + 0.5).clamp(0.0, 1.0)
+  }
+}
+
+impl std::str::FromStr for Curtain {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (edge, extent) = s
+      .split_once(':')
+      .ok_or_else(|| format!("{s} is not like bottom:40%"))?;
+    Ok(Self {
+      edge: edge.parse()?,
+      extent: extent.parse()?,
+    })
+  }
+}
+
+impl std::fmt::Display for Curtain {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", self.edge, self.extent)
+  }
+}
+
+/// Concave corners where a panel along an edge meets the sides, like `top:12` or `top:12:30`.
+///
+/// The corners of the box below the panel are filled like the corners of the overlay,
+/// so the screen looks rounded into the panel. The panel is `inset` pixels thick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fillets {
+  pub edge: Edge,
+  pub radius: u32,
+  pub inset: u32,
+}
+
+impl Fillets {
+  /// Whether a pixel lies in one of the two fillets.
+  pub fn covers(self, x: u32, y: u32, width: u32, height: u32) -> bool {
+    // turned so the edge is on top, running along it and away from it
+    let (along, away, length) = match self.edge {
+      Edge::Top => (x, y, width),
+      Edge::Bottom => (x, height - 1 - y, width),
+      Edge::Left => (y, x, height),
+      Edge::Right => (y, width - 1 - x, height),
+    };
+    let Some(away) = away.checked_sub(self.inset) else {
+      return false;
+    };
+    // endlessly deep, so only the two corners at the panel are left
+    in_corner(
+      along,
+      away,
+      length,
+      u32::MAX,
+      Radius::circle(self.radius.min(length / 2)),
+    )
+  }
+}
+
+impl std::str::FromStr for Fillets {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("{s} is not like top:12 or top:12:30");
+    let mut parts = s.split(':');
+    let edge = parts.next().ok_or_else(invalid)?.parse()?;
+    let radius = parts
+      .next()
+      .ok_or_else(invalid)?
+      .parse()
+      .map_err(|_| invalid())?;
+    let inset = match parts.next() {
+      Some(inset) => inset.parse().map_err(|_| invalid())?,
+      None => 0,
+    };
+    if parts.next().is_some() {
+      return Err(invalid());
+    }
+    Ok(Self {
+      edge,
+      radius,
+      inset,
+    })
+  }
+}
+
+/// Corner radii in pixels, `24` for circles or `24x12` for wider than tall ellipses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Radius {
+  pub horizontal: u32,
+  pub vertical: u32,
+}
+
+impl Radius {
+  pub const fn circle(radius: u32) -> Self {
+    Self {
+      horizontal: radius,
+      vertical: radius,
+    }
+  }
+
+  /// Shrinks the radii so the corners don't overlap, circles stay circles.
+  pub fn fit(self, width: u32, height: u32) -> Self {
+    if self.horizontal == self.vertical {
+      Self::circle(self.horizontal.min(width.min(height) / 2))
+    } else {
+      Self {
+        horizontal: self.horizontal.min(width / 2),
+        vertical: self.vertical.min(height / 2),
+      }
+    }
+  }
+}
+
+impl std::str::FromStr for Radius {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let parse = |part: &str| {
+      part
+        .parse::<u32>()
+        .map_err(|_| format!("{s} is neither a radius like 24 nor like 24x12"))
+    };
+    match s.split_once('x') {
+      Some((horizontal, vertical)) => Ok(Self {
+        horizontal: parse(horizontal)?,
+        vertical: parse(vertical)?,
+      }),
+      None => parse(s).map(Self::circle),
+    }
+  }
+}
+
+impl std::fmt::Display for Radius {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.horizontal == self.vertical {
+      write!(f, "{}", self.horizontal)
+    } else {
+      write!(f, "{}x{}", self.horizontal, self.vertical)
+    }
+  }
+}
+
+/// The sRGB transfer function, decoding to linear light.
+fn srgb_to_linear(value: f32) -> f32 {
+  if value <= 0.04045 {
+    value / 12.92
+  } else {
+    ((value + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+/// The rectangle of a buffer that changed, in buffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Damage {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Damage {
+  pub fn full(width: u32, height: u32) -> Self {
+    Self {
+      x: 0,
+      y: 0,
+      width,
+      height,
+    }
+  }
+
+  /// The smallest rectangle covering both.
+  pub fn union(self, other: Self) -> Self {
+    let (left, top) = (self.x.min(other.x), self.y.min(other.y));
+    let right = (self.x + self.width).max(other.x + other.width);
+    let bottom = (self.y + self.height).max(other.y + other.height);
+    Self {
+      x: left,
+      y: top,
+      width: right - left,
+      height: bottom - top,
+    }
+  }
+}
+
+/// Where an overlay sits in the box around all outputs, see `--span`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  /// Offset of the overlay from the top left of the box.
+  pub x: u32,
+  pub y: u32,
+  /// Size of the whole box.
+  pub width: u32,
+  pub height: u32,
+}
+
+/// What one view draws, on top of what all views share through the settings.
+///
+/// Alpha and radius are part of it since they can differ per output or be mid-animation.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+  pub width: u32,
+  pub height: u32,
+  pub format: PixelFormat,
+  pub alpha: f32,
+  pub radius: Radius,
+  pub underlay: Option<&'a Underlay>,
+  pub span: Option<Span>,
+  pub color: Option<[u8; 3]>,
+  /// The opacity of the clipboard badge while it shows.
+  pub badge: Option<f32>,
+}
+
+/// Fills `canvas` with the `width * height` pixels of `frame` in its format.
+///
+/// With an underlay, the overlay is drawn opaque, as that dimmed with the alpha,
+/// or with only its lightness taken away for `--preserve-wallpaper-colors`.
+/// With a span, curtains, regions, the reading strip and images are laid out across it instead of the overlay alone.
+/// A color of the output's own replaces the overlay color, except during a blackout.
+/// A pulsing alert shows over everything but the corners, even while hidden,
+/// and a progress bar over everything.
+/// A clipboard badge with its opacity shows over the dim, but not while hidden or blacked out.
+/// Pixels that already have the right color are left alone, and the returned damage
+/// covers the ones that didn't, if any.
+pub fn render(canvas: &mut [u8], frame: Frame, settings: &DimlandSettings) -> Option<Damage> {
+  let Frame {
+    width,
+    height,
+    format,
+    alpha,
+    radius,
+    underlay,
+    span,
+    color,
+    badge,
+  } = frame;
+  let pulse = settings.alert_color();
+  let bar = settings.progress_bar();
+  let bar = |x, y| {
+    bar
+      .filter(|bar| bar.covers(x, y, width, height))
+      .map(|bar| bar.color.0)
+  };
+  if settings.hidden && pulse.is_none() {
+    return paint(canvas, width, format, |x, y| match bar(x, y) {
+      Some(color) => (color, 0xFF),
+      None => (BLACK, 0x00),
+    });
+  }
+  if settings.test_pattern {
+    return paint(canvas, width, format, |x, y| {
+      test_pattern(x, y, width, height)
+    });
+  }
+
+  // corners wider than half the overlay would overlap and leave transparent strips
+  let radius = radius.fit(width, height);
+  let physical = |alpha: f32| {
+    let alpha = apply_contrast(alpha, settings.contrast);
+    settings.brightness_curve.apply(alpha, settings.gamma)
+  };
+  let curtain_alpha = physical(settings.curtain_alpha);
+  let region_alphas: Vec<Option<f32>> = settings
+    .regions
+    .iter()
+    .map(|region| region.alpha.map(physical))
+    .collect();
+  let alpha = physical(alpha);
+  let corner = if settings.invert_corners { 0x00 } else { 0xFF };
+  let color = match color {
+    Some(color) if settings.blacked_out.is_none() => color,
+    _ => settings.overlay_color(),
+  };
+  let base = color;
+  // a buffer that doesn't match its output, like mid resize, is drawn on its own
+  let span = span
+    .filter(|span| span.x + width <= span.width && span.y + height <= span.height)
+    .unwrap_or(Span {
+      x: 0,
+      y: 0,
+      width,
+      height,
+    });
+  let images = settings.image_layers(span.width, span.height);
+  let badge = badge.filter(|_| settings.blacked_out.is_none());
+  let strip = settings.reading_strip();
+
+  paint(canvas, width, format, |x, y| {
+    if let Some(color) = bar(x, y) {
+      return (color, 0xFF);
+    }
+    let alpha = match settings.curtain {
+      Some(curtain) => {
+        let coverage = curtain.coverage(
+          span.x + x,
+          span.y + y,
+          span.width,
+          span.height,
+          settings.curtain_feather,
+        );
+        alpha + (curtain_alpha - alpha) * coverage
+      }
+      None => alpha,
+    };
+    let alpha = regions::covering(&settings.regions, span.x + x, span.y + y)
+      .and_then(|index| region_alphas[index])
+      .unwrap_or(alpha);
+    let alpha = grain::apply(alpha, settings.grain, x, y, settings.grain_seed);
+    let alpha = match settings.scanlines {
+      Some(scanlines) => scanlines.apply(alpha, span.y + y),
+      None => alpha,
+    };
+    let alpha = match strip {
+      Some(strip) => alpha * (1.0 - strip.opening(span.y + y, span.height)),
+      None => alpha,
+    };
+    let index = (span.y + y) as usize * span.width as usize + (span.x + x) as usize;
+    let sample = image::sample(&images, index);
+    let (color, alpha) = image::over(color, alpha, sample);
+    let (color, alpha) = match pulse {
+      Some((pulse, strength)) => {
+        // while hidden only the pulse itself shows
+        let alpha = if settings.hidden { 0.0 } else { alpha };
+        let peak = alpha.max(alert::ALPHA);
+        (
+          mix(color, pulse, strength),
+          alpha + (peak - alpha) * strength,
+        )
+      }
+      None => (color, alpha),
+    };
+    let (color, alpha) = match badge.zip(clipboard::badge(x, y)) {
+      Some((opacity, badge)) => clipboard::over(color, alpha, badge, opacity),
+      None => (color, alpha),
+    };
+    if in_corner(x, y, width, height, radius) {
+      (BLACK, corner)
+    } else if settings
+      .fillets
+      .is_some_and(|fillets| fillets.covers(x, y, width, height))
+    {
+      (BLACK, 0xFF)
+    } else if let Some(underlay) = underlay {
+      let below = underlay.sample(x, y, width, height);
+      // images, alerts and badges change the color and still blend in as usual
+      match settings.preserve_wallpaper_colors && color == base {
+        true => (lab::darken(below, alpha), 0xFF),
+        false => (mix(below, color, alpha), 0xFF),
+      }
+    } else {
+      (color, (alpha * 255.0) as u8)
+    }
+  })
+}
+
+/// Blends `over` onto `under` with `alpha`.
+pub fn mix(under: [u8; 3], over: [u8; 3], alpha: f32) -> [u8; 3] {
+  std::array::from_fn(|i| (under[i] as f32 * (1.0 - alpha) + over[i] as f32 * alpha).round() as u8)
+}
+
+/// Whether the pixel lies outside the arc of the nearest corner.
+///
+/// Every corner is measured the same way from its mirrored position in the top left one,
+/// so all four come out as exact mirror images of each other.
+fn in_corner(x: u32, y: u32, width: u32, height: u32, radius: Radius) -> bool {
+  let x = x.min(width - 1 - x);
+  let y = y.min(height - 1 - y);
+  if x >= radius.horizontal || y >= radius.vertical {
+    return false;
+  }
+
+  // distances from the center of the ellipse to the center of the pixel, doubled to stay in integers
+  let (rx, ry) = (2 * radius.horizontal as u64, 2 * radius.vertical as u64);
+  let dx = rx - 2 * x as u64 - 1;
+  let dy = ry - 2 * y as u64 - 1;
+  // outside the ellipse when (dx/rx)² + (dy/ry)² > 1, multiplied out
+  dx.pow(2) * ry.pow(2) + dy.pow(2) * rx.pow(2) > rx.pow(2) * ry.pow(2)
+}
+
+/// Writes the color of every pixel that differs and returns the bounds of those that did.
+fn paint(
+  canvas: &mut [u8],
+  width: u32,
+  format: PixelFormat,
+  color: impl Fn(u32, u32) -> ([u8; 3], u8),
+) -> Option<Damage> {
+  let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+  canvas
+    .chunks_exact_mut(4)
+    .enumerate()
+    .for_each(|(index, chunk)| {
+      let x = (index as u32) % width;
+      let y = (index as u32) / width;
+
+      let (rgb, alpha) = color(x, y);
+      let pixel = format.pack(rgb, alpha);
+      let array: &mut [u8; 4] = chunk.try_into().unwrap();
+      if *array == pixel {
+        return;
+      }
+      *array = pixel;
+
+      bounds = Some(match bounds {
+        Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+        None => (x, y, x, y),
+      });
+    });
+
+  bounds.map(|(left, top, right, bottom)| Damage {
+    x: left,
+    y: top,
+    width: right - left + 1,
+    height: bottom - top + 1,
+  })
+}
+
+/// Spacing of the test pattern grid lines in buffer pixels.
+const GRID_SPACING: u32 = 32;
+/// Size of the squares marking the corners and the center.
+const MARKER_SIZE: u32 = 24;
+
+/// A grid with a differently colored square in each corner and the center.
+///
+/// Top left is red, top right green, bottom left blue, bottom right yellow and the center white,
+/// so a flipped, rotated, offset or wrongly scaled buffer is easy to spot.
+fn test_pattern(x: u32, y: u32, width: u32, height: u32) -> ([u8; 3], u8) {
+  let (center_x, center_y) = (width / 2, height / 2);
+  let half = MARKER_SIZE / 2;
+  let left = x < MARKER_SIZE;
+  let right = x >= width.saturating_sub(MARKER_SIZE);
+  let top = y < MARKER_SIZE;
+  let bottom = y >= height.saturating_sub(MARKER_SIZE);
+
+  if top && left {
+    ([0xFF, 0x00, 0x00], 0xFF)
+  } else if top && right {
+    ([0x00, 0xFF, 0x00], 0xFF)
+  } else if bottom && left {
+    ([0x00, 0x00, 0xFF], 0xFF)
+  } else if bottom && right {
+    ([0xFF, 0xFF, 0x00], 0xFF)
+  } else if x.abs_diff(center_x) < half && y.abs_diff(center_y) < half {
+    (WHITE, 0xFF)
+  } else if x.is_multiple_of(GRID_SPACING)
+    || y.is_multiple_of(GRID_SPACING)
+    || x == width - 1
+    || y == height - 1
+  {
+    (WHITE, 0x80)
+  } else {
+    (BLACK, 0x00)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn curtains_cover_their_band() {
+    let curtain: Curtain = "bottom:40%".parse().unwrap();
+    assert_eq!(curtain.coverage(0, 59, 100, 100, 0), 0.0);
+    assert_eq!(curtain.coverage(0, 60, 100, 100, 0), 1.0);
+    assert_eq!(curtain.coverage(0, 99, 100, 100, 0), 1.0);
+    let curtain: Curtain = "left:10".parse().unwrap();
+    assert_eq!(curtain.coverage(9, 0, 100, 100, 0), 1.0);
+    assert_eq!(curtain.coverage(10, 0, 100, 100, 0), 0.0);
+    assert_eq!(curtain.to_string(), "left:10");
+  }
+
+  #[test]
+  fn curtains_feather_their_edge() {
+    let curtain: Curtain = "top:50".parse().unwrap();
+    assert_eq!(curtain.coverage(0, 0, 100, 100, 10), 1.0);
+    assert_eq!(curtain.coverage(0, 99, 100, 100, 10), 0.0);
+    let inside = curtain.coverage(0, 47, 100, 100, 10);
+    let outside = curtain.coverage(0, 52, 100, 100, 10);
+    assert!(inside > 0.5 && inside < 1.0);
+    assert!(outside > 0.0 && outside < 0.5);
+  }
+
+  #[test]
+  fn fillets_round_into_the_panel() {
+    let fillets: Fillets = "top:4:2".parse().unwrap();
+    let (width, height) = (10, 6);
+    let drawn: Vec<String> = (0..height)
+      .map(|y| {
+        (0..width)
+          .map(|x| match fillets.covers(x, y, width, height) {
+            true => '#',
+            false => '.',
+          })
+          .collect()
+      })
+      .collect();
+    assert_eq!(
+      drawn,
+      [
+        "..........",
+        "..........",
+        "##......##",
+        "#........#",
+        "..........",
+        "..........",
+      ]
+    );
+  }
+
+  #[test]
+  fn fillets_turn_with_their_edge() {
+    let fillets: Fillets = "right:4".parse().unwrap();
+    let (width, height) = (6, 10);
+    assert!(fillets.covers(5, 0, width, height));
+    assert!(fillets.covers(5, 9, width, height));
+    assert!(!fillets.covers(0, 0, width, height));
+    assert!(!fillets.covers(5, 5, width, height));
+    assert!("top:12:30:1".parse::<Fillets>().is_err());
+    assert!("middle:12".parse::<Fillets>().is_err());
+  }
+
+  #[test]
+  fn contrast_spreads_around_the_middle() {
+    assert_eq!(apply_contrast(0.75, 1.0), 0.75);
+    assert_eq!(apply_contrast(0.5, 1.5), 0.5);
+    assert_eq!(apply_contrast(0.75, 2.0), 1.0);
+    assert_eq!(apply_contrast(0.25, 2.0), 0.0);
+    assert_eq!(apply_contrast(0.75, 0.5), 0.625);
+  }
+
+  #[test]
+  fn contrast_keeps_zero_off() {
+    assert_eq!(apply_contrast(0.0, 0.5), 0.0);
+    assert_eq!(apply_contrast(0.0, 0.0), 0.0);
+    // anything above 0 follows the formula again
+    assert_eq!(apply_contrast(0.1, 0.5), 0.3);
+  }
+
+  #[test]
+  fn fit_clamps_to_half_the_smaller_side() {
+    assert_eq!(Radius::circle(24).fit(3840, 2160), Radius::circle(24));
+    assert_eq!(Radius::circle(300).fit(1280, 400), Radius::circle(200));
+    assert_eq!(
+      Radius {
+        horizontal: 700,
+        vertical: 300
+      }
+      .fit(1280, 400),
+      Radius {
+        horizontal: 640,
+        vertical: 200
+      }
+    );
+    assert_eq!(Radius::circle(5).fit(1, 1), Radius::circle(0));
+  }
+
+  #[test]
+  fn fit_follows_a_shrinking_size() {
+    let radius = Radius::circle(100);
+    assert_eq!(radius.fit(800, 600), radius);
+    // configured smaller after the first render, the corners must not overlap
+    let shrunk = radius.fit(150, 120);
+    assert_eq!(shrunk, Radius::circle(60));
+    for y in 0..120 {
+      assert!(!in_corner(75, y, 150, 120, shrunk));
+    }
+  }
+
+  #[test]
+  fn elliptical_corners_are_wider_than_tall() {
+    use clap::Parser;
+
+    let args = crate::DimlandArgs::parse_from(["dimland", "--alpha", "0", "--radius", "6x3"]);
+    let settings = DimlandSettings::new(&args);
+    let (width, height) = (16, 10);
+    let mut canvas = vec![0; width as usize * height as usize * 4];
+    let frame = Frame {
+      width,
+      height,
+      format: PixelFormat::Argb8888,
+      alpha: settings.alpha,
+      radius: settings.radius,
+      underlay: None,
+      span: None,
+      color: None,
+      badge: None,
+    };
+    render(&mut canvas, frame, &settings);
+
+    // the opaque corners in the top left quarter of the buffer
+    let quarter: Vec<String> = (0..height / 2)
+      .map(|y| {
+        (0..width / 2)
+          .map(|x| match canvas[(y * width + x) as usize * 4 + 3] {
+            0xFF => '#',
+            _ => '.',
+          })
+          .collect()
+      })
+      .collect();
+    assert_eq!(
+      quarter,
+      ["###.....", "#.......", "........", "........", "........"]
+    );
+  }
+
+  #[test]
+  fn a_flat_radius_has_square_corners() {
+    let (width, height) = (64, 48);
+    for radius in [
+      Radius {
+        horizontal: 0,
+        vertical: 12,
+      },
+      Radius {
+        horizontal: 20,
+        vertical: 0,
+      },
+    ] {
+      assert_eq!(radius.fit(width, height), radius);
+      for y in 0..height {
+        for x in 0..width {
+          assert!(
+            !in_corner(x, y, width, height, radius),
+            "{radius} at {x},{y}"
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn corners_are_symmetric() {
+    let (width, height) = (64, 48);
+    for radius in [
+      Radius::circle(12),
+      Radius {
+        horizontal: 20,
+        vertical: 9,
+      },
+    ] {
+      let corner = |x, y| in_corner(x, y, width, height, radius);
+      assert!(corner(0, 0));
+      assert!(!corner(width / 2, height / 2));
+      for y in 0..height {
+        for x in 0..width {
+          let mirrored_x = width - 1 - x;
+          let mirrored_y = height - 1 - y;
+          assert_eq!(corner(x, y), corner(mirrored_x, y), "{radius} at {x},{y}");
+          assert_eq!(corner(x, y), corner(x, mirrored_y), "{radius} at {x},{y}");
+          assert_eq!(
+            corner(x, y),
+            corner(mirrored_x, mirrored_y),
+            "{radius} at {x},{y}"
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn parse_colors() {
+    assert_eq!("#ff8800".parse(), Ok(Color([0xFF, 0x88, 0x00])));
+    assert_eq!("0044FF".parse(), Ok(Color([0x00, 0x44, 0xFF])));
+    assert_eq!("white".parse(), Ok(Color(WHITE)));
+    assert!("#ff88".parse::<Color>().is_err());
+    assert!("#gg8800".parse::<Color>().is_err());
+    assert!("#ff88€".parse::<Color>().is_err());
+    assert_eq!(Color([0xFF, 0x88, 0x00]).to_string(), "#ff8800");
+  }
+
+  #[test]
+  fn parse_temperatures() {
+    assert_eq!("6600K".parse(), Ok(Color(WHITE)));
+    let Ok(Color([red, green, blue])) = "3500K".parse() else {
+      panic!("3500K is a temperature");
+    };
+    // warm, red falls off last
+    assert!(
+      red == 0xFF && green < red && blue < green,
+      "{red} {green} {blue}"
+    );
+    assert!("500K".parse::<Color>().is_err());
+    assert!("warmK".parse::<Color>().is_err());
+  }
+
+  #[test]
+  fn negotiate_prefers_argb() {
+    let both = [Format::Abgr8888, Format::Argb8888, Format::Xrgb8888];
+    assert_eq!(
+      PixelFormat::negotiate(&both, false),
+      Some(PixelFormat::Argb8888)
+    );
+    let abgr = [Format::Abgr8888, Format::Xbgr8888];
+    assert_eq!(
+      PixelFormat::negotiate(&abgr, false),
+      Some(PixelFormat::Abgr8888)
+    );
+    assert_eq!(PixelFormat::negotiate(&[], false), None);
+    assert_eq!(PixelFormat::negotiate(&[Format::Rgb565], false), None);
+  }
+
+  #[test]
+  fn negotiate_opaque_without_alpha() {
+    let argb = [Format::Argb8888, Format::Xrgb8888];
+    assert_eq!(
+      PixelFormat::negotiate(&argb, true),
+      Some(PixelFormat::Argb8888)
+    );
+    assert_eq!(PixelFormat::Argb8888.wl_format(true), Format::Xrgb8888);
+    let mixed = [Format::Argb8888, Format::Xbgr8888];
+    assert_eq!(
+      PixelFormat::negotiate(&mixed, true),
+      Some(PixelFormat::Abgr8888)
+    );
+    assert_eq!(PixelFormat::negotiate(&[Format::Argb8888], true), None);
+  }
+
+  #[test]
+  fn pack_argb() {
+    let format = PixelFormat::Argb8888;
+    assert_eq!(
+      format.pack([0x11, 0x22, 0x33], 0xFF),
+      [0x33, 0x22, 0x11, 0xFF]
+    );
+    assert_eq!(format.pack(WHITE, 0x80), [0x80, 0x80, 0x80, 0x80]);
+    assert_eq!(format.pack(BLACK, 0x40), [0x00, 0x00, 0x00, 0x40]);
+  }
+
+  #[test]
+  fn pack_abgr() {
+    let format = PixelFormat::Abgr8888;
+    assert_eq!(
+      format.pack([0x11, 0x22, 0x33], 0xFF),
+      [0x11, 0x22, 0x33, 0xFF]
+    );
+    assert_eq!(
+      format.pack([0xFF, 0x00, 0x00], 0x80),
+      [0x80, 0x00, 0x00, 0x80]
+    );
+    assert_eq!(format.pack(BLACK, 0x40), [0x00, 0x00, 0x00, 0x40]);
+  }
+}
@@ -0,0 +1,325 @@
+//! The JSON protocol of the control socket, for tools that talk to a running dimland.
+//!
+//! A connection sends one [`Request`] as a line of JSON in place of a text command and
+//! gets one [`Response`] line back, like `{"id":1,"method":"set","params":{"alpha":0.6}}`
+//! answered with `{"id":1,"result":null}`.
+//!
+//! Within a protocol [`VERSION`], methods, params and result fields are only ever added,
+//! never renamed, removed or given another meaning, so clients should ignore result fields
+//! they don't know. Sending `hello` with the version a client speaks fails with
+//! [`UNSUPPORTED_VERSION`] if dimland doesn't speak it.
+//!
+//! The text commands of `dimland set` and friends are shorthands for the same methods.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The version of the protocol described here.
+pub const VERSION: u32 = 1;
+
+/// The request is not valid JSON.
+pub const PARSE_ERROR: i32 = -32700;
+/// The request is JSON, but not a request.
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+/// The method ran into a problem, like a profile that doesn't exist.
+pub const FAILED: i32 = 1;
+pub const UNSUPPORTED_VERSION: i32 = 2;
+/// The params are valid, but dimland can't do that yet.
+pub const UNSUPPORTED: i32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+  /// Sent back in the response as is.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub id: Option<Value>,
+  pub method: String,
+  /// Can be left out for methods without params or with only optional ones.
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub params: Value,
+}
+
+impl Request {
+  pub fn new(method: &str, params: impl Serialize) -> Self {
+    Self {
+      id: None,
+      method: method.to_string(),
+      params: serde_json::to_value(params).expect("params have to serialize to JSON"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+  #[serde(default)]
+  pub id: Option<Value>,
+  /// What the method returned, null for those that only change something.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub result: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<Error>,
+}
+
+impl Response {
+  pub fn result(id: Option<Value>, result: Value) -> Self {
+    Self {
+      id,
+      result: Some(result),
+      error: None,
+    }
+  }
+
+  pub fn error(id: Option<Value>, error: Error) -> Self {
+    Self {
+      id,
+      result: None,
+      error: Some(error),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Error {
+  pub code: i32,
+  pub message: String,
+}
+
+impl Error {
+  pub fn new(code: i32, message: impl Into<String>) -> Self {
+    Self {
+      code,
+      message: message.into(),
+    }
+  }
+}
+
+/// `hello`, answered with a [`HelloResult`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HelloParams {
+  pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HelloResult {
+  pub version: u32,
+}
+
+/// `set`, which needs an alpha, a radius, a curtain, an image, or a color and an output,
+/// or a region and what to change about it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SetParams {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub alpha: Option<f32>,
+  /// Like `24` or `24x12`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub radius: Option<String>,
+  /// Like `bottom:40%`, `none`, or a step like `+5%`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub curtain: Option<String>,
+  /// The path of a png, or `none`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub image: Option<String>,
+  /// Ignore `--min-alpha` and `--max-alpha`.
+  pub force: bool,
+  /// Go back after this long, like `30s` or `10m`.
+  #[serde(rename = "for", skip_serializing_if = "Option::is_none")]
+  pub duration: Option<String>,
+  /// Like `#ffeedd` or `5000K`, or `none` to go back to the color of all outputs.
+  /// Only for a single output for now, so it needs `output`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub color: Option<String>,
+  /// The name of the output to change, only the color can be changed for one.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub output: Option<String>,
+  /// The named region to change, only its alpha, radius and color.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub region: Option<String>,
+}
+
+/// `toggle`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToggleParams {
+  #[serde(rename = "for", skip_serializing_if = "Option::is_none")]
+  pub duration: Option<String>,
+}
+
+/// `increase` and `decrease`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StepParams {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub step: Option<f32>,
+}
+
+/// `blackout`, lifting it again with `off`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlackoutParams {
+  pub off: bool,
+  /// Lift it after this long instead of `--blackout-timeout`.
+  #[serde(rename = "for", skip_serializing_if = "Option::is_none")]
+  pub duration: Option<String>,
+}
+
+/// `ramp`, and `ramp-pause`, `ramp-resume` and `ramp-cancel` without params.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RampParams {
+  pub to: f32,
+  /// Like `25m`.
+  pub over: String,
+  /// `flash`, `hold` or `revert`, `flash` if left out.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub then: Option<String>,
+}
+
+/// `alert`, pulsing `#ff0000` 3 times without params.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertParams {
+  /// Like `#aa0000`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub color: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub count: Option<u32>,
+}
+
+/// `progress`, a bar along an edge that hides itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgressParams {
+  /// From 0 to 1.
+  pub value: f32,
+  /// `top`, `bottom`, `left` or `right`, `bottom` if left out.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub edge: Option<String>,
+  /// Like `#88c0d0`, white if left out.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub color: Option<String>,
+  /// Hide it after this long instead of after 1.5s.
+  #[serde(rename = "for", default, skip_serializing_if = "Option::is_none")]
+  pub duration: Option<String>,
+}
+
+/// `strip`, moving the reading strip or putting it up, and taking it down with `off`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StripParams {
+  pub off: bool,
+  /// The middle of the strip in pixels from the top, where it was if left out.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub y: Option<u32>,
+  /// In pixels, the last height if left out.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub height: Option<u32>,
+}
+
+/// `profile`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileParams {
+  pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RampStatus {
+  pub from: f32,
+  pub to: f32,
+  /// From 0 to 1.
+  pub progress: f32,
+  pub paused: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StripStatus {
+  /// None while it sits in the middle.
+  pub y: Option<u32>,
+  pub height: u32,
+}
+
+/// A named region as it is drawn right now.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionStatus {
+  pub alpha: f32,
+  pub radius: String,
+  /// Like `#ffeedd`, none while it has the color of the output it is on.
+  pub color: Option<String>,
+}
+
+/// The result of `status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+  pub alpha: f32,
+  pub radius: String,
+  /// Like `bottom:40%`, none without a curtain.
+  #[serde(default)]
+  pub curtain: Option<String>,
+  /// The path of the png drawn over the dim.
+  #[serde(default)]
+  pub image: Option<String>,
+  pub min_alpha: f32,
+  pub max_alpha: f32,
+  pub hidden: bool,
+  pub profile: Option<String>,
+  /// Seconds until a `for` override goes back.
+  pub override_left: Option<u64>,
+  /// The ramp towards an alpha, none without one.
+  #[serde(default)]
+  pub ramp: Option<RampStatus>,
+  /// Seconds until a blackout lifts itself, none without one.
+  #[serde(default)]
+  pub blackout_left: Option<u64>,
+  pub outputs: Vec<String>,
+  /// Alert pulses still to come.
+  #[serde(default)]
+  pub alert_pulses: u32,
+  /// The reading strip, none without one.
+  #[serde(default)]
+  pub strip: Option<StripStatus>,
+  /// The color each output is drawn in right now, like `#ffeedd`, by name.
+  #[serde(default)]
+  pub colors: BTreeMap<String, String>,
+  /// Where the colors of outputs with their own one come from by name, `remembered` for
+  /// ones set at runtime and `configured` for `--output-color`.
+  #[serde(default)]
+  pub color_sources: BTreeMap<String, String>,
+  /// The named regions by name.
+  #[serde(default)]
+  pub regions: BTreeMap<String, RegionStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn set_params_use_the_text_names() {
+    let request: Request =
+      serde_json::from_str(r#"{"id":1,"method":"set","params":{"alpha":0.6,"for":"10m"}}"#)
+        .unwrap();
+    let params: SetParams = serde_json::from_value(request.params).unwrap();
+    assert_eq!(params.alpha, Some(0.6));
+    assert_eq!(params.duration.as_deref(), Some("10m"));
+    assert!(!params.force);
+  }
+
+  #[test]
+  fn responses_leave_out_what_is_missing() {
+    let response = Response::result(Some(1.into()), Value::Null);
+    assert_eq!(
+      serde_json::to_string(&response).unwrap(),
+      r#"{"id":1,"result":null}"#
+    );
+    let response = Response::error(None, Error::new(PARSE_ERROR, "oops"));
+    assert_eq!(
+      serde_json::to_string(&response).unwrap(),
+      r#"{"id":null,"error":{"code":-32700,"message":"oops"}}"#
+    );
+  }
+}
@@ -0,0 +1,42 @@
+//! `--scanlines`, darker rows across the dim like an old CRT.
+//!
+//! Only the row decides, so the pattern stays put and costs nothing to repaint.
+
+pub const DEFAULT_SPACING: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scanlines {
+  /// How much of what the dim lets through a scanline takes away, from 0 to 1.
+  pub strength: f32,
+  /// Every this many rows one is a scanline, starting with the first.
+  pub spacing: u32,
+}
+
+impl Scanlines {
+  /// Darkens `alpha` on the scanlines and leaves the rows between them alone.
+  pub fn apply(self, alpha: f32, y: u32) -> f32 {
+    if !y.is_multiple_of(self.spacing) {
+      return alpha;
+    }
+    alpha + (1.0 - alpha) * self.strength
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn darkens_every_few_rows() {
+    let scanlines = Scanlines {
+      strength: 0.5,
+      spacing: 3,
+    };
+    assert_eq!(scanlines.apply(0.5, 0), 0.75);
+    assert_eq!(scanlines.apply(0.5, 1), 0.5);
+    assert_eq!(scanlines.apply(0.5, 2), 0.5);
+    assert_eq!(scanlines.apply(0.5, 3), 0.75);
+    // an opaque dim has nothing left to darken
+    assert_eq!(scanlines.apply(1.0, 0), 1.0);
+  }
+}
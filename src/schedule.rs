@@ -0,0 +1,184 @@
+//! `--alpha-from-time`, following a daily schedule of alphas, and `--at` and `--duration`.
+
+use std::{str::FromStr, time::Duration};
+
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  LoopHandle,
+};
+
+use crate::DimlandData;
+
+const MINUTES_PER_DAY: f32 = 24.0 * 60.0;
+/// Changes between two points are spread over the minutes between them.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Alphas at times of the day, like `08:00=0.0,22:00=0.5`,
+/// interpolated linearly in between and around midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+  /// Minutes since midnight and the alpha there, sorted by time.
+  points: Vec<(f32, f32)>,
+}
+
+impl Schedule {
+  /// The alpha at a time given in minutes since midnight.
+  pub fn alpha(&self, minutes: f32) -> f32 {
+    let next = self
+      .points
+      .iter()
+      .position(|&(time, _)| time > minutes)
+      .unwrap_or(0);
+    let previous = next.checked_sub(1).unwrap_or(self.points.len() - 1);
+    let (start, from) = self.points[previous];
+    let (end, to) = self.points[next];
+
+    // either one may be on the other side of midnight
+    let span = (end - start).rem_euclid(MINUTES_PER_DAY);
+    if span == 0.0 {
+      return from;
+    }
+    let progress = (minutes - start).rem_euclid(MINUTES_PER_DAY) / span;
+    from + (to - from) * progress
+  }
+}
+
+impl FromStr for Schedule {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut points = s
+      .split(',')
+      .map(|point| {
+        let (time, alpha) = point
+          .split_once('=')
+          .ok_or_else(|| format!("{point} is not like 22:00=0.5"))?;
+        let alpha: f32 = alpha
+          .trim()
+          .parse()
+          .map_err(|_| format!("{alpha} is not an alpha"))?;
+        if !(0.0..=1.0).contains(&alpha) {
+          return Err(format!("{alpha} is not between 0 and 1"));
+        }
+        Ok((parse_time(time.trim())?, alpha))
+      })
+      .collect::<Result<Vec<_>, String>>()?;
+
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+      return Err("every time can only appear once".to_string());
+    }
+    Ok(Self { points })
+  }
+}
+
+/// Parses `HH:MM` into minutes since midnight.
+pub fn parse_time(time: &str) -> Result<f32, String> {
+  let invalid = || format!("{time} is not a time like 08:00");
+  let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+  let hours: u32 = hours.parse().map_err(|_| invalid())?;
+  let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+  if hours >= 24 || minutes >= 60 {
+    return Err(invalid());
+  }
+  Ok((hours * 60 + minutes) as f32)
+}
+
+/// Minutes since local midnight.
+fn local_minutes() -> f32 {
+  // SAFETY: time with a null pointer only returns, localtime_r writes into the tm given to it
+  unsafe {
+    let now = libc::time(std::ptr::null_mut());
+    let mut tm: libc::tm = std::mem::zeroed();
+    if libc::localtime_r(&now, &mut tm).is_null() {
+      return 0.0;
+    }
+    tm.tm_hour as f32 * 60.0 + tm.tm_min as f32 + tm.tm_sec as f32 / 60.0
+  }
+}
+
+/// Sets the alpha from the schedule every minute through [`DimlandData::set_alpha`].
+pub fn watch(handle: &LoopHandle<DimlandData>, schedule: Schedule) {
+  handle
+    .insert_source(Timer::immediate(), move |_, _, data| {
+      data.set_alpha(schedule.alpha(local_minutes()));
+      TimeoutAction::ToDuration(INTERVAL)
+    })
+    .expect("can't start the schedule timer");
+}
+
+/// Minutes from `now` until `time` comes around next, both since midnight.
+fn minutes_until(time: f32, now: f32) -> f32 {
+  (time - now).rem_euclid(MINUTES_PER_DAY)
+}
+
+/// Stays idle until `time` in minutes since midnight, then dims, for `duration` if given.
+pub fn wake_at(handle: &LoopHandle<DimlandData>, time: f32, duration: Option<Duration>) {
+  let wait = Duration::from_secs_f32(minutes_until(time, local_minutes()) * 60.0);
+  handle
+    .insert_source(Timer::from_duration(wait), move |_, _, data| {
+      data.wake_scheduled();
+      if let Some(duration) = duration {
+        exit_after(&data.loop_handle, duration);
+      }
+      TimeoutAction::Drop
+    })
+    .expect("can't start the --at timer");
+}
+
+/// Exits after `duration`, see `--duration`.
+pub fn exit_after(handle: &LoopHandle<DimlandData>, duration: Duration) {
+  handle
+    .insert_source(Timer::from_duration(duration), |_, _, data| {
+      data.exit = true;
+      TimeoutAction::Drop
+    })
+    .expect("can't start the --duration timer");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+  }
+
+  #[test]
+  fn interpolates_between_points() {
+    let schedule: Schedule = "08:00=0.0,22:00=0.5".parse().unwrap();
+    assert_eq!(schedule.alpha(8.0 * 60.0), 0.0);
+    assert_eq!(schedule.alpha(15.0 * 60.0), 0.25);
+    assert_eq!(schedule.alpha(22.0 * 60.0), 0.5);
+  }
+
+  #[test]
+  fn wraps_around_midnight() {
+    let schedule: Schedule = "22:00=0.5,06:00=0.1".parse().unwrap();
+    assert_close(schedule.alpha(2.0 * 60.0), 0.3);
+    assert_close(schedule.alpha(23.0 * 60.0), 0.45);
+    assert_close(schedule.alpha(14.0 * 60.0), 0.3);
+  }
+
+  #[test]
+  fn a_single_point_holds_all_day() {
+    let schedule: Schedule = "12:00=0.4".parse().unwrap();
+    assert_eq!(schedule.alpha(0.0), 0.4);
+    assert_eq!(schedule.alpha(18.0 * 60.0), 0.4);
+  }
+
+  #[test]
+  fn waits_for_the_next_time_around() {
+    assert_eq!(minutes_until(22.0 * 60.0, 21.0 * 60.0), 60.0);
+    assert_eq!(minutes_until(8.0 * 60.0, 22.0 * 60.0), 10.0 * 60.0);
+    assert_eq!(minutes_until(8.0 * 60.0, 8.0 * 60.0), 0.0);
+  }
+
+  #[test]
+  fn rejects_malformed_schedules() {
+    assert!("8=0.5".parse::<Schedule>().is_err());
+    assert!("24:00=0.5".parse::<Schedule>().is_err());
+    assert!("08:00=1.5".parse::<Schedule>().is_err());
+    assert!("08:00=0.1,08:00=0.2".parse::<Schedule>().is_err());
+  }
+}
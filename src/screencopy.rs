@@ -0,0 +1,250 @@
+//! `--capture-before-dim`, freezing a screenshot of each output under its overlay.
+//!
+//! The screenshot is taken with zwlr_screencopy_manager_v1 before the overlay is first shown,
+//! so the dimmed image stays as it was while the windows below keep changing.
+
+use smithay_client_toolkit::{
+  reexports::{
+    client::{
+      globals::GlobalList,
+      protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm::Format},
+      Connection, Dispatch, QueueHandle, WEnum,
+    },
+    protocols_wlr::screencopy::v1::client::{
+      zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+      zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+    },
+  },
+  shm::raw::RawPool,
+};
+
+use crate::{events, stats::PoolUsage, DimlandData};
+
+pub struct Screencopy {
+  manager: ZwlrScreencopyManagerV1,
+}
+
+impl Screencopy {
+  pub fn bind(globals: &GlobalList, qh: &QueueHandle<DimlandData>) -> Option<Self> {
+    // version 3 adds dmabufs, which would need to be declined first
+    let manager = globals
+      .bind(qh, 1..=2, ())
+      .inspect_err(|_| {
+        eprintln!("zwlr_screencopy_manager_v1 is not available, dimming without screenshots")
+      })
+      .ok()?;
+    Some(Self { manager })
+  }
+
+  /// Starts capturing `output` without the cursor.
+  pub fn capture(&self, qh: &QueueHandle<DimlandData>, output: &WlOutput) -> Capture {
+    Capture::Pending(PendingCapture {
+      frame: self.manager.capture_output(0, output, qh, ()),
+      target: None,
+      y_invert: false,
+    })
+  }
+}
+
+/// The screenshot of a view, while it is taken and once it is there.
+pub enum Capture {
+  Pending(PendingCapture),
+  Done(Underlay),
+}
+
+pub struct PendingCapture {
+  frame: ZwlrScreencopyFrameV1,
+  /// The buffer the compositor copies into, once it said which one it wants.
+  target: Option<CaptureTarget>,
+  y_invert: bool,
+}
+
+impl Drop for PendingCapture {
+  fn drop(&mut self) {
+    self.frame.destroy();
+  }
+}
+
+struct CaptureTarget {
+  pool: RawPool,
+  /// Counts the pool for `dimland stats` while it lives.
+  _usage: PoolUsage,
+  buffer: WlBuffer,
+  format: Format,
+  width: u32,
+  height: u32,
+  stride: u32,
+}
+
+impl Drop for CaptureTarget {
+  fn drop(&mut self) {
+    self.buffer.destroy();
+  }
+}
+
+impl CaptureTarget {
+  /// Reads the copied pixels, if they are in a format dimland can read.
+  fn underlay(&mut self, y_invert: bool) -> Option<Underlay> {
+    // where red, green and blue are within the little-endian bytes of a pixel
+    let order = match self.format {
+      Format::Argb8888 | Format::Xrgb8888 => [2, 1, 0],
+      Format::Abgr8888 | Format::Xbgr8888 => [0, 1, 2],
+      _ => return None,
+    };
+
+    let (width, height, stride) = (
+      self.width as usize,
+      self.height as usize,
+      self.stride as usize,
+    );
+    let data = self.pool.mmap();
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in 0..height {
+      let source = if y_invert { height - 1 - row } else { row };
+      let line = &data[source * stride..][..width * 4];
+      pixels.extend(line.chunks_exact(4).map(|pixel| order.map(|i| pixel[i])));
+    }
+    Some(Underlay {
+      width: self.width,
+      height: self.height,
+      pixels,
+    })
+  }
+}
+
+/// A captured output, as rgb pixels row by row from the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Underlay {
+  width: u32,
+  height: u32,
+  pixels: Vec<[u8; 3]>,
+}
+
+impl Underlay {
+  /// The color below a pixel of a buffer covering the same output, but maybe at another size.
+  pub fn sample(&self, x: u32, y: u32, width: u32, height: u32) -> [u8; 3] {
+    let x = x as u64 * self.width as u64 / width as u64;
+    let y = y as u64 * self.height as u64 / height as u64;
+    self.pixels[(y * self.width as u64 + x) as usize]
+  }
+}
+
+impl DimlandData {
+  /// Renders a view again with its screenshot, or without if there is none, and shows it.
+  fn finish_capture(&mut self, index: usize, underlay: Option<Underlay>) {
+    let view = &mut self.views[index];
+    view.capture = underlay.map(Capture::Done);
+    view.redraw(&self.settings);
+    if view.first_configure && view.buffer.is_some() {
+      view.draw(&self.qh, &self.settings);
+      view.first_configure = false;
+    }
+  }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for DimlandData {
+  fn event(
+    _: &mut Self,
+    manager: &ZwlrScreencopyManagerV1,
+    event: zwlr_screencopy_manager_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(manager, &event);
+  }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for DimlandData {
+  fn event(
+    data: &mut Self,
+    frame: &ZwlrScreencopyFrameV1,
+    event: zwlr_screencopy_frame_v1::Event,
+    _: &(),
+    _: &Connection,
+    qh: &QueueHandle<Self>,
+  ) {
+    events::log(frame, &event);
+    let Some(index) = data.views.iter().position(
+      |view| matches!(&view.capture, Some(Capture::Pending(pending)) if pending.frame == *frame),
+    ) else {
+      return;
+    };
+    let name = data
+      .output_state
+      .info(&data.views[index].output)
+      .and_then(|info| info.name)
+      .unwrap_or_else(|| "unknown".to_string());
+    let Some(Capture::Pending(pending)) = &mut data.views[index].capture else {
+      return;
+    };
+
+    match event {
+      zwlr_screencopy_frame_v1::Event::Buffer {
+        format: WEnum::Value(format),
+        width,
+        height,
+        stride,
+      } => {
+        // the compositor may offer more than one buffer type, the first shm one will do
+        if pending.target.is_some() {
+          return;
+        }
+        if !data.settings.buffer_fits(stride.div_ceil(4), height) {
+          eprintln!("the screenshot of {name} would be too large, dimming without it");
+          data.finish_capture(index, None);
+          return;
+        }
+
+        let mut pool = match RawPool::new(stride as usize * height as usize, &data.shm) {
+          Ok(pool) => pool,
+          Err(err) => {
+            eprintln!("can't allocate the screenshot of {name} ({err}), dimming without it");
+            data.finish_capture(index, None);
+            return;
+          }
+        };
+        let buffer = pool.create_buffer(
+          0,
+          width as i32,
+          height as i32,
+          stride as i32,
+          format,
+          (),
+          qh,
+        );
+        frame.copy(&buffer);
+        pending.target = Some(CaptureTarget {
+          _usage: data.settings.stats.map(&pool),
+          pool,
+          buffer,
+          format,
+          width,
+          height,
+          stride,
+        });
+      }
+      zwlr_screencopy_frame_v1::Event::Flags {
+        flags: WEnum::Value(flags),
+      } => pending.y_invert = flags.contains(Flags::YInvert),
+      zwlr_screencopy_frame_v1::Event::Ready { .. } => {
+        let y_invert = pending.y_invert;
+        let underlay = pending
+          .target
+          .as_mut()
+          .and_then(|target| target.underlay(y_invert));
+        if underlay.is_none() {
+          eprintln!(
+            "the screenshot of {name} is in a format dimland can't read, dimming without it"
+          );
+        }
+        data.finish_capture(index, underlay);
+      }
+      zwlr_screencopy_frame_v1::Event::Failed => {
+        eprintln!("can't take a screenshot of {name}, dimming without it");
+        data.finish_capture(index, None);
+      }
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,120 @@
+//! The surfaces the overlay is drawn on, layer surfaces or with `--fallback-window` fullscreen windows.
+
+use std::num::NonZeroU32;
+
+use smithay_client_toolkit::{
+  delegate_xdg_shell, delegate_xdg_window,
+  reexports::client::{
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    Connection, QueueHandle,
+  },
+  shell::{
+    wlr_layer::{LayerShell, LayerSurface},
+    xdg::{
+      window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
+      XdgShell,
+    },
+    WaylandSurface,
+  },
+};
+
+use crate::DimlandData;
+
+/// What the overlay surfaces are created with.
+pub enum DimlandShell {
+  Layer(LayerShell),
+  /// Fullscreen windows for compositors without layer shell,
+  /// which show up in task switchers and end up below other windows.
+  Window(XdgShell),
+}
+
+/// The surface of one view.
+pub enum OverlaySurface {
+  Layer(LayerSurface),
+  Window(Window),
+}
+
+impl OverlaySurface {
+  pub fn wl_surface(&self) -> &WlSurface {
+    match self {
+      OverlaySurface::Layer(layer) => layer.wl_surface(),
+      OverlaySurface::Window(window) => window.wl_surface(),
+    }
+  }
+
+  pub fn commit(&self) {
+    match self {
+      OverlaySurface::Layer(layer) => layer.commit(),
+      OverlaySurface::Window(window) => window.commit(),
+    }
+  }
+
+  pub fn layer(&self) -> Option<&LayerSurface> {
+    match self {
+      OverlaySurface::Layer(layer) => Some(layer),
+      OverlaySurface::Window(_) => None,
+    }
+  }
+
+  pub fn window(&self) -> Option<&Window> {
+    match self {
+      OverlaySurface::Layer(_) => None,
+      OverlaySurface::Window(window) => Some(window),
+    }
+  }
+}
+
+/// Creates a window that asks to cover `output`.
+pub fn create_window(
+  xdg_shell: &XdgShell,
+  surface: WlSurface,
+  qh: &QueueHandle<DimlandData>,
+  output: &WlOutput,
+) -> Window {
+  let window = xdg_shell.create_window(surface, WindowDecorations::None, qh);
+  window.set_title("dimland");
+  window.set_app_id("dimland");
+  window.set_fullscreen(Some(output));
+  window.commit();
+  window
+}
+
+impl WindowHandler for DimlandData {
+  fn request_close(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _window: &Window) {
+    self.exit = true;
+  }
+
+  fn configure(
+    &mut self,
+    _conn: &Connection,
+    qh: &QueueHandle<Self>,
+    window: &Window,
+    configure: WindowConfigure,
+    _serial: u32,
+  ) {
+    let Some(index) = self
+      .views
+      .iter()
+      .position(|view| view.surface.window() == Some(window))
+    else {
+      return;
+    };
+
+    // the size is up to us when the compositor leaves it out, so take the one of the output
+    let (width, height) = self.settings.overlay_size(
+      self
+        .output_info(&self.views[index].output)
+        .and_then(|info| info.logical_size),
+    );
+    let (new_width, new_height) = configure.new_size;
+    self.configure_view(
+      qh,
+      index,
+      new_width.map_or(width, NonZeroU32::get),
+      new_height.map_or(height, NonZeroU32::get),
+    );
+  }
+}
+
+delegate_xdg_shell!(DimlandData);
+delegate_xdg_window!(DimlandData);
@@ -0,0 +1,253 @@
+//! `--restore`, picking up the alpha, radius, toggle and output colors where the last run left them.
+//!
+//! The state lives at `$XDG_STATE_HOME/dimland/state.json` and is written a moment after it
+//! changes, so a ramp or a flood of commands costs one write. Temporary changes like
+//! `set --for` and blackouts are saved as what they go back to.
+
+use std::{
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::reexports::calloop::{
+  timer::{TimeoutAction, Timer},
+  RegistrationToken,
+};
+
+use crate::{
+  control::RuntimeState,
+  outputs::{OutputColor, OutputMatcher},
+  render::Radius,
+  DimlandArgs, DimlandData, DimlandSettings,
+};
+
+/// How long after a change the file gets written, with whatever the state is by then.
+const DELAY: Duration = Duration::from_secs(1);
+
+/// What the state file holds, in the words of the command line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+  pub alpha: f32,
+  /// Like `24` or `24x12`.
+  pub radius: String,
+  pub hidden: bool,
+  /// Colors of single outputs like `DP-1=#ffeedd`, as `--output-color` takes them.
+  #[serde(default)]
+  pub output_colors: Vec<String>,
+}
+
+impl SavedState {
+  pub fn of(settings: &DimlandSettings) -> Self {
+    let lasting = RuntimeState::lasting(settings);
+    Self {
+      alpha: lasting.alpha,
+      radius: lasting.radius.to_string(),
+      hidden: lasting.hidden,
+      output_colors: settings
+        .output_colors
+        .iter()
+        .map(OutputColor::to_string)
+        .chain(settings.remembered_colors.iter().filter_map(|remembered| {
+          let color = OutputColor {
+            matcher: OutputMatcher::Name(remembered.identity.name.clone()?),
+            color: remembered.color,
+          };
+          Some(color.to_string())
+        }))
+        .collect(),
+    }
+  }
+}
+
+/// A saved state that turned out to be valid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restored {
+  pub alpha: f32,
+  pub radius: Radius,
+  pub hidden: bool,
+  pub output_colors: Vec<OutputColor>,
+}
+
+impl TryFrom<SavedState> for Restored {
+  type Error = String;
+
+  fn try_from(state: SavedState) -> Result<Self, Self::Error> {
+    if !(0.0..=1.0).contains(&state.alpha) {
+      return Err(format!("{} is not an alpha from 0 to 1", state.alpha));
+    }
+    Ok(Self {
+      alpha: state.alpha,
+      radius: state.radius.parse()?,
+      hidden: state.hidden,
+      output_colors: state
+        .output_colors
+        .iter()
+        .map(|color| color.parse())
+        .collect::<Result<_, _>>()?,
+    })
+  }
+}
+
+/// `$XDG_STATE_HOME`, or `~/.local/state` without it.
+fn state_dir() -> Option<PathBuf> {
+  std::env::var_os("XDG_STATE_HOME")
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+}
+
+/// Where the state is kept.
+pub fn state_path() -> Option<PathBuf> {
+  Some(state_dir()?.join("dimland").join("state.json"))
+}
+
+fn parse(text: &str) -> Result<Restored, String> {
+  let state: SavedState = serde_json::from_str(text).map_err(|err| err.to_string())?;
+  Restored::try_from(state)
+}
+
+/// Reads the state of the last run, none if there is no file yet.
+pub fn load(path: &Path) -> Result<Option<Restored>, String> {
+  let text = match std::fs::read_to_string(path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => return Err(format!("can't read {} ({err})", path.display())),
+  };
+  parse(&text)
+    .map(Some)
+    .map_err(|err| format!("{} is not a valid state file ({err})", path.display()))
+}
+
+/// Writes the state next to the file first, so a crash halfway leaves the old one in place.
+fn save(path: &Path, state: &SavedState) -> std::io::Result<()> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let partial = path.with_extension("json.partial");
+  std::fs::write(&partial, serde_json::to_string_pretty(state)?)?;
+  std::fs::rename(partial, path)
+}
+
+/// The state file of `--restore` and what was last written to it.
+#[derive(Debug, Clone)]
+pub struct StateFile {
+  path: PathBuf,
+  saved: Option<SavedState>,
+  timer: Option<RegistrationToken>,
+}
+
+impl StateFile {
+  pub fn new(path: PathBuf) -> Self {
+    Self {
+      path,
+      saved: None,
+      timer: None,
+    }
+  }
+}
+
+impl DimlandSettings {
+  /// Takes over the state of the last run, except for what the command line gives explicitly.
+  pub fn restore(&mut self, args: &DimlandArgs, restored: Restored) {
+    // a blackout has its alpha and radius fixed
+    if args.alpha.is_none() && !args.blackout {
+      self.alpha = self
+        .alpha_clamp
+        .apply(restored.alpha.clamp(self.min_alpha, self.max_alpha));
+    }
+    if args.radius.is_none() && !args.blackout {
+      self.radius = restored.radius;
+    }
+    self.hidden = restored.hidden;
+    // the last color matching an output wins, so the ones on the command line go after
+    self.output_colors.splice(0..0, restored.output_colors);
+  }
+}
+
+impl DimlandData {
+  /// Writes the state file a moment from now if the state changed since it was last written,
+  /// called after every batch of events.
+  pub fn persist_state(&mut self) {
+    let state = SavedState::of(&self.settings);
+    let Some(file) = &mut self.settings.state_file else {
+      return;
+    };
+    if file.timer.is_some() || file.saved.as_ref() == Some(&state) {
+      return;
+    }
+
+    let timer = self
+      .loop_handle
+      .insert_source(Timer::from_duration(DELAY), |_, _, data| {
+        data.write_state();
+        TimeoutAction::Drop
+      })
+      .expect("can't start the state timer");
+    file.timer = Some(timer);
+  }
+
+  /// Writes the state right away, also on exit while a write is still due.
+  pub fn write_state(&mut self) {
+    let state = SavedState::of(&self.settings);
+    let Some(file) = &mut self.settings.state_file else {
+      return;
+    };
+    if let Some(timer) = file.timer.take() {
+      self.loop_handle.remove(timer);
+    }
+    if file.saved.as_ref() == Some(&state) {
+      return;
+    }
+    if let Err(err) = save(&file.path, &state) {
+      eprintln!("can't save the state to {} ({err})", file.path.display());
+    }
+    // a failed write isn't tried again until the state changes once more
+    file.saved = Some(state);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::render::Color;
+
+  #[test]
+  fn restores_what_was_saved() {
+    let restored =
+      parse(r##"{"alpha": 0.65, "radius": "24x12", "hidden": true, "output_colors": ["DP-1=#ffeedd", "HDMI-A-1=none"]}"##)
+        .unwrap();
+    assert_eq!(
+      restored,
+      Restored {
+        alpha: 0.65,
+        radius: Radius {
+          horizontal: 24,
+          vertical: 12
+        },
+        hidden: true,
+        output_colors: vec![
+          OutputColor {
+            matcher: OutputMatcher::Name("DP-1".to_string()),
+            color: Some(Color([0xFF, 0xEE, 0xDD])),
+          },
+          OutputColor {
+            matcher: OutputMatcher::Name("HDMI-A-1".to_string()),
+            color: None,
+          },
+        ],
+      }
+    );
+  }
+
+  #[test]
+  fn rejects_corrupt_state() {
+    assert!(parse("").is_err());
+    assert!(parse(r#"{"alpha": 0.65, "radius": "24""#).is_err());
+    assert!(parse(r#"{"alpha": 1.65, "radius": "24", "hidden": false}"#).is_err());
+    assert!(parse(r#"{"alpha": 0.65, "radius": "round", "hidden": false}"#).is_err());
+    assert!(parse(
+      r#"{"alpha": 0.65, "radius": "24", "hidden": false, "output_colors": ["DP-1"]}"#
+    )
+    .is_err());
+  }
+}
@@ -0,0 +1,153 @@
+//! `dimland stats` and `--stats`, what dimland did since it started.
+//!
+//! Every shm pool dimland maps is counted, the overlay buffers, screenshots and the 1x1
+//! buffers of bump and clock surfaces alike. Pools only grow, so a pool resized to a
+//! larger size counts with what it grew by.
+
+use std::{
+  cell::RefCell,
+  fmt::Display,
+  rc::Rc,
+  time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::shm::raw::RawPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimlandStats {
+  /// Buffers committed to views.
+  pub total_frames_drawn: u64,
+  /// Bytes of every shm pool mapped, summed up.
+  pub total_shm_allocated: u64,
+  /// The most bytes of shm mapped at the same time.
+  pub peak_shm_usage: usize,
+  pub start_time: Instant,
+  /// The bytes of shm mapped right now.
+  shm_usage: usize,
+}
+
+impl DimlandStats {
+  fn new(start_time: Instant) -> Self {
+    Self {
+      total_frames_drawn: 0,
+      total_shm_allocated: 0,
+      peak_shm_usage: 0,
+      start_time,
+      shm_usage: 0,
+    }
+  }
+
+  fn allocate(&mut self, bytes: usize) {
+    self.total_shm_allocated += bytes as u64;
+    self.shm_usage += bytes;
+    self.peak_shm_usage = self.peak_shm_usage.max(self.shm_usage);
+  }
+
+  fn uptime(&self) -> Duration {
+    self.start_time.elapsed()
+  }
+}
+
+impl Display for DimlandStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let seconds = self.uptime().as_secs();
+    writeln!(
+      f,
+      "uptime {}h {:02}m {:02}s",
+      seconds / 3600,
+      seconds / 60 % 60,
+      seconds % 60
+    )?;
+    writeln!(f, "frames drawn {}", self.total_frames_drawn)?;
+    writeln!(f, "shm allocated {}", mebibytes(self.total_shm_allocated))?;
+    write!(
+      f,
+      "peak shm usage {}",
+      mebibytes(self.peak_shm_usage as u64)
+    )
+  }
+}
+
+fn mebibytes(bytes: u64) -> String {
+  format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// The stats of the whole run, kept across reconnects.
+#[derive(Debug, Clone)]
+pub struct Stats(Rc<RefCell<DimlandStats>>);
+
+impl Default for Stats {
+  fn default() -> Self {
+    Self(Rc::new(RefCell::new(DimlandStats::new(Instant::now()))))
+  }
+}
+
+impl Stats {
+  pub fn get(&self) -> DimlandStats {
+    *self.0.borrow()
+  }
+
+  pub fn count_frame(&self) {
+    self.0.borrow_mut().total_frames_drawn += 1;
+  }
+
+  /// Counts a newly mapped pool until the returned usage is dropped along with it.
+  pub fn map(&self, pool: &RawPool) -> PoolUsage {
+    self.0.borrow_mut().allocate(pool.len());
+    PoolUsage {
+      stats: self.clone(),
+      bytes: pool.len(),
+    }
+  }
+}
+
+/// The part of the shm usage one pool takes up.
+#[derive(Debug)]
+pub struct PoolUsage {
+  stats: Stats,
+  bytes: usize,
+}
+
+impl PoolUsage {
+  /// Counts what the pool grew by since it was last counted.
+  pub fn grow(&mut self, pool: &RawPool) {
+    let grown = pool.len().saturating_sub(self.bytes);
+    self.stats.0.borrow_mut().allocate(grown);
+    self.bytes += grown;
+  }
+}
+
+impl Drop for PoolUsage {
+  fn drop(&mut self) {
+    self.stats.0.borrow_mut().shm_usage -= self.bytes;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn peak_is_the_most_at_once() {
+    let mut stats = DimlandStats::new(Instant::now());
+    stats.allocate(400);
+    stats.allocate(100);
+    stats.shm_usage -= 400;
+    stats.allocate(200);
+    assert_eq!(stats.total_shm_allocated, 700);
+    assert_eq!(stats.peak_shm_usage, 500);
+    assert_eq!(stats.shm_usage, 300);
+  }
+
+  #[test]
+  fn prints_uptime_and_sizes() {
+    let mut stats = DimlandStats::new(Instant::now() - Duration::from_secs(3725));
+    stats.total_frames_drawn = 42;
+    stats.allocate(3 * 1024 * 1024 / 2);
+    let printed = stats.to_string();
+    assert!(printed.starts_with("uptime 1h 02m 0"));
+    assert!(printed.contains("frames drawn 42\n"));
+    assert!(printed.contains("shm allocated 1.5 MiB\n"));
+    assert!(printed.ends_with("peak shm usage 1.5 MiB"));
+  }
+}
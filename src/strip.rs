@@ -0,0 +1,114 @@
+//! The reading strip, a horizontal band left undimmed that can be moved up and down.
+//!
+//! The overlay lets the pointer through, so it can't follow the cursor by itself,
+//! `dimland strip --y` moves it instead, which anything knowing where the cursor is can send.
+//! Moving it repaints only the rows that changed, from where it was to where it is.
+
+use crate::{DimlandData, DimlandSettings};
+
+pub const DEFAULT_HEIGHT: u32 = 120;
+/// Pixels across which the edges of the strip blend into the dim.
+const SOFTNESS: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strip {
+  /// The middle of the strip in pixels from the top, the middle of the overlay until moved.
+  pub y: Option<u32>,
+  pub height: u32,
+}
+
+impl Strip {
+  /// The first row of the strip, kept within the overlay.
+  fn top(self, full: u32) -> u32 {
+    let room = full.saturating_sub(self.height);
+    match self.y {
+      Some(y) => y.saturating_sub(self.height / 2).min(room),
+      None => room / 2,
+    }
+  }
+
+  /// How much the strip lets through at a row, from 0 outside to 1 inside,
+  /// blending across its soft edges.
+  pub fn opening(self, y: u32, full: u32) -> f32 {
+    let top = self.top(full) as f32;
+    let row = y as f32 + 0.5;
+    let depth = (row - top).min(top + self.height as f32 - row);
+    (depth / SOFTNESS as f32 + 0.5).clamp(0.0, 1.0)
+  }
+}
+
+impl std::fmt::Display for Strip {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.y {
+      Some(y) => write!(f, "{y} height {}", self.height),
+      None => write!(f, "middle height {}", self.height),
+    }
+  }
+}
+
+impl DimlandSettings {
+  /// The strip to leave undimmed right now, none during a blackout.
+  pub fn reading_strip(&self) -> Option<Strip> {
+    if self.blacked_out.is_some() {
+      return None;
+    }
+    self.strip
+  }
+}
+
+impl DimlandData {
+  /// Moves the strip or changes its height, putting it up with the last height if there is none.
+  pub fn move_strip(&mut self, y: Option<u32>, height: Option<u32>) {
+    let mut strip = self.settings.strip.unwrap_or(Strip {
+      y: None,
+      height: self.settings.strip_height,
+    });
+    strip.y = y.or(strip.y);
+    strip.height = height.unwrap_or(strip.height);
+    self.settings.strip_height = strip.height;
+    if self.settings.strip == Some(strip) {
+      return;
+    }
+
+    self.settings.strip = Some(strip);
+    self.settings.watchers.emit(&format!("strip {strip}"));
+    // the strip isn't part of what views compare to know whether they are up to date
+    self.repaint_all();
+  }
+
+  pub fn remove_strip(&mut self) -> Result<(), String> {
+    self.settings.strip.take().ok_or("there is no strip")?;
+    self.settings.watchers.emit("strip off");
+    self.repaint_all();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strip(y: Option<u32>, height: u32) -> Strip {
+    Strip { y, height }
+  }
+
+  #[test]
+  fn opens_around_its_middle() {
+    let strip = strip(Some(500), 100);
+    assert_eq!(strip.opening(500, 1000), 1.0);
+    assert_eq!(strip.opening(420, 1000), 0.0);
+    assert_eq!(strip.opening(580, 1000), 0.0);
+    // halfway through the soft edge right at the top
+    assert_eq!(strip.opening(450, 1000), 0.5 + 0.5 / SOFTNESS as f32);
+  }
+
+  #[test]
+  fn stays_on_screen() {
+    assert_eq!(strip(Some(0), 100).top(1000), 0);
+    assert_eq!(strip(Some(990), 100).top(1000), 900);
+    assert_eq!(strip(None, 100).top(1000), 450);
+    // taller than the overlay opens all of it
+    assert_eq!(strip(Some(10), 2000).top(1000), 0);
+    assert_eq!(strip(Some(10), 2000).opening(500, 1000), 1.0);
+  }
+}
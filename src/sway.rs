@@ -0,0 +1,151 @@
+//! `--sway-focus`, leaving the output with the focused sway workspace undimmed.
+//!
+//! Sway is followed over its i3 compatible ipc socket on a thread of its own,
+//! which sends the workspaces to the event loop whenever they change.
+
+use std::{
+  collections::BTreeMap,
+  ffi::OsStr,
+  io::{self, Read, Write},
+  os::unix::net::UnixStream,
+  thread,
+};
+
+use serde::Deserialize;
+use smithay_client_toolkit::reexports::calloop::{
+  channel::{self, Event, Sender},
+  LoopHandle,
+};
+
+use crate::DimlandData;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+
+/// The workspace shown on each output and the output with the focus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Workspaces {
+  pub visible: BTreeMap<String, String>,
+  pub focused: Option<String>,
+}
+
+/// A workspace as sway reports it, leaving out what dimland doesn't need.
+#[derive(Debug, Deserialize)]
+struct Workspace {
+  name: String,
+  output: String,
+  visible: bool,
+  focused: bool,
+}
+
+impl FromIterator<Workspace> for Workspaces {
+  fn from_iter<T: IntoIterator<Item = Workspace>>(workspaces: T) -> Self {
+    let mut result = Workspaces::default();
+    for workspace in workspaces {
+      if workspace.focused {
+        result.focused = Some(workspace.output.clone());
+      }
+      if workspace.visible {
+        result.visible.insert(workspace.output, workspace.name);
+      }
+    }
+    result
+  }
+}
+
+/// Starts following sway, giving up with a message if it isn't running.
+pub fn watch(handle: &LoopHandle<DimlandData>) {
+  let Some(socket) = std::env::var_os("SWAYSOCK") else {
+    eprintln!("SWAYSOCK is not set, ignoring --sway-focus");
+    return;
+  };
+
+  let (sender, channel) = channel::channel();
+  thread::Builder::new()
+    .name("sway-ipc".to_string())
+    .spawn(move || {
+      if let Err(err) = follow(&socket, &sender) {
+        eprintln!("lost sway ({err}), dimming all outputs again");
+        sender.send(Workspaces::default()).ok();
+      }
+    })
+    .expect("can't start the sway ipc thread");
+
+  handle
+    .insert_source(channel, |event, _, data| {
+      if let Event::Msg(workspaces) = event {
+        data.set_workspaces(workspaces);
+      }
+    })
+    .expect("can't listen to sway");
+}
+
+/// Sends the workspaces after every workspace or window event that changed them,
+/// until sway goes away or the event loop does.
+fn follow(socket: &OsStr, sender: &Sender<Workspaces>) -> io::Result<()> {
+  // replies to queries would get mixed up with the events on one connection
+  let mut events = UnixStream::connect(socket)?;
+  let mut queries = UnixStream::connect(socket)?;
+  send(&mut events, SUBSCRIBE, br#"["workspace","window"]"#)?;
+  receive(&mut events)?;
+
+  let mut last = None;
+  loop {
+    send(&mut queries, GET_WORKSPACES, b"")?;
+    let workspaces: Vec<Workspace> =
+      serde_json::from_slice(&receive(&mut queries)?).map_err(io::Error::other)?;
+    let workspaces: Workspaces = workspaces.into_iter().collect();
+    if last.as_ref() != Some(&workspaces) {
+      if sender.send(workspaces.clone()).is_err() {
+        return Ok(());
+      }
+      last = Some(workspaces);
+    }
+    receive(&mut events)?;
+  }
+}
+
+fn send(stream: &mut UnixStream, kind: u32, payload: &[u8]) -> io::Result<()> {
+  let mut message = MAGIC.to_vec();
+  message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+  message.extend_from_slice(&kind.to_ne_bytes());
+  message.extend_from_slice(payload);
+  stream.write_all(&message)
+}
+
+/// Reads the payload of the next message, replies and events alike.
+fn receive(stream: &mut UnixStream) -> io::Result<Vec<u8>> {
+  let mut header = [0; 14];
+  stream.read_exact(&mut header)?;
+  if &header[..6] != MAGIC {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "not an i3-ipc message",
+    ));
+  }
+  let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+  let mut payload = vec![0; length as usize];
+  stream.read_exact(&mut payload)?;
+  Ok(payload)
+}
+
+impl DimlandData {
+  /// Undims the output with the focused workspace and dims the others.
+  fn set_workspaces(&mut self, workspaces: Workspaces) {
+    let Some(current) = &self.settings.workspaces else {
+      return;
+    };
+    if *current == workspaces {
+      return;
+    }
+
+    if current.focused != workspaces.focused {
+      if let Some(output) = &workspaces.focused {
+        self.settings.watchers.emit(&format!("focus {output}"));
+      }
+    }
+    self.settings.workspaces = Some(workspaces);
+    self.refresh_alpha_factors();
+  }
+}
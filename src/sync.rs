@@ -0,0 +1,91 @@
+//! Committing the views of all outputs in one burst, and `--surface-sync-group` on top.
+//!
+//! Views hold their commits while repainting, the event loop then commits all of them
+//! together after each dispatch, in the order of `views`, before anything is flushed.
+//! So every output gets the new alpha in the same write to the socket, and a compositor
+//! handling them in one go shows them on the same frame as far as the outputs' refresh allows.
+//!
+//! With `--surface-sync-group` a `wl_display.sync` after every burst tells when the
+//! compositor got through it, and the next burst waits for that.
+
+use smithay_client_toolkit::reexports::client::{
+  globals::GlobalList,
+  protocol::{
+    wl_callback::{self, WlCallback},
+    wl_display::WlDisplay,
+  },
+  Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{events, DimlandData};
+
+pub struct SyncGroup {
+  display: WlDisplay,
+  /// The last burst of commits sent, counting up from 0 for none yet.
+  transaction_id: u64,
+  /// The last burst the compositor is done with.
+  settled_id: u64,
+}
+
+impl SyncGroup {
+  pub fn new(globals: &GlobalList) -> Self {
+    let backend = globals
+      .registry()
+      .backend()
+      .upgrade()
+      .expect("the registry outlives its connection");
+    Self {
+      display: Connection::from_backend(backend).display(),
+      transaction_id: 0,
+      settled_id: 0,
+    }
+  }
+}
+
+impl DimlandData {
+  /// Commits every view holding a commit at once, called after each dispatch of the event loop.
+  ///
+  /// With `--surface-sync-group` nothing goes out while the last burst is still in flight.
+  pub fn commit_transaction(&mut self) {
+    if !self.views.iter().any(|view| view.commit_held) {
+      return;
+    }
+    // what piles up meanwhile goes out once the compositor answered
+    if let Some(group) = &self.sync_group {
+      if group.settled_id < group.transaction_id {
+        return;
+      }
+    }
+
+    // all attaches happened while repainting, so only the commits are left and go out back to back
+    for view in self.views.iter_mut().filter(|view| view.commit_held) {
+      view.commit_held = false;
+      view.commit();
+      self.settings.stats.count_frame();
+    }
+    if let Some(group) = &mut self.sync_group {
+      group.transaction_id += 1;
+      group.display.sync(&self.qh, group.transaction_id);
+    }
+  }
+}
+
+impl Dispatch<WlCallback, u64> for DimlandData {
+  fn event(
+    data: &mut Self,
+    callback: &WlCallback,
+    event: wl_callback::Event,
+    transaction_id: &u64,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(callback, &event);
+    let wl_callback::Event::Done { .. } = event else {
+      return;
+    };
+    if let Some(group) = &mut data.sync_group {
+      group.settled_id = group.settled_id.max(*transaction_id);
+    }
+    data.commit_transaction();
+  }
+}
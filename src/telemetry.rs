@@ -0,0 +1,184 @@
+//! Presentation feedback for `--telemetry`, printed one line per frame.
+
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::{
+  client::{
+    globals::GlobalList, protocol::wl_surface::WlSurface, Connection, Dispatch, QueueHandle, WEnum,
+  },
+  protocols::wp::presentation_time::client::{
+    wp_presentation::{self, WpPresentation},
+    wp_presentation_feedback::{self, Kind, WpPresentationFeedback},
+  },
+};
+
+use crate::{events, DimlandData};
+
+/// `CLOCK_MONOTONIC`, the clock nearly every compositor presents with.
+const CLOCK_MONOTONIC: u32 = 1;
+
+pub struct Telemetry {
+  presentation: WpPresentation,
+  /// The clock presentation timestamps are in, sent right after binding.
+  clock_id: Option<u32>,
+  discarded: u64,
+}
+
+/// Requests feedback for the commits of one surface.
+#[derive(Clone)]
+pub struct Tracker {
+  presentation: WpPresentation,
+  qh: QueueHandle<DimlandData>,
+  output: String,
+}
+
+/// What a feedback object needs to report once its frame is presented.
+pub struct Frame {
+  output: String,
+  committed: Instant,
+}
+
+impl Telemetry {
+  pub fn bind(globals: &GlobalList, qh: &QueueHandle<DimlandData>) -> Option<Self> {
+    let presentation = globals
+      .bind(qh, 1..=1, ())
+      .inspect_err(|_| eprintln!("wp_presentation is not available, there is no telemetry"))
+      .ok()?;
+    Some(Self {
+      presentation,
+      clock_id: None,
+      discarded: 0,
+    })
+  }
+
+  pub fn tracker(&self, qh: &QueueHandle<DimlandData>, output: String) -> Tracker {
+    Tracker {
+      presentation: self.presentation.clone(),
+      qh: qh.clone(),
+      output,
+    }
+  }
+
+  /// How long ago the compositor presented at `presented`, if its clock can be read.
+  fn age(&self, presented: Duration) -> Option<Duration> {
+    if self.clock_id != Some(CLOCK_MONOTONIC) {
+      return None;
+    }
+
+    let now = rustix::time::clock_gettime(rustix::time::ClockId::Monotonic);
+    let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+    Some(now.saturating_sub(presented))
+  }
+}
+
+impl Tracker {
+  /// Asks for feedback on the next commit of `surface`, call it right before committing.
+  pub fn track(&self, surface: &WlSurface) {
+    self.presentation.feedback(
+      surface,
+      &self.qh,
+      Frame {
+        output: self.output.clone(),
+        committed: Instant::now(),
+      },
+    );
+  }
+}
+
+impl Dispatch<WpPresentation, ()> for DimlandData {
+  fn event(
+    data: &mut Self,
+    presentation: &WpPresentation,
+    event: wp_presentation::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(presentation, &event);
+    if let wp_presentation::Event::ClockId { clk_id } = event {
+      if let Some(telemetry) = &mut data.telemetry {
+        telemetry.clock_id = Some(clk_id);
+      }
+    }
+  }
+}
+
+impl Dispatch<WpPresentationFeedback, Frame> for DimlandData {
+  fn event(
+    data: &mut Self,
+    feedback: &WpPresentationFeedback,
+    event: wp_presentation_feedback::Event,
+    frame: &Frame,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    events::log(feedback, &event);
+    let Some(telemetry) = &mut data.telemetry else {
+      return;
+    };
+
+    match event {
+      wp_presentation_feedback::Event::Presented {
+        tv_sec_hi,
+        tv_sec_lo,
+        tv_nsec,
+        refresh,
+        seq_hi,
+        seq_lo,
+        flags,
+      } => {
+        let presented = Duration::new(((tv_sec_hi as u64) << 32) | tv_sec_lo as u64, tv_nsec);
+        let sequence = ((seq_hi as u64) << 32) | seq_lo as u64;
+        // the commit happened this long before now, the presentation this long
+        let latency = telemetry
+          .age(presented)
+          .map(|age| frame.committed.elapsed().saturating_sub(age));
+
+        println!(
+          "presented output={} seq={} time={}.{:09} refresh={}ns latency={} flags={} discarded={}",
+          frame.output,
+          sequence,
+          presented.as_secs(),
+          presented.subsec_nanos(),
+          refresh,
+          latency.map_or("unknown".to_string(), |latency| format!(
+            "{:.3}ms",
+            latency.as_secs_f64() * 1000.0
+          )),
+          format_flags(flags),
+          telemetry.discarded,
+        );
+      }
+      wp_presentation_feedback::Event::Discarded => {
+        telemetry.discarded += 1;
+        println!(
+          "discarded output={} discarded={}",
+          frame.output, telemetry.discarded
+        );
+      }
+      _ => {}
+    }
+  }
+}
+
+fn format_flags(flags: WEnum<Kind>) -> String {
+  let WEnum::Value(flags) = flags else {
+    return "unknown".to_string();
+  };
+
+  let names: Vec<&str> = [
+    (Kind::Vsync, "vsync"),
+    (Kind::HwClock, "hw_clock"),
+    (Kind::HwCompletion, "hw_completion"),
+    (Kind::ZeroCopy, "zero_copy"),
+  ]
+  .into_iter()
+  .filter(|(flag, _)| flags.contains(*flag))
+  .map(|(_, name)| name)
+  .collect();
+
+  match names.is_empty() {
+    true => "none".to_string(),
+    false => names.join(","),
+  }
+}
@@ -0,0 +1,56 @@
+//! `--follow-theme`, tinting the overlay white when GTK is set to prefer dark themes.
+//!
+//! Only the GTK settings files are read, the color scheme of the settings portal is not queried.
+
+use std::fs;
+
+use crate::profiles::config_dir;
+
+/// The key GTK stores the dark theme preference under, in the `[Settings]` section.
+const PREFER_DARK: &str = "gtk-application-prefer-dark-theme";
+
+/// Whether the GTK 4 or else GTK 3 settings ask for dark themes.
+pub fn prefers_dark() -> bool {
+  let Some(config_dir) = config_dir() else {
+    return false;
+  };
+  ["gtk-4.0", "gtk-3.0"]
+    .iter()
+    .filter_map(|gtk| fs::read_to_string(config_dir.join(gtk).join("settings.ini")).ok())
+    .find_map(|ini| prefer_dark(&ini))
+    .unwrap_or(false)
+}
+
+/// Reads the dark theme preference from a GTK `settings.ini`, if it is set.
+fn prefer_dark(ini: &str) -> Option<bool> {
+  let mut in_settings = false;
+  for line in ini.lines().map(str::trim) {
+    if line.starts_with('[') {
+      in_settings = line == "[Settings]";
+      continue;
+    }
+    let Some((key, value)) = line.split_once('=') else {
+      continue;
+    };
+    if in_settings && key.trim() == PREFER_DARK {
+      return Some(matches!(value.trim(), "1" | "true" | "TRUE" | "True"));
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_the_settings_section_only() {
+    let ini = "[Other]\ngtk-application-prefer-dark-theme=1\n\n[Settings]\ngtk-theme-name=Adwaita\ngtk-application-prefer-dark-theme = true\n";
+    assert_eq!(prefer_dark(ini), Some(true));
+    assert_eq!(
+      prefer_dark("[Settings]\ngtk-application-prefer-dark-theme=0\n"),
+      Some(false)
+    );
+    assert_eq!(prefer_dark("[Settings]\ngtk-theme-name=Adwaita\n"), None);
+  }
+}
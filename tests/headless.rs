@@ -0,0 +1,278 @@
+//! Runs dimland against a headless sway and looks at what ends up on screen.
+//!
+//! These need `sway`, `swaymsg` and `grim` in PATH, so they are ignored by
+//! default. Run them with `cargo test -- --ignored`.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  process::{Child, Command, Output, Stdio},
+  thread::sleep,
+  time::{Duration, Instant},
+};
+
+/// The solid background sway draws below dimland.
+const BACKGROUND: u8 = 0xff;
+
+struct Sway {
+  process: Child,
+  runtime_dir: PathBuf,
+  wayland_display: String,
+  ipc_socket: PathBuf,
+}
+
+impl Sway {
+  fn start(name: &str) -> Self {
+    let runtime_dir = std::env::temp_dir().join(format!("dimland-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&runtime_dir);
+    fs::create_dir_all(&runtime_dir).unwrap();
+    let config = runtime_dir.join("config");
+    fs::write(
+      &config,
+      "output * bg #ffffff solid_color\noutput * mode 640x480\n",
+    )
+    .unwrap();
+
+    let process = Command::new("sway")
+      .arg("--config")
+      .arg(&config)
+      .env("XDG_RUNTIME_DIR", &runtime_dir)
+      .env("WLR_BACKENDS", "headless")
+      .env("WLR_RENDERER", "pixman")
+      .env("WLR_LIBINPUT_NO_DEVICES", "1")
+      .env_remove("WAYLAND_DISPLAY")
+      .env_remove("SWAYSOCK")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+      .expect("sway is not installed");
+
+    let (wayland_display, ipc_socket) = wait_for(|| {
+      let entries: Vec<PathBuf> = fs::read_dir(&runtime_dir)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+      let display = entries.iter().find(|path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        name.starts_with("wayland-") && !name.ends_with(".lock")
+      })?;
+      let ipc = entries.iter().find(|path| {
+        path
+          .file_name()
+          .unwrap()
+          .to_string_lossy()
+          .starts_with("sway-ipc.")
+      })?;
+      Some((
+        display.file_name()?.to_string_lossy().into_owned(),
+        ipc.clone(),
+      ))
+    });
+
+    Self {
+      process,
+      runtime_dir,
+      wayland_display,
+      ipc_socket,
+    }
+  }
+
+  fn dimland(&self, args: &[&str]) -> Dimland {
+    Dimland(
+      Command::new(env!("CARGO_BIN_EXE_dimland"))
+        .args(args)
+        .env("XDG_RUNTIME_DIR", &self.runtime_dir)
+        .env("WAYLAND_DISPLAY", &self.wayland_display)
+        .spawn()
+        .unwrap(),
+    )
+  }
+
+  fn swaymsg(&self, args: &[&str]) -> Output {
+    let output = Command::new("swaymsg")
+      .arg("--socket")
+      .arg(&self.ipc_socket)
+      .args(args)
+      .output()
+      .expect("swaymsg is not installed");
+    assert!(output.status.success(), "swaymsg {args:?} failed");
+    output
+  }
+
+  fn outputs(&self) -> Vec<String> {
+    let output = self.swaymsg(&["-t", "get_outputs", "--raw"]);
+    let outputs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    outputs
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|output| output["name"].as_str().unwrap().to_string())
+      .collect()
+  }
+
+  /// Grabs the given output and returns its size and the red channel of every pixel.
+  fn screenshot(&self, output: &str) -> (u32, u32, Vec<u8>) {
+    let path = self.runtime_dir.join(format!("{output}.png"));
+    let status = Command::new("grim")
+      .arg("-t")
+      .arg("png")
+      .arg("-o")
+      .arg(output)
+      .arg(&path)
+      .env("XDG_RUNTIME_DIR", &self.runtime_dir)
+      .env("WAYLAND_DISPLAY", &self.wayland_display)
+      .status()
+      .expect("grim is not installed");
+    assert!(status.success(), "grim failed on {output}");
+    read_red(&path)
+  }
+}
+
+impl Drop for Sway {
+  fn drop(&mut self) {
+    let _ = self.process.kill();
+    let _ = self.process.wait();
+    let _ = fs::remove_dir_all(&self.runtime_dir);
+  }
+}
+
+/// A running dimland, killed when dropped.
+struct Dimland(Child);
+
+impl Dimland {
+  fn is_running(&mut self) -> bool {
+    self.0.try_wait().unwrap().is_none()
+  }
+}
+
+impl Drop for Dimland {
+  fn drop(&mut self) {
+    let _ = self.0.kill();
+    let _ = self.0.wait();
+  }
+}
+
+fn read_red(path: &Path) -> (u32, u32, Vec<u8>) {
+  let decoder = png::Decoder::new(std::io::BufReader::new(fs::File::open(path).unwrap()));
+  let mut reader = decoder.read_info().unwrap();
+  let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+  let info = reader.next_frame(&mut pixels).unwrap();
+  let channels = info.color_type.samples();
+  let red = pixels[..info.buffer_size()]
+    .chunks_exact(channels)
+    .map(|pixel| pixel[0])
+    .collect();
+  (info.width, info.height, red)
+}
+
+fn wait_for<T>(mut check: impl FnMut() -> Option<T>) -> T {
+  let start = Instant::now();
+  loop {
+    if let Some(value) = check() {
+      return value;
+    }
+    assert!(
+      start.elapsed() < Duration::from_secs(10),
+      "timed out waiting for sway"
+    );
+    sleep(Duration::from_millis(50));
+  }
+}
+
+/// Waits until the middle of the output has the expected brightness.
+fn wait_for_center(sway: &Sway, output: &str, expected: u8) -> (u32, u32, Vec<u8>) {
+  wait_for(|| {
+    let (width, height, red) = sway.screenshot(output);
+    let center = red[(height / 2 * width + width / 2) as usize];
+    (center.abs_diff(expected) <= 2).then_some((width, height, red))
+  })
+}
+
+#[test]
+#[ignore = "needs sway, swaymsg and grim"]
+fn dims_every_output() {
+  let sway = Sway::start("dims");
+  let _dimland = sway.dimland(&["--alpha", "0.5"]);
+
+  for output in sway.outputs() {
+    let (width, height, red) = wait_for_center(&sway, &output, BACKGROUND / 2 + 1);
+    // no 0x0 buffers: the whole output is covered, edges included
+    for (x, y) in [
+      (0, 0),
+      (width - 1, 0),
+      (0, height - 1),
+      (width - 1, height - 1),
+    ] {
+      assert_eq!(
+        red[(y * width + x) as usize],
+        BACKGROUND / 2 + 1,
+        "{output} at {x},{y}"
+      );
+    }
+  }
+}
+
+#[test]
+#[ignore = "needs sway, swaymsg and grim"]
+fn corners_are_opaque() {
+  let sway = Sway::start("corners");
+  let _dimland = sway.dimland(&["--alpha", "0.5", "--radius", "40"]);
+
+  let output = sway.outputs().remove(0);
+  let (width, height, red) = wait_for_center(&sway, &output, BACKGROUND / 2 + 1);
+  for (x, y) in [
+    (0, 0),
+    (width - 1, 0),
+    (0, height - 1),
+    (width - 1, height - 1),
+  ] {
+    assert_eq!(red[(y * width + x) as usize], 0, "corner at {x},{y}");
+  }
+}
+
+#[test]
+#[ignore = "needs sway, swaymsg and grim"]
+fn elliptical_corners() {
+  let sway = Sway::start("ellipse");
+  let _dimland = sway.dimland(&["--alpha", "0.5", "--radius", "80x20"]);
+
+  let output = sway.outputs().remove(0);
+  let (width, _, red) = wait_for_center(&sway, &output, BACKGROUND / 2 + 1);
+  let at = |x: u32, y: u32| red[(y * width + x) as usize];
+  // far out along the wide axis, a circle of either radius wouldn't reach here
+  assert_eq!(at(30, 0), 0);
+  assert_eq!(at(width - 31, 0), 0);
+  // inside the ellipse
+  assert_eq!(at(60, 5), BACKGROUND / 2 + 1);
+}
+
+#[test]
+#[ignore = "needs sway, swaymsg and grim"]
+fn flat_radius_has_no_corners() {
+  let sway = Sway::start("flat");
+  let _dimland = sway.dimland(&["--alpha", "0.5", "--radius", "80x0"]);
+
+  let output = sway.outputs().remove(0);
+  let (_, _, red) = wait_for_center(&sway, &output, BACKGROUND / 2 + 1);
+  assert_eq!(red[0], BACKGROUND / 2 + 1);
+}
+
+#[test]
+#[ignore = "needs sway, swaymsg and grim"]
+fn views_follow_hotplug() {
+  let sway = Sway::start("hotplug");
+  let mut dimland = sway.dimland(&["--alpha", "0.5"]);
+
+  let first = sway.outputs().remove(0);
+  wait_for_center(&sway, &first, BACKGROUND / 2 + 1);
+
+  sway.swaymsg(&["create_output"]);
+  let added = wait_for(|| sway.outputs().into_iter().find(|output| *output != first));
+  wait_for_center(&sway, &added, BACKGROUND / 2 + 1);
+
+  sway.swaymsg(&["output", &added, "unplug"]);
+  wait_for(|| (!sway.outputs().contains(&added)).then_some(()));
+
+  assert!(dimland.is_running(), "dimland died on unplug");
+  wait_for_center(&sway, &first, BACKGROUND / 2 + 1);
+}